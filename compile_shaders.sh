@@ -3,3 +3,4 @@
 cargo build -p evn_shaderc --release
 
 ./target/release/evn_shaderc -i ./evn/src/shaders/ -o ./resources/closed/shaders/
+./target/release/evn_shaderc -i ./evn_engine/src/shaders/ -o ./resources/closed/shaders/
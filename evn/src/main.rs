@@ -1,10 +1,27 @@
-use evn_engine::prelude::*;
+use evn_engine::{
+    components::{Camera, Material, Translation3},
+    prelude::*,
+};
+use nalgebra::geometry;
+use specs::Builder;
 
 fn main() {
     let mut game = Game::new(
         "Indev",
-        |_world| {
+        |world| {
             // register components here
+
+            world
+                .create_entity()
+                .with(Translation3(geometry::Translation3::new(0.0f32, 0.0, 0.0)))
+                .with(Material::new("shader_normal"))
+                .build();
+
+            world
+                .create_entity()
+                .with(Translation3(geometry::Translation3::new(0.0f32, 0.0, 2.0)))
+                .with(Camera::perspective(std::f32::consts::FRAC_PI_3, 0.1, 100.0))
+                .build();
         },
         |dispatcher| {
             // add systems here
@@ -22,6 +39,18 @@ fn main() {
                     "shaders/normal.vert.spv",
                     "shaders/normal.frag.spv",
                 )
+                // the post chain in config.yml
+                .with_post_shader(
+                    "post_bloom_threshold",
+                    "shaders/post_bloom_threshold.frag.spv",
+                )
+                .with_post_shader("post_blur", "shaders/post_blur.frag.spv")
+                .with_post_shader("post_bloom_combine", "shaders/post_bloom_combine.frag.spv")
+                .with_post_shader("post_tonemap", "shaders/post_tonemap.frag.spv")
+                .with_post_shader("post_color_grade", "shaders/post_color_grade.frag.spv")
+                .with_post_shader("post_gamma", "shaders/post_gamma.frag.spv")
+                .with_post_shader("post_fxaa", "shaders/post_fxaa.frag.spv")
+                .with_texture("color_lut", "textures/color_lut.png")
         },
         |window_builder| {
             window_builder
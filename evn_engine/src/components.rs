@@ -1,4 +1,5 @@
 use nalgebra::{geometry, Real};
+use nalgebra_glm as glm;
 use specs::{storage::*, Component, World};
 use specs_derive::Component;
 
@@ -14,6 +15,11 @@ pub fn register(world: &mut World) {
 
     world.register::<Rotation2<f32>>();
     world.register::<Rotation2<f64>>();
+
+    world.register::<Camera>();
+    world.register::<Material>();
+    world.register::<Sprite>();
+    world.register::<Text>();
 }
 
 #[derive(Component)]
@@ -25,3 +31,210 @@ pub struct Translation3<T: Real>(pub geometry::Translation3<T>);
 pub struct Rotation2<T: Real>(pub geometry::Rotation2<T>);
 #[derive(Component)]
 pub struct Rotation3<T: Real>(pub geometry::Rotation3<T>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    // vertical field of view in radians
+    Perspective { fov_y: f32 },
+    // visible height in world units
+    Orthographic { height: f32 },
+}
+
+// normalized window coordinates, (0, 0) is the top left corner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        }
+    }
+}
+
+// Looks down -Z with +Y up, positioned by the Translation3<f32>/Rotation3<f32>
+// of its entity. The first active camera is rendered from.
+#[derive(Component, Debug, Clone)]
+#[storage(HashMapStorage)]
+pub struct Camera {
+    pub projection: Projection,
+    pub near: f32,
+    pub far: f32,
+    pub viewport: Viewport,
+    pub active: bool,
+}
+
+impl Camera {
+    pub fn perspective(fov_y: f32, near: f32, far: f32) -> Self {
+        Camera {
+            projection: Projection::Perspective { fov_y },
+            near,
+            far,
+            viewport: Viewport::default(),
+            active: true,
+        }
+    }
+
+    pub fn orthographic(height: f32, near: f32, far: f32) -> Self {
+        Camera {
+            projection: Projection::Orthographic { height },
+            near,
+            far,
+            viewport: Viewport::default(),
+            active: true,
+        }
+    }
+
+    // Vulkan clip space: depth 0..1, Y pointing down
+    pub fn projection_matrix(&self, aspect: f32) -> glm::Mat4 {
+        let mut projection = match self.projection {
+            Projection::Perspective { fov_y } => {
+                glm::perspective_rh_zo(aspect, fov_y, self.near, self.far)
+            }
+            Projection::Orthographic { height } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * aspect;
+
+                glm::ortho_rh_zo(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.near,
+                    self.far,
+                )
+            }
+        };
+
+        projection[(1, 1)] *= -1.0;
+        projection
+    }
+}
+
+// Entities are only drawn with a Material, `pipeline` is the name of a registered
+// pipeline (every shader also gets a default pipeline under its own name)
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Material {
+    pub pipeline: String,
+}
+
+impl Material {
+    pub fn new(pipeline: impl AsRef<str>) -> Self {
+        Material {
+            pipeline: pipeline.as_ref().to_owned(),
+        }
+    }
+}
+
+// normalized texture coordinates, (0, 0) is the top left corner of the texture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for UvRect {
+    fn default() -> Self {
+        UvRect {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        }
+    }
+}
+
+// A textured quad positioned by the Translation2<f32>/Rotation2<f32> of its entity.
+// Sprites are drawn after everything else, by layer and then by texture, without depth testing.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Sprite {
+    // name of a texture resource
+    pub texture: String,
+    pub uv: UvRect,
+    // multiplied with the texture color
+    pub tint: glm::Vec4,
+    // lower layers are drawn first
+    pub layer: i32,
+    // in world units
+    pub size: glm::Vec2,
+    // the point of the sprite at its translation and around which it rotates,
+    // (0, 0) is the bottom left corner and (1, 1) the top right one
+    pub pivot: glm::Vec2,
+}
+
+impl Sprite {
+    pub fn new(texture: impl AsRef<str>, width: f32, height: f32) -> Self {
+        Sprite {
+            texture: texture.as_ref().to_owned(),
+            uv: UvRect::default(),
+            tint: glm::vec4(1.0, 1.0, 1.0, 1.0),
+            layer: 0,
+            size: glm::vec2(width, height),
+            pivot: glm::vec2(0.5, 0.5),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextAlign {
+    // the lines start at the translation
+    Left,
+    Center,
+    // the lines end at the translation
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextSpace {
+    // the translation is in pixels from the top left corner of the window
+    Screen,
+    // the translation is in world units, seen through the camera
+    World,
+}
+
+// Text positioned by the Translation2<f32> of its entity, drawn after the sprites.
+// Screen space text is drawn last, over the whole window.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Text {
+    pub text: String,
+    // name of a font resource
+    pub font: String,
+    // height of a line, in pixels or world units depending on the space
+    pub size: f32,
+    pub color: glm::Vec4,
+    pub align: TextAlign,
+    // in the same unit as the size, lines are only broken at spaces and newlines
+    pub wrap_width: Option<f32>,
+    pub space: TextSpace,
+}
+
+impl Text {
+    pub fn screen(text: impl AsRef<str>, font: impl AsRef<str>, size: f32) -> Self {
+        Text {
+            text: text.as_ref().to_owned(),
+            font: font.as_ref().to_owned(),
+            size,
+            color: glm::vec4(1.0, 1.0, 1.0, 1.0),
+            align: TextAlign::Left,
+            wrap_width: None,
+            space: TextSpace::Screen,
+        }
+    }
+
+    pub fn world(text: impl AsRef<str>, font: impl AsRef<str>, size: f32) -> Self {
+        Text {
+            space: TextSpace::World,
+            ..Text::screen(text, font, size)
+        }
+    }
+}
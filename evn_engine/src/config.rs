@@ -2,7 +2,7 @@ use err_derive::Error;
 use serde_yaml::Value;
 use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
@@ -10,6 +10,10 @@ pub enum ConfigError {
     ReadConfigFile { err: io::Error },
     #[error(display = "Failed to parse config: {}", err)]
     ParseConfig { err: serde_yaml::Error },
+    #[error(display = "Failed to serialize config: {}", err)]
+    SerializeConfig { err: serde_yaml::Error },
+    #[error(display = "Failed to write config file: {}", err)]
+    WriteConfigFile { err: io::Error },
     #[error(
         display = "The structure of \"{}\" is not valid, please refer to:\n{}",
         path_str,
@@ -18,9 +22,11 @@ pub enum ConfigError {
     StructureValidation { path_str: String, template: String },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Config {
     conf: Value,
+    // the file it was loaded from, `save` writes it back there
+    path: PathBuf,
 }
 
 impl Config {
@@ -43,35 +49,174 @@ impl Config {
             Err(err) => panic!("Template is invalid: {}", err),
         };
 
-        if normalize_value(&conf) != normalize_value(&template) {
+        if !matches_template(&conf, &template) {
             Err(ConfigError::StructureValidation {
                 path_str: path.as_ref().to_str().unwrap().into(),
                 template: template_src.to_owned(),
             })
         } else {
-            Ok(Config { conf })
+            Ok(Config {
+                conf,
+                path: path.as_ref().to_owned(),
+            })
         }
     }
 
     pub fn get(&self) -> &Value {
         &self.conf
     }
+
+    // the structure has to stay the same, only values may be changed
+    pub fn get_mut(&mut self) -> &mut Value {
+        &mut self.conf
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // comments and formatting of the file are lost
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let conf_src = serde_yaml::to_string(&self.conf)
+            .map_err(|err| ConfigError::SerializeConfig { err })?;
+
+        fs::write(&self.path, conf_src).map_err(|err| ConfigError::WriteConfigFile { err })
+    }
+}
+
+// the config needs the template's structure, only values may differ.
+// lists are sized by the user, so only the shape of their elements is checked
+fn matches_template(value: &Value, template: &Value) -> bool {
+    match (value, template) {
+        (Value::Null, Value::Null)
+        | (Value::Bool(_), Value::Bool(_))
+        | (Value::Number(_), Value::Number(_))
+        | (Value::String(_), Value::String(_)) => true,
+        // an empty list in the template doesn't tell the element shape
+        (Value::Sequence(seq), Value::Sequence(template_seq)) => {
+            template_seq.is_empty() || seq.iter().all(|val| matches_element(val, template_seq))
+        }
+        (Value::Mapping(map), Value::Mapping(template_map)) => {
+            map.len() == template_map.len()
+                && map.iter().all(|(key, val)| match template_map.get(key) {
+                    Some(template_val) => matches_template(val, template_val),
+                    None => false,
+                })
+        }
+        _ => false,
+    }
 }
 
-fn normalize_value(value: &Value) -> Value {
-    match value {
-        Value::Null => Value::Null,
-        Value::Bool(_) => Value::Bool(false),
-        Value::Number(_) => Value::Number(serde_yaml::Number::from(0)),
-        Value::String(_) => Value::String(String::new()),
-        Value::Sequence(seq) => {
-            Value::Sequence(seq.iter().map(|val| normalize_value(val)).collect())
+// mappings in a list may leave out the keys some of the template's elements don't have
+fn matches_element(value: &Value, templates: &[Value]) -> bool {
+    let map = match value {
+        Value::Mapping(map) => map,
+        _ => {
+            return templates
+                .iter()
+                .any(|template| matches_template(value, template))
         }
-        // In this case we only normalize the value on the right
-        Value::Mapping(map) => Value::Mapping(
-            map.iter()
-                .map(|(val1, val2)| (val1.clone(), normalize_value(val2)))
-                .collect(),
-        ),
+    };
+
+    let template_maps = templates
+        .iter()
+        .filter_map(Value::as_mapping)
+        .collect::<Vec<_>>();
+    if template_maps.is_empty() {
+        return false;
+    }
+
+    let has_required = template_maps[0]
+        .iter()
+        .map(|(key, _)| key)
+        .filter(|key| {
+            template_maps
+                .iter()
+                .all(|template| template.contains_key(key))
+        })
+        .all(|key| map.contains_key(key));
+
+    has_required
+        && map.iter().all(|(key, val)| {
+            template_maps
+                .iter()
+                .filter_map(|template| template.get(key))
+                .any(|template_val| matches_template(val, template_val))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rendering::GraphicsSettings;
+    use std::env;
+
+    const TEMPLATE: &str = include_str!("../../resources/open/config.yml");
+
+    fn load(name: &str, conf_src: &str) -> Result<Config, ConfigError> {
+        let path = env::temp_dir().join(format!("evn_config_{}.yml", name));
+        fs::write(&path, conf_src).unwrap();
+        let config = Config::new(&path, TEMPLATE);
+        fs::remove_file(&path).unwrap();
+        config
+    }
+
+    #[test]
+    fn template_is_valid() {
+        assert!(load("template", TEMPLATE).is_ok());
+    }
+
+    #[test]
+    fn validation_ignore_list() {
+        let conf_src = TEMPLATE.replace(
+            "ignore: []",
+            "ignore: [UNASSIGNED-CoreValidation-DrawState-InvalidImageLayout, VUID-vkCmdDraw-None-02699]",
+        );
+        let config = load("validation_ignore_list", &conf_src).unwrap();
+
+        let ignore = &config.get()["graphics"]["validation"]["ignore"];
+        assert_eq!(ignore.as_sequence().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn post_chain_length() {
+        let start = TEMPLATE.find("  post:\n").unwrap();
+        let chain = |passes: &str| format!("{}  post:\n{}", &TEMPLATE[..start], passes);
+
+        // optional keys left out, a key only some of the template's passes have
+        let shorter = chain(
+            "    - shader: post_tonemap\n    - shader: post_gamma\n      inputs: [post_tonemap]\n      scale: 1.0\n",
+        );
+        let config = load("post_chain_shorter", &shorter).unwrap();
+        let post = GraphicsSettings::from_value(&config.get()["graphics"]).post;
+        let shaders = post
+            .iter()
+            .map(|pass| pass.shader.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(shaders, ["post_tonemap", "post_gamma"]);
+
+        let longer = chain(&"    - shader: post_fxaa\n      name: fxaa\n".repeat(12));
+        assert!(load("post_chain_longer", &longer).is_ok());
+        assert!(load("post_chain_empty", &chain("    []\n")).is_ok());
+
+        // every pass needs a shader and no unknown keys
+        let missing_shader = chain("    - scale: 0.5\n");
+        assert!(load("post_chain_missing_shader", &missing_shader).is_err());
+        let unknown_key = chain("    - shader: post_fxaa\n      strength: 1.0\n");
+        assert!(load("post_chain_unknown_key", &unknown_key).is_err());
+        let wrong_type = chain("    - shader: post_fxaa\n      params: 1.0\n");
+        assert!(load("post_chain_wrong_type", &wrong_type).is_err());
+    }
+
+    #[test]
+    fn invalid_structure() {
+        let wrong_type = TEMPLATE.replace("depth_buffer: true", "depth_buffer: [true]");
+        assert!(load("wrong_type", &wrong_type).is_err());
+
+        let missing_key = TEMPLATE.replace("  max_fps: 0\n", "");
+        assert!(load("missing_key", &missing_key).is_err());
+
+        let extra_key = TEMPLATE.replace("max_fps: 0", "max_fps: 0\n  vsync: true");
+        assert!(load("extra_key", &extra_key).is_err());
     }
 }
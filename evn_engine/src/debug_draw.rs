@@ -0,0 +1,168 @@
+// Debug drawing
+//
+// Any system can push lines, boxes, spheres, arrows and labels into the `DebugDraw`
+// resource, they're drawn on top of everything with the next rendered frame and cleared
+// afterwards. Outside of --dev everything pushed is dropped right away.
+// Like the profiler it only needs read access, so drawing systems still run in parallel.
+
+use nalgebra_glm as glm;
+use std::{f32::consts::PI, sync::Mutex};
+
+// per circle of a sphere
+const SPHERE_SEGMENTS: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DebugLine {
+    pub start: glm::Vec3,
+    pub end: glm::Vec3,
+    pub color: glm::Vec4,
+}
+
+// text drawn in screen space at the projected position
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugLabel {
+    pub position: glm::Vec3,
+    pub text: String,
+    pub color: glm::Vec4,
+}
+
+// disabled by default, the game enables it with --dev
+#[derive(Debug, Default)]
+pub struct DebugDraw {
+    enabled: bool,
+    lines: Mutex<Vec<DebugLine>>,
+    labels: Mutex<Vec<DebugLabel>>,
+}
+
+impl DebugDraw {
+    pub fn new(enabled: bool) -> Self {
+        DebugDraw {
+            enabled,
+            ..DebugDraw::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn line(&self, start: glm::Vec3, end: glm::Vec3, color: glm::Vec4) {
+        if self.enabled {
+            self.lines
+                .lock()
+                .unwrap()
+                .push(DebugLine { start, end, color });
+        }
+    }
+
+    // axis aligned, the 12 edges of the box
+    pub fn aabb(&self, min: glm::Vec3, max: glm::Vec3, color: glm::Vec4) {
+        if !self.enabled {
+            return;
+        }
+
+        let corner = |x: bool, y: bool, z: bool| {
+            glm::vec3(
+                if x { max.x } else { min.x },
+                if y { max.y } else { min.y },
+                if z { max.z } else { min.z },
+            )
+        };
+
+        let mut lines = self.lines.lock().unwrap();
+        for &(a, b) in &[(false, false), (true, false), (false, true), (true, true)] {
+            let edges = [
+                (corner(false, a, b), corner(true, a, b)),
+                (corner(a, false, b), corner(a, true, b)),
+                (corner(a, b, false), corner(a, b, true)),
+            ];
+            lines.extend(
+                edges
+                    .iter()
+                    .map(|&(start, end)| DebugLine { start, end, color }),
+            );
+        }
+    }
+
+    // one circle around every axis
+    pub fn sphere(&self, center: glm::Vec3, radius: f32, color: glm::Vec4) {
+        if !self.enabled {
+            return;
+        }
+
+        let point = |axis: usize, segment: usize| {
+            let angle = segment as f32 / SPHERE_SEGMENTS as f32 * 2.0 * PI;
+            let (sin, cos) = (angle.sin() * radius, angle.cos() * radius);
+            center
+                + match axis {
+                    0 => glm::vec3(0.0, cos, sin),
+                    1 => glm::vec3(cos, 0.0, sin),
+                    _ => glm::vec3(cos, sin, 0.0),
+                }
+        };
+
+        let mut lines = self.lines.lock().unwrap();
+        for axis in 0..3 {
+            lines.extend((0..SPHERE_SEGMENTS).map(|segment| DebugLine {
+                start: point(axis, segment),
+                end: point(axis, segment + 1),
+                color,
+            }));
+        }
+    }
+
+    // the head is a fifth of the length
+    pub fn arrow(&self, start: glm::Vec3, end: glm::Vec3, color: glm::Vec4) {
+        if !self.enabled {
+            return;
+        }
+
+        let direction = end - start;
+        let length = glm::length(&direction);
+        if length <= 0.0 {
+            return;
+        }
+        let direction = direction / length;
+
+        // any two axes perpendicular to the arrow
+        let up = if direction.y.abs() < 0.99 {
+            glm::vec3(0.0, 1.0, 0.0)
+        } else {
+            glm::vec3(1.0, 0.0, 0.0)
+        };
+        let side = glm::normalize(&direction.cross(&up));
+        let up = side.cross(&direction);
+
+        let head_length = length / 5.0;
+        let base = end - direction * head_length;
+        let head_width = head_length / 2.0;
+
+        let mut lines = self.lines.lock().unwrap();
+        lines.push(DebugLine { start, end, color });
+        for offset in &[side, -side, up, -up] {
+            lines.push(DebugLine {
+                start: end,
+                end: base + offset * head_width,
+                color,
+            });
+        }
+    }
+
+    pub fn label(&self, position: glm::Vec3, text: impl AsRef<str>, color: glm::Vec4) {
+        if self.enabled {
+            self.labels.lock().unwrap().push(DebugLabel {
+                position,
+                text: text.as_ref().to_owned(),
+                color,
+            });
+        }
+    }
+
+    // everything pushed since the last call, called by the render system every frame
+    pub fn take(&self) -> (Vec<DebugLine>, Vec<DebugLabel>) {
+        (
+            std::mem::take(&mut *self.lines.lock().unwrap()),
+            std::mem::take(&mut *self.labels.lock().unwrap()),
+        )
+    }
+}
@@ -0,0 +1,441 @@
+// Developer UI
+//
+// An egui overlay in --dev with the log, the frame timings, the resources and their states
+// and the values of every loaded config, which can be edited and saved. F1 toggles it.
+// `Game::step` feeds it the window events, `DevUiSystem` builds the UI and the render
+// system hands the tessellated meshes to the backend, which draws them last.
+
+use crate::{
+    config::Config,
+    logger,
+    profiler::{self, FrameTimings, Profiler},
+    resources::{Resource, ResourceState, Resources, ResourcesData},
+    WindowSize,
+};
+use egui::{
+    pos2, vec2, ClippedPrimitive, CollapsingHeader, Context, DragValue, Grid, Key, Modifiers,
+    PointerButton, RawInput, Rect, ScrollArea, TextEdit, TexturesDelta, Ui, Window,
+};
+use log::{error, info};
+use serde_yaml::{Number, Value};
+use specs::{Read, ReadExpect, System, Write};
+use std::{sync::Arc, time::Instant};
+use winit::{
+    dpi::LogicalSize, ElementState, Event, KeyboardInput, ModifiersState, MouseButton,
+    MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+// points scrolled per line of a mouse wheel
+const SCROLL_LINE: f32 = 24.0;
+
+// what the backend draws, in logical pixels
+#[derive(Debug, Clone, Default)]
+pub struct UiFrame {
+    pub primitives: Vec<ClippedPrimitive>,
+    // has to be applied before drawing `primitives`
+    pub textures: TexturesDelta,
+}
+
+pub struct DevUi {
+    enabled: bool,
+    visible: bool,
+    context: Context,
+    // the input since the last frame
+    input: RawInput,
+    pointer: egui::Pos2,
+    modifiers: Modifiers,
+    hidpi_factor: f32,
+    start: Instant,
+    // built by `DevUiSystem`, taken by the render system
+    frame: Option<UiFrame>,
+}
+
+impl Default for DevUi {
+    fn default() -> Self {
+        DevUi::new(false, 1.0)
+    }
+}
+
+impl DevUi {
+    // disabled outside of --dev, visible right away otherwise
+    pub fn new(enabled: bool, hidpi_factor: f32) -> Self {
+        DevUi {
+            enabled,
+            visible: enabled,
+            context: Context::default(),
+            input: RawInput::default(),
+            pointer: pos2(0.0, 0.0),
+            modifiers: Modifiers::default(),
+            hidpi_factor,
+            start: Instant::now(),
+            frame: None,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = self.enabled && visible;
+    }
+
+    // the UI is using the mouse or keyboard, the game may want to ignore them
+    pub fn wants_input(&self) -> bool {
+        self.visible && (self.context.wants_pointer_input() || self.context.wants_keyboard_input())
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        if !self.enabled {
+            return;
+        }
+
+        let event = match event {
+            Event::WindowEvent { event, .. } => event,
+            _ => return,
+        };
+
+        match event {
+            WindowEvent::HiDpiFactorChanged(hidpi_factor) => {
+                self.hidpi_factor = *hidpi_factor as f32;
+            }
+            WindowEvent::Focused(focused) => self.input.focused = *focused,
+            WindowEvent::CursorMoved {
+                position,
+                modifiers,
+                ..
+            } => {
+                self.modifiers = convert_modifiers(*modifiers);
+                self.pointer = pos2(position.x as f32, position.y as f32);
+                self.push(egui::Event::PointerMoved(self.pointer));
+            }
+            WindowEvent::CursorLeft { .. } => self.push(egui::Event::PointerGone),
+            WindowEvent::MouseInput {
+                state,
+                button,
+                modifiers,
+                ..
+            } => {
+                self.modifiers = convert_modifiers(*modifiers);
+                let button = match button {
+                    MouseButton::Left => PointerButton::Primary,
+                    MouseButton::Right => PointerButton::Secondary,
+                    MouseButton::Middle => PointerButton::Middle,
+                    MouseButton::Other(_) => return,
+                };
+                self.push(egui::Event::PointerButton {
+                    pos: self.pointer,
+                    button,
+                    pressed: *state == ElementState::Pressed,
+                    modifiers: self.modifiers,
+                });
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => vec2(*x, *y) * SCROLL_LINE,
+                    MouseScrollDelta::PixelDelta(delta) => vec2(delta.x as f32, delta.y as f32),
+                };
+                self.push(egui::Event::Scroll(delta));
+            }
+            WindowEvent::ReceivedCharacter(c) if !c.is_control() => {
+                self.push(egui::Event::Text(c.to_string()));
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(keycode),
+                        modifiers,
+                        ..
+                    },
+                ..
+            } => {
+                self.modifiers = convert_modifiers(*modifiers);
+                let pressed = *state == ElementState::Pressed;
+
+                if *keycode == VirtualKeyCode::F1 {
+                    if pressed {
+                        self.visible = !self.visible;
+                    }
+                } else if let Some(key) = convert_key(*keycode) {
+                    self.push(egui::Event::Key {
+                        key,
+                        pressed,
+                        repeat: false,
+                        modifiers: self.modifiers,
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // events arriving while hidden are dropped
+    fn push(&mut self, event: egui::Event) {
+        if self.visible {
+            self.input.events.push(event);
+        }
+    }
+
+    // builds the frame for the render system, nothing while hidden
+    pub fn run(&mut self, window_size: LogicalSize, build: impl FnOnce(&Context)) {
+        if !self.visible {
+            self.input.events.clear();
+            return;
+        }
+
+        let mut input = std::mem::take(&mut self.input);
+        input.screen_rect = Some(Rect::from_min_size(
+            pos2(0.0, 0.0),
+            vec2(window_size.width as f32, window_size.height as f32),
+        ));
+        input.pixels_per_point = Some(self.hidpi_factor);
+        input.time = Some(self.start.elapsed().as_secs_f64());
+        input.modifiers = self.modifiers;
+        self.input.focused = input.focused;
+
+        let output = self.context.run(input, build);
+        let primitives = self.context.tessellate(output.shapes);
+
+        // keeps the texture changes of a frame that wasn't rendered
+        let mut textures = self
+            .frame
+            .take()
+            .map(|frame| frame.textures)
+            .unwrap_or_default();
+        textures.append(output.textures_delta);
+
+        self.frame = Some(UiFrame {
+            primitives,
+            textures,
+        });
+    }
+
+    pub fn take_frame(&mut self) -> Option<UiFrame> {
+        self.frame.take()
+    }
+}
+
+fn convert_modifiers(modifiers: ModifiersState) -> Modifiers {
+    let mac = cfg!(target_os = "macos");
+
+    Modifiers {
+        alt: modifiers.alt,
+        ctrl: modifiers.ctrl,
+        shift: modifiers.shift,
+        mac_cmd: mac && modifiers.logo,
+        command: if mac { modifiers.logo } else { modifiers.ctrl },
+    }
+}
+
+// only the keys egui uses for navigation and text editing
+fn convert_key(keycode: VirtualKeyCode) -> Option<Key> {
+    Some(match keycode {
+        VirtualKeyCode::Down => Key::ArrowDown,
+        VirtualKeyCode::Left => Key::ArrowLeft,
+        VirtualKeyCode::Right => Key::ArrowRight,
+        VirtualKeyCode::Up => Key::ArrowUp,
+        VirtualKeyCode::Escape => Key::Escape,
+        VirtualKeyCode::Tab => Key::Tab,
+        VirtualKeyCode::Back => Key::Backspace,
+        VirtualKeyCode::Return => Key::Enter,
+        VirtualKeyCode::Space => Key::Space,
+        VirtualKeyCode::Insert => Key::Insert,
+        VirtualKeyCode::Delete => Key::Delete,
+        VirtualKeyCode::Home => Key::Home,
+        VirtualKeyCode::End => Key::End,
+        VirtualKeyCode::PageUp => Key::PageUp,
+        VirtualKeyCode::PageDown => Key::PageDown,
+        VirtualKeyCode::A => Key::A,
+        VirtualKeyCode::C => Key::C,
+        VirtualKeyCode::V => Key::V,
+        VirtualKeyCode::X => Key::X,
+        VirtualKeyCode::Z => Key::Z,
+        _ => return None,
+    })
+}
+
+// only added to the dispatcher with --dev
+pub struct DevUiSystem;
+
+impl<'a> System<'a> for DevUiSystem {
+    type SystemData = (
+        Write<'a, DevUi>,
+        ReadExpect<'a, WindowSize>,
+        Read<'a, Profiler>,
+        ReadExpect<'a, Resources>,
+    );
+
+    fn run(&mut self, (mut dev_ui, window_size, profiler, resources): Self::SystemData) {
+        let timings = profiler.timings();
+        let resources = resources.read().unwrap();
+        let list = resources.list();
+
+        dev_ui.run(window_size.0, |ctx| {
+            log_window(ctx);
+            timings_window(ctx, &timings);
+            resources_window(ctx, &list);
+            configs_window(ctx, &resources, &list);
+        });
+    }
+}
+
+fn log_window(ctx: &Context) {
+    Window::new("Log")
+        .default_pos([10.0, 10.0])
+        .default_size([600.0, 200.0])
+        .show(ctx, |ui| {
+            ScrollArea::vertical().stick_to_bottom(true).show(ui, |ui| {
+                for line in logger::history() {
+                    ui.monospace(line);
+                }
+            });
+        });
+}
+
+fn timings_window(ctx: &Context, timings: &FrameTimings) {
+    Window::new("Timings")
+        .default_pos([10.0, 260.0])
+        .show(ctx, |ui| {
+            Grid::new("timings").striped(true).show(ui, |ui| {
+                ui.label("frame");
+                ui.label(profiler::format_ms(timings.frame));
+                ui.end_row();
+
+                for (name, time) in &timings.systems {
+                    ui.label(format!("system {}", name));
+                    ui.label(profiler::format_ms(*time));
+                    ui.end_row();
+                }
+
+                for pass in &timings.passes {
+                    ui.label(format!("pass {}", pass.name));
+                    ui.label(format!("cpu {}", profiler::format_ms(pass.cpu)));
+                    if let Some(gpu) = pass.gpu {
+                        ui.label(format!("gpu {}", profiler::format_ms(gpu)));
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+fn resources_window(ctx: &Context, list: &[(String, Arc<ResourceState>)]) {
+    Window::new("Resources")
+        .default_pos([620.0, 10.0])
+        .show(ctx, |ui| {
+            Grid::new("resources").striped(true).show(ui, |ui| {
+                for (name, state) in list {
+                    ui.label(name);
+                    ui.label(match &**state {
+                        ResourceState::Loaded(Resource::Config(_)) => "config",
+                        ResourceState::Loaded(Resource::Shader(_)) => "shader",
+                        ResourceState::Loaded(Resource::Texture(_)) => "texture",
+                        ResourceState::Loaded(Resource::Font(_)) => "font",
+                        ResourceState::Loading => "loading",
+                        ResourceState::Failed => "failed",
+                    });
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+// edits replace the config resource, most settings are only read at startup though
+fn configs_window(ctx: &Context, resources: &ResourcesData, list: &[(String, Arc<ResourceState>)]) {
+    Window::new("Configs")
+        .default_pos([620.0, 260.0])
+        .show(ctx, |ui| {
+            for (name, state) in list {
+                let mut config = match &**state {
+                    ResourceState::Loaded(Resource::Config(config)) => config.clone(),
+                    _ => continue,
+                };
+
+                CollapsingHeader::new(name).show(ui, |ui| {
+                    if edit_value(ui, config.get_mut()) {
+                        resources.replace_resource(name, Resource::Config(config.clone()));
+                    }
+
+                    if ui.button("Save").clicked() {
+                        save_config(name, &config);
+                    }
+                });
+            }
+        });
+}
+
+fn save_config(name: &str, config: &Config) {
+    match config.save() {
+        Ok(()) => info!(
+            "Saved config \"{}\" to {}",
+            name,
+            config.path().to_string_lossy()
+        ),
+        Err(err) => error!("Failed to save config \"{}\": {}", name, err),
+    }
+}
+
+// true if the value was changed, the type of a value never changes
+fn edit_value(ui: &mut Ui, value: &mut Value) -> bool {
+    match value {
+        Value::Null => {
+            ui.label("~");
+            false
+        }
+        Value::Bool(value) => ui.checkbox(value, "").changed(),
+        Value::Number(number) => {
+            if let Some(mut int) = number.as_i64() {
+                let changed = ui.add(DragValue::new(&mut int)).changed();
+                *number = Number::from(int);
+                changed
+            } else {
+                let mut float = number.as_f64().unwrap_or_default();
+                let changed = ui.add(DragValue::new(&mut float).speed(0.01)).changed();
+                *number = Number::from(float);
+                changed
+            }
+        }
+        Value::String(string) => ui.add(TextEdit::singleline(string)).changed(),
+        Value::Sequence(sequence) => {
+            let mut changed = false;
+            for (index, value) in sequence.iter_mut().enumerate() {
+                changed |= edit_entry(ui, &index.to_string(), value);
+            }
+            changed
+        }
+        Value::Mapping(mapping) => {
+            let mut changed = false;
+            for (key, value) in mapping.iter_mut() {
+                let key = match key {
+                    Value::String(key) => key.clone(),
+                    key => serde_yaml::to_string(key).unwrap_or_default(),
+                };
+                changed |= edit_entry(ui, &key, value);
+            }
+            changed
+        }
+    }
+}
+
+// nested values get their own collapsible section
+fn edit_entry(ui: &mut Ui, label: &str, value: &mut Value) -> bool {
+    match value {
+        Value::Sequence(_) | Value::Mapping(_) => CollapsingHeader::new(label)
+            .default_open(true)
+            .show(ui, |ui| edit_value(ui, value))
+            .body_returned
+            .unwrap_or(false),
+        _ => {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                edit_value(ui, value)
+            })
+            .inner
+        }
+    }
+}
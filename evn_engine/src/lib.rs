@@ -1,16 +1,25 @@
 pub mod components;
 pub mod config;
+pub mod debug_draw;
+pub mod dev_ui;
 pub mod logger;
 pub mod prelude;
+pub mod profiler;
 pub mod rendering;
 pub mod resources;
 pub mod systems;
 
 use crate::{
+    debug_draw::DebugDraw,
+    dev_ui::{DevUi, DevUiSystem},
     logger::Logger,
-    rendering::{Renderer, RendererInitError},
+    profiler::{Profiler, TimedDispatcherBuilder},
+    rendering::{
+        CameraMatrices, GraphicsSettings, NullRenderer, RenderOutput, RenderSystem, Renderer,
+        RendererInitError, Screenshot,
+    },
     resources::{ResourceBuilder, ResourcesData},
-    systems::EventHandler,
+    systems::{CameraSystem, EventHandler},
 };
 use clap::{App, Arg};
 use crossbeam::{channel, Sender};
@@ -19,8 +28,12 @@ use fnv::FnvHashMap;
 use log::info;
 use rayon::{ThreadPoolBuildError, ThreadPoolBuilder};
 use specs::{Dispatcher, DispatcherBuilder, World};
-use std::sync::{Arc, RwLock};
-use winit::{CreationError, Event, EventsLoop, WindowBuilder};
+use std::{
+    process,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use winit::{dpi::LogicalSize, CreationError, Event, EventsLoop, WindowBuilder};
 
 #[macro_export]
 macro_rules! include_resource {
@@ -34,6 +47,9 @@ macro_rules! include_resource {
 
 pub struct Running(pub bool);
 
+// inner size of the window, updated on resize
+pub struct WindowSize(pub LogicalSize);
+
 #[derive(Debug, Error)]
 pub enum GameInitError {
     #[error(display = "Failed to create window: {}", err)]
@@ -44,11 +60,21 @@ pub enum GameInitError {
     RendererCreation { err: RendererInitError },
 }
 
+// which renderer `Game` is created with
+pub enum Backend {
+    Vulkan,
+    // no window and no Vulkan, the frames are only recorded (see `NullRenderer`)
+    Null(NullRenderer),
+}
+
 pub struct Game<'a, 'b> {
     pub world: World,
     pub dispatcher: Dispatcher<'a, 'b>,
-    pub events_loop: EventsLoop,
+    // None with the null backend
+    pub events_loop: Option<EventsLoop>,
     pub event_send: Sender<Event>,
+    // last time the timings were logged, only with --profile
+    profile_logged: Option<Instant>,
 }
 
 impl<'a, 'b> Game<'a, 'b> {
@@ -66,7 +92,32 @@ impl<'a, 'b> Game<'a, 'b> {
         RB: FnOnce(ResourceBuilder) -> ResourceBuilder,
         WB: FnOnce(WindowBuilder) -> WindowBuilder,
     {
-        let clap = App::new("evn")
+        Self::with_backend(
+            version,
+            Backend::Vulkan,
+            world_access,
+            dispatcher_builder,
+            resources,
+            window_builder,
+        )
+    }
+
+    // the window builder only provides the window size for the null backend
+    pub fn with_backend<RB, WB, DB, WA>(
+        version: &str,
+        backend: Backend,
+        world_access: WA,
+        dispatcher_builder: DB,
+        resources: RB,
+        window_builder: WB,
+    ) -> Result<Self, GameInitError>
+    where
+        WA: FnOnce(&mut World),
+        DB: FnOnce(DispatcherBuilder<'a, 'b>) -> DispatcherBuilder<'a, 'b>,
+        RB: FnOnce(ResourceBuilder) -> ResourceBuilder,
+        WB: FnOnce(WindowBuilder) -> WindowBuilder,
+    {
+        let app = App::new("evn")
             .version(version)
             .about("A hobby game with an selfmade engine written in Rust")
             .arg(
@@ -79,22 +130,51 @@ impl<'a, 'b> Game<'a, 'b> {
                     .long("validation")
                     .help("Enable Vulkan validation layers"),
             )
+            .arg(
+                Arg::with_name("list-gpus")
+                    .long("list-gpus")
+                    .help("Print the available GPUs and exit"),
+            )
+            .arg(
+                Arg::with_name("gpu")
+                    .long("gpu")
+                    .takes_value(true)
+                    .value_name("NAME_OR_UUID")
+                    .help("Use the GPU with this name or UUID"),
+            )
+            .arg(
+                Arg::with_name("profile")
+                    .long("profile")
+                    .help("Log the frame timings every second"),
+            )
             .arg(
                 Arg::with_name("color")
                     .long("color")
                     .short("c")
                     .help("Enable console coloring"),
-            )
-            .get_matches();
+            );
+
+        // the null backend is used by tests, the arguments belong to the test harness there
+        let clap = match backend {
+            Backend::Vulkan => app.get_matches(),
+            Backend::Null(_) => app.get_matches_from(vec!["evn"]),
+        };
 
         let color = clap.is_present("color");
         let is_dev = clap.is_present("dev");
         let debug_callback = clap.is_present("validation");
+        let profile = clap.is_present("profile");
 
         if let Err(err) = Logger::init(color) {
             eprintln!("Failed to init logger: {}", err);
         }
 
+        // exits like --help and --version do
+        if clap.is_present("list-gpus") {
+            rendering::list_gpus().map_err(|err| GameInitError::RendererCreation { err })?;
+            process::exit(0);
+        }
+
         let mut world = World::new();
 
         // register components
@@ -116,34 +196,92 @@ impl<'a, 'b> Game<'a, 'b> {
             res: Arc::new(RwLock::new(ResourcesData::new())),
             is_dev,
             names: FnvHashMap::default(),
+            pipelines: Vec::new(),
         });
 
-        // Renderer
-        let events_loop = EventsLoop::new();
-        let window = window_builder(WindowBuilder::new())
-            .build(&events_loop)
-            .map_err(|err| GameInitError::WindowCreation { err })?;
-
-        let renderer = Renderer::new(
-            window,
-            debug_callback,
-            resources.res.clone(),
-            resources.names,
-        )
-        .map_err(|err| GameInitError::RendererCreation { err })?;
-
-        // Dispatcher
-        let dispatcher =
+        let mut dispatcher_builder =
             dispatcher_builder(DispatcherBuilder::new().with_pool(thread_pool.clone()))
-                .with(EventHandler, "event_handler", &[])
-                .with(renderer, "renderer", &["event_handler"])
-                .build();
+                .with_timed(EventHandler, "event_handler", &[])
+                .with_timed(CameraSystem, "camera", &["event_handler"]);
+
+        let mut renderer_dependencies = vec!["event_handler", "camera"];
+        if is_dev {
+            dispatcher_builder =
+                dispatcher_builder.with_timed(DevUiSystem, "dev_ui", &["event_handler"]);
+            renderer_dependencies.push("dev_ui");
+        }
+
+        // Renderer and Dispatcher
+        let (events_loop, window_size, hidpi_factor, dispatcher) = match backend {
+            Backend::Vulkan => {
+                let events_loop = EventsLoop::new();
+                let window = window_builder(WindowBuilder::new())
+                    .build(&events_loop)
+                    .map_err(|err| GameInitError::WindowCreation { err })?;
+                let window_size = window
+                    .get_inner_size()
+                    .unwrap_or_else(|| LogicalSize::new(0.0, 0.0));
+                let hidpi_factor = window.get_hidpi_factor();
+
+                let mut settings = GraphicsSettings::load(
+                    &resources.res.read().unwrap(),
+                    resources.names.get("configs").map_or(&[], Vec::as_slice),
+                );
+                if let Some(gpu) = clap.value_of("gpu") {
+                    settings.gpu = Some(gpu.to_owned());
+                }
+
+                let renderer = Renderer::new(
+                    RenderOutput::Window(window),
+                    debug_callback,
+                    settings,
+                    resources.res.clone(),
+                    resources.names,
+                    resources.pipelines,
+                    resources::resource_path("pipeline_cache.bin", is_dev, true),
+                )
+                .map_err(|err| GameInitError::RendererCreation { err })?;
+
+                let dispatcher = dispatcher_builder
+                    .with_timed(
+                        RenderSystem::new(renderer),
+                        "renderer",
+                        &renderer_dependencies,
+                    )
+                    .build();
+
+                (Some(events_loop), window_size, hidpi_factor, dispatcher)
+            }
+            Backend::Null(null_renderer) => {
+                // winit's default size if none is set
+                let window_size = window_builder(WindowBuilder::new())
+                    .window
+                    .dimensions
+                    .unwrap_or_else(|| LogicalSize::new(1024.0, 768.0));
+
+                let dispatcher = dispatcher_builder
+                    .with_timed(
+                        RenderSystem::new(null_renderer),
+                        "renderer",
+                        &renderer_dependencies,
+                    )
+                    .build();
+
+                (None, window_size, 1.0, dispatcher)
+            }
+        };
 
         world.add_resource(recv);
         world.add_resource(clap);
         world.add_resource(thread_pool);
         world.add_resource(resources.res);
         world.add_resource(Running(true));
+        world.add_resource(WindowSize(window_size));
+        world.add_resource(CameraMatrices::default());
+        world.add_resource(Screenshot::default());
+        world.add_resource(Profiler::default());
+        world.add_resource(DebugDraw::new(is_dev));
+        world.add_resource(DevUi::new(is_dev, hidpi_factor as f32));
 
         info!("Game initialized");
 
@@ -152,21 +290,48 @@ impl<'a, 'b> Game<'a, 'b> {
             dispatcher,
             events_loop,
             event_send: send,
+            profile_logged: if profile { Some(Instant::now()) } else { None },
         })
     }
 
     pub fn run(&mut self) {
-        let event_send = self.event_send.clone();
         while self.world.read_resource::<Running>().0 {
-            self.events_loop.poll_events(|event| {
+            self.step();
+        }
+
+        info!("Exiting...");
+    }
+
+    // polls the window events and dispatches every system once
+    pub fn step(&mut self) {
+        if let Some(events_loop) = &mut self.events_loop {
+            let event_send = &self.event_send;
+            // the event handler consumes the channel, so the UI gets the events here
+            let mut dev_ui = self.world.write_resource::<DevUi>();
+            events_loop.poll_events(|event| {
+                dev_ui.handle_event(&event);
                 event_send.send(event).unwrap();
             });
+        }
 
-            self.dispatcher.dispatch(&mut self.world.res);
+        let dispatch_start = Instant::now();
+        self.dispatcher.dispatch(&mut self.world.res);
 
-            self.world.maintain();
+        let profiler = self.world.read_resource::<Profiler>();
+        profiler.record_frame(dispatch_start.elapsed());
+        if let Some(logged) = &mut self.profile_logged {
+            if logged.elapsed() >= Duration::from_secs(1) {
+                info!("{}", profiler.timings());
+                *logged = Instant::now();
+            }
         }
+        drop(profiler);
 
-        info!("Exiting...");
+        self.world.maintain();
+    }
+
+    // the next rendered frame gets saved to ./screenshots
+    pub fn screenshot(&mut self) {
+        self.world.write_resource::<Screenshot>().request();
     }
 }
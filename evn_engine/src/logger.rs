@@ -4,6 +4,7 @@ use err_derive::Error;
 use log::{error, info};
 use log::{Level, LevelFilter, Log, Metadata, Record};
 use std::{
+    collections::VecDeque,
     fmt::Display,
     fs::{self, File, OpenOptions},
     io::{Error as IoError, ErrorKind, Write},
@@ -11,6 +12,11 @@ use std::{
     sync::Mutex,
 };
 
+// lines kept for the developer UI
+const HISTORY_LENGTH: usize = 500;
+
+static HISTORY: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
 pub trait UnwrapOrLog<T> {
     fn unwrap_or_log(self, message: impl Display) -> T;
 }
@@ -114,6 +120,14 @@ impl Log for Logger {
                 writeln!(self.archived_log.lock().unwrap(), "{}", uncolored_output),
             );
 
+            {
+                let mut history = HISTORY.lock().unwrap();
+                if history.len() == HISTORY_LENGTH {
+                    history.pop_front();
+                }
+                history.push_back(uncolored_output.clone());
+            }
+
             let console_ouput = if self.console_colored {
                 output(&time, record, true)
             } else {
@@ -136,6 +150,11 @@ impl Log for Logger {
     }
 }
 
+// the most recent lines, oldest first
+pub fn history() -> Vec<String> {
+    HISTORY.lock().unwrap().iter().cloned().collect()
+}
+
 fn output(time: &str, record: &log::Record, colored: bool) -> String {
     if colored {
         let level = match record.level() {
@@ -165,7 +184,7 @@ fn format_hhmmssnnn() -> String {
     )
 }
 
-fn format_yyyymmdd_hhmmss() -> String {
+pub(crate) fn format_yyyymmdd_hhmmss() -> String {
     let time = Local::now();
 
     format!(
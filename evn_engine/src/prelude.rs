@@ -1 +1,3 @@
-pub use crate::{include_resource, logger::UnwrapOrLog, Game};
+pub use crate::{
+    include_resource, logger::UnwrapOrLog, profiler::TimedDispatcherBuilder, Backend, Game,
+};
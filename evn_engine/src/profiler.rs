@@ -0,0 +1,147 @@
+// Frame timings
+//
+// The `Profiler` resource collects the CPU time of the dispatch, of every system wrapped
+// in `Timed` and the CPU and GPU time of every render pass. GPU times arrive a few frames
+// late, once the renderer could read its timestamp queries back.
+// Everything is behind mutexes, so timed systems only need read access and still run in parallel.
+
+use fnv::FnvHashMap;
+use specs::{prelude::Resources, DispatcherBuilder, Read, System, SystemData};
+use std::{fmt, sync::Mutex, time::Duration, time::Instant};
+
+#[derive(Debug, Clone)]
+pub struct PassTiming {
+    pub name: String,
+    // time spent recording the pass
+    pub cpu: Duration,
+    // None if the device doesn't support timestamps
+    pub gpu: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FrameTimings {
+    pub frame: Duration,
+    // sorted by name
+    pub systems: Vec<(String, Duration)>,
+    pub passes: Vec<PassTiming>,
+}
+
+impl fmt::Display for FrameTimings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "frame {}", format_ms(self.frame))?;
+
+        for (name, time) in &self.systems {
+            write!(f, "\n  system {}: {}", name, format_ms(*time))?;
+        }
+
+        for pass in &self.passes {
+            write!(f, "\n  pass {}: cpu {}", pass.name, format_ms(pass.cpu))?;
+            if let Some(gpu) = pass.gpu {
+                write!(f, ", gpu {}", format_ms(gpu))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Profiler {
+    frame: Mutex<Duration>,
+    systems: Mutex<FnvHashMap<String, Duration>>,
+    passes: Mutex<Vec<PassTiming>>,
+}
+
+impl Profiler {
+    pub fn record_frame(&self, time: Duration) {
+        *self.frame.lock().unwrap() = time;
+    }
+
+    pub fn record_system(&self, name: &str, time: Duration) {
+        let mut systems = self.systems.lock().unwrap();
+        match systems.get_mut(name) {
+            Some(system) => *system = time,
+            None => {
+                systems.insert(name.to_owned(), time);
+            }
+        }
+    }
+
+    // replaces the passes of an older frame
+    pub fn record_passes(&self, passes: Vec<PassTiming>) {
+        *self.passes.lock().unwrap() = passes;
+    }
+
+    pub fn timings(&self) -> FrameTimings {
+        let mut systems = self
+            .systems
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, time)| (name.clone(), *time))
+            .collect::<Vec<_>>();
+        systems.sort_by(|a, b| a.0.cmp(&b.0));
+
+        FrameTimings {
+            frame: *self.frame.lock().unwrap(),
+            systems,
+            passes: self.passes.lock().unwrap().clone(),
+        }
+    }
+}
+
+// measures the CPU time of the inner system
+pub struct Timed<S> {
+    name: String,
+    system: S,
+}
+
+impl<S> Timed<S> {
+    pub fn new(system: S, name: impl AsRef<str>) -> Self {
+        Timed {
+            name: name.as_ref().to_owned(),
+            system,
+        }
+    }
+}
+
+impl<'a, S> System<'a> for Timed<S>
+where
+    S: System<'a>,
+    S::SystemData: SystemData<'a>,
+{
+    type SystemData = (Read<'a, Profiler>, S::SystemData);
+
+    fn run(&mut self, (profiler, data): Self::SystemData) {
+        let start = Instant::now();
+        self.system.run(data);
+        profiler.record_system(&self.name, start.elapsed());
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        <Read<'a, Profiler> as SystemData>::setup(res);
+        self.system.setup(res);
+    }
+}
+
+pub trait TimedDispatcherBuilder {
+    // `DispatcherBuilder::with`, timed under the system's name
+    fn with_timed<S>(self, system: S, name: &str, dependencies: &[&str]) -> Self
+    where
+        S: for<'c> System<'c> + Send + 'static,
+        for<'c> <S as System<'c>>::SystemData: SystemData<'c>;
+}
+
+impl<'a, 'b> TimedDispatcherBuilder for DispatcherBuilder<'a, 'b> {
+    fn with_timed<S>(self, system: S, name: &str, dependencies: &[&str]) -> Self
+    where
+        S: for<'c> System<'c> + Send + 'static,
+        for<'c> <S as System<'c>>::SystemData: SystemData<'c>,
+    {
+        self.with(Timed::new(system, name), name, dependencies)
+    }
+}
+
+pub(crate) fn format_ms(time: Duration) -> String {
+    format!("{:.2} ms", time.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,821 @@
+// Sub-allocating GPU memory allocator
+//
+// Every buffer and image the renderer creates should get its memory from here,
+// drivers only guarantee `maxMemoryAllocationCount` (often 4096) allocations.
+
+// the unsafe functions here have the same requirements as the Vulkan calls they wrap
+#![allow(clippy::missing_safety_doc)]
+
+use ash::{
+    prelude::VkResult,
+    version::{DeviceV1_0, InstanceV1_0},
+    vk, Device, Instance,
+};
+use err_derive::Error;
+use log::info;
+use std::{fmt, os::raw::c_void, ptr::NonNull};
+
+// size of a single memory block, allocations bigger than half of this get their own memory
+const BLOCK_SIZE: vk::DeviceSize = 64 * 1024 * 1024;
+// the amount of empty blocks to keep around per pool, so freeing and allocating
+// in the same frame doesn't cause a vkFreeMemory/vkAllocateMemory pair
+const SPARE_BLOCKS: usize = 1;
+
+#[derive(Debug, Error)]
+pub enum AllocatorError {
+    #[error(display = "No memory type matching the requirements")]
+    NoCompatibleMemoryType,
+    #[error(display = "Vulkan error: {}", err)]
+    VulkanError { err: vk::Result },
+}
+
+impl From<vk::Result> for AllocatorError {
+    fn from(err: vk::Result) -> Self {
+        AllocatorError::VulkanError { err }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryUsage {
+    // device local, not mappable (textures, static vertex buffers, render targets)
+    GpuOnly,
+    // mappable, preferably device local (uniform buffers, staging for uploads)
+    CpuToGpu,
+    // mappable, preferably cached (readback)
+    GpuToCpu,
+}
+
+impl MemoryUsage {
+    fn flags(self) -> (vk::MemoryPropertyFlags, vk::MemoryPropertyFlags) {
+        // (required, preferred)
+        match self {
+            MemoryUsage::GpuOnly => (
+                vk::MemoryPropertyFlags::empty(),
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            ),
+            MemoryUsage::CpuToGpu => (
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            ),
+            MemoryUsage::GpuToCpu => (
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                vk::MemoryPropertyFlags::HOST_CACHED,
+            ),
+        }
+    }
+}
+
+// buffers and optimally tiled images never share a block, that way we don't
+// have to care about bufferImageGranularity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResourceKind {
+    Linear,
+    Optimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AllocationSource {
+    Dedicated,
+    Block { pool: usize, block: usize },
+}
+
+#[derive(Debug)]
+pub struct Allocation {
+    memory: vk::DeviceMemory,
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+    memory_type_index: u32,
+    mapped: Option<NonNull<u8>>,
+    source: AllocationSource,
+}
+
+// the mapped pointer is only ever accessed through &mut self
+unsafe impl Send for Allocation {}
+unsafe impl Sync for Allocation {}
+
+impl Allocation {
+    pub fn memory(&self) -> vk::DeviceMemory {
+        self.memory
+    }
+
+    pub fn offset(&self) -> vk::DeviceSize {
+        self.offset
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    pub fn memory_type_index(&self) -> u32 {
+        self.memory_type_index
+    }
+
+    // None if the memory isn't host visible
+    pub fn mapped_ptr(&self) -> Option<*mut u8> {
+        self.mapped.map(NonNull::as_ptr)
+    }
+
+    // copies `data` into the mapped memory at `offset`, panics if it doesn't fit
+    // or the allocation isn't host visible
+    pub fn write<T: Copy>(&mut self, offset: vk::DeviceSize, data: &[T]) {
+        let size = std::mem::size_of_val(data) as vk::DeviceSize;
+        assert!(offset + size <= self.size, "Write out of allocation bounds");
+
+        let ptr = self
+            .mapped_ptr()
+            .expect("Called Allocation::write on non host visible memory");
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr() as *const u8,
+                ptr.add(offset as usize),
+                size as usize,
+            );
+        }
+    }
+}
+
+// free ranges are kept sorted by offset and merged on free,
+// so the block doesn't fragment over time
+#[derive(Debug)]
+struct MemoryBlock {
+    memory: vk::DeviceMemory,
+    size: vk::DeviceSize,
+    mapped: Option<NonNull<u8>>,
+    free_ranges: Vec<(vk::DeviceSize, vk::DeviceSize)>,
+    allocation_count: usize,
+}
+
+unsafe impl Send for MemoryBlock {}
+unsafe impl Sync for MemoryBlock {}
+
+impl MemoryBlock {
+    fn new(memory: vk::DeviceMemory, size: vk::DeviceSize, mapped: Option<NonNull<u8>>) -> Self {
+        MemoryBlock {
+            memory,
+            size,
+            mapped,
+            free_ranges: vec![(0, size)],
+            allocation_count: 0,
+        }
+    }
+
+    // best fit, returns the aligned offset
+    fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        let (index, aligned_offset) = self
+            .free_ranges
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &(offset, range_size))| {
+                let aligned_offset = align_up(offset, alignment);
+                let padding = aligned_offset - offset;
+
+                if padding + size <= range_size {
+                    Some((index, aligned_offset, range_size - padding - size))
+                } else {
+                    None
+                }
+            })
+            .min_by_key(|&(_, _, leftover)| leftover)
+            .map(|(index, aligned_offset, _)| (index, aligned_offset))?;
+
+        let (offset, range_size) = self.free_ranges.remove(index);
+        let end = aligned_offset + size;
+        let range_end = offset + range_size;
+
+        // keep the remaining space around the allocation free
+        if end < range_end {
+            self.free_ranges.insert(index, (end, range_end - end));
+        }
+        if offset < aligned_offset {
+            self.free_ranges
+                .insert(index, (offset, aligned_offset - offset));
+        }
+
+        self.allocation_count += 1;
+        Some(aligned_offset)
+    }
+
+    fn free(&mut self, offset: vk::DeviceSize, size: vk::DeviceSize) {
+        let index = self
+            .free_ranges
+            .iter()
+            .position(|&(free_offset, _)| free_offset > offset)
+            .unwrap_or(self.free_ranges.len());
+        self.free_ranges.insert(index, (offset, size));
+
+        // merge with the next range
+        if index + 1 < self.free_ranges.len() {
+            let (next_offset, next_size) = self.free_ranges[index + 1];
+            if offset + size == next_offset {
+                self.free_ranges[index].1 += next_size;
+                self.free_ranges.remove(index + 1);
+            }
+        }
+
+        // merge with the previous range
+        if index > 0 {
+            let (prev_offset, prev_size) = self.free_ranges[index - 1];
+            if prev_offset + prev_size == offset {
+                self.free_ranges[index - 1].1 += self.free_ranges[index].1;
+                self.free_ranges.remove(index);
+            }
+        }
+
+        self.allocation_count -= 1;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.allocation_count == 0
+    }
+
+    fn used(&self) -> vk::DeviceSize {
+        self.size
+            - self
+                .free_ranges
+                .iter()
+                .map(|(_, size)| size)
+                .sum::<vk::DeviceSize>()
+    }
+}
+
+#[derive(Debug)]
+struct MemoryPool {
+    memory_type_index: u32,
+    kind: ResourceKind,
+    // freed blocks leave a None behind, so block indices stay valid
+    blocks: Vec<Option<MemoryBlock>>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllocatorStats {
+    pub block_count: usize,
+    pub dedicated_count: usize,
+    pub allocation_count: usize,
+    // memory allocated from the device
+    pub bytes_allocated: vk::DeviceSize,
+    // memory handed out to allocations
+    pub bytes_used: vk::DeviceSize,
+}
+
+impl fmt::Display for AllocatorStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} allocations in {} blocks + {} dedicated, {:.2}/{:.2} MiB used",
+            self.allocation_count,
+            self.block_count,
+            self.dedicated_count,
+            self.bytes_used as f64 / (1024.0 * 1024.0),
+            self.bytes_allocated as f64 / (1024.0 * 1024.0),
+        )
+    }
+}
+
+pub struct Allocator {
+    device: Device,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pools: Vec<MemoryPool>,
+    dedicated_count: usize,
+    dedicated_bytes: vk::DeviceSize,
+}
+
+impl Allocator {
+    pub fn new(instance: &Instance, physical_device: vk::PhysicalDevice, device: &Device) -> Self {
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+        Allocator {
+            device: device.clone(),
+            memory_properties,
+            pools: Vec::new(),
+            dedicated_count: 0,
+            dedicated_bytes: 0,
+        }
+    }
+
+    pub fn memory_properties(&self) -> &vk::PhysicalDeviceMemoryProperties {
+        &self.memory_properties
+    }
+
+    pub fn find_memory_type(&self, type_bits: u32, usage: MemoryUsage) -> Option<u32> {
+        let (required, preferred) = usage.flags();
+
+        self.find_memory_type_with_flags(type_bits, required | preferred)
+            .or_else(|| self.find_memory_type_with_flags(type_bits, required))
+    }
+
+    fn find_memory_type_with_flags(
+        &self,
+        type_bits: u32,
+        flags: vk::MemoryPropertyFlags,
+    ) -> Option<u32> {
+        self.memory_properties.memory_types[..self.memory_properties.memory_type_count as usize]
+            .iter()
+            .enumerate()
+            .find(|(index, memory_type)| {
+                type_bits & (1 << index) != 0 && memory_type.property_flags.contains(flags)
+            })
+            .map(|(index, _)| index as u32)
+    }
+
+    fn is_host_visible(&self, memory_type_index: u32) -> bool {
+        self.memory_properties.memory_types[memory_type_index as usize]
+            .property_flags
+            .contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+    }
+
+    unsafe fn allocate_device_memory(
+        &self,
+        size: vk::DeviceSize,
+        memory_type_index: u32,
+    ) -> VkResult<(vk::DeviceMemory, Option<NonNull<u8>>)> {
+        let allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(size)
+            .memory_type_index(memory_type_index);
+
+        let memory = self.device.allocate_memory(&allocate_info, None)?;
+
+        // host visible memory stays persistently mapped
+        let mapped = if self.is_host_visible(memory_type_index) {
+            match self
+                .device
+                .map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+            {
+                Ok(ptr) => NonNull::new(ptr as *mut u8),
+                Err(err) => {
+                    self.device.free_memory(memory, None);
+                    return Err(err);
+                }
+            }
+        } else {
+            None
+        };
+
+        Ok((memory, mapped))
+    }
+
+    unsafe fn allocate(
+        &mut self,
+        requirements: vk::MemoryRequirements,
+        usage: MemoryUsage,
+        kind: ResourceKind,
+    ) -> Result<Allocation, AllocatorError> {
+        let memory_type_index = self
+            .find_memory_type(requirements.memory_type_bits, usage)
+            .ok_or(AllocatorError::NoCompatibleMemoryType)?;
+
+        if is_dedicated(requirements.size) {
+            let (memory, mapped) =
+                self.allocate_device_memory(requirements.size, memory_type_index)?;
+
+            self.dedicated_count += 1;
+            self.dedicated_bytes += requirements.size;
+
+            return Ok(Allocation {
+                memory,
+                offset: 0,
+                size: requirements.size,
+                memory_type_index,
+                mapped,
+                source: AllocationSource::Dedicated,
+            });
+        }
+
+        let pool_index = match self
+            .pools
+            .iter()
+            .position(|pool| pool.memory_type_index == memory_type_index && pool.kind == kind)
+        {
+            Some(pool_index) => pool_index,
+            None => {
+                self.pools.push(MemoryPool {
+                    memory_type_index,
+                    kind,
+                    blocks: Vec::new(),
+                });
+                self.pools.len() - 1
+            }
+        };
+
+        // try the existing blocks first
+        for (block_index, block) in self.pools[pool_index].blocks.iter_mut().enumerate() {
+            if let Some(block) = block {
+                if let Some(offset) = block.allocate(requirements.size, requirements.alignment) {
+                    return Ok(Allocation {
+                        memory: block.memory,
+                        offset,
+                        size: requirements.size,
+                        memory_type_index,
+                        mapped: block
+                            .mapped
+                            .map(|ptr| NonNull::new_unchecked(ptr.as_ptr().add(offset as usize))),
+                        source: AllocationSource::Block {
+                            pool: pool_index,
+                            block: block_index,
+                        },
+                    });
+                }
+            }
+        }
+
+        // no space left, allocate a new block
+        let heap_index =
+            self.memory_properties.memory_types[memory_type_index as usize].heap_index as usize;
+        let heap_size = self.memory_properties.memory_heaps[heap_index].size;
+        // small heaps (e.g. 256 MiB device local + host visible) get smaller blocks
+        let block_size = BLOCK_SIZE.min(heap_size / 8).max(requirements.size);
+
+        let (memory, mapped) = self.allocate_device_memory(block_size, memory_type_index)?;
+
+        let mut block = MemoryBlock::new(memory, block_size, mapped);
+        let offset = block
+            .allocate(requirements.size, requirements.alignment)
+            .expect("Fresh memory block too small");
+
+        let blocks = &mut self.pools[pool_index].blocks;
+        let block_index = match blocks.iter().position(Option::is_none) {
+            Some(block_index) => {
+                blocks[block_index] = Some(block);
+                block_index
+            }
+            None => {
+                blocks.push(Some(block));
+                blocks.len() - 1
+            }
+        };
+
+        Ok(Allocation {
+            memory,
+            offset,
+            size: requirements.size,
+            memory_type_index,
+            mapped: mapped.map(|ptr| NonNull::new_unchecked(ptr.as_ptr().add(offset as usize))),
+            source: AllocationSource::Block {
+                pool: pool_index,
+                block: block_index,
+            },
+        })
+    }
+
+    pub unsafe fn free(&mut self, allocation: Allocation) {
+        match allocation.source {
+            AllocationSource::Dedicated => {
+                self.device.free_memory(allocation.memory, None);
+                self.dedicated_count -= 1;
+                self.dedicated_bytes -= allocation.size;
+            }
+            AllocationSource::Block { pool, block } => {
+                let blocks = &mut self.pools[pool].blocks;
+                let freed_block = {
+                    let memory_block = blocks[block]
+                        .as_mut()
+                        .expect("Allocation refers to freed memory block");
+                    memory_block.free(allocation.offset, allocation.size);
+                    memory_block.is_empty()
+                };
+
+                // give empty blocks back to the driver, except for a few spares
+                if freed_block {
+                    let empty_blocks = blocks
+                        .iter()
+                        .filter(|block| block.as_ref().is_some_and(MemoryBlock::is_empty))
+                        .count();
+
+                    if empty_blocks > SPARE_BLOCKS {
+                        let memory_block = blocks[block].take().unwrap();
+                        self.device.free_memory(memory_block.memory, None);
+                    }
+                }
+            }
+        }
+    }
+
+    pub unsafe fn create_buffer(
+        &mut self,
+        create_info: &vk::BufferCreateInfo,
+        usage: MemoryUsage,
+    ) -> Result<(vk::Buffer, Allocation), AllocatorError> {
+        let buffer = self.device.create_buffer(create_info, None)?;
+        let requirements = self.device.get_buffer_memory_requirements(buffer);
+
+        let allocation = match self.allocate(requirements, usage, ResourceKind::Linear) {
+            Ok(allocation) => allocation,
+            Err(err) => {
+                self.device.destroy_buffer(buffer, None);
+                return Err(err);
+            }
+        };
+
+        if let Err(err) =
+            self.device
+                .bind_buffer_memory(buffer, allocation.memory, allocation.offset)
+        {
+            self.device.destroy_buffer(buffer, None);
+            self.free(allocation);
+            return Err(err.into());
+        }
+
+        Ok((buffer, allocation))
+    }
+
+    pub unsafe fn destroy_buffer(&mut self, buffer: vk::Buffer, allocation: Allocation) {
+        self.device.destroy_buffer(buffer, None);
+        self.free(allocation);
+    }
+
+    pub unsafe fn create_image(
+        &mut self,
+        create_info: &vk::ImageCreateInfo,
+        usage: MemoryUsage,
+    ) -> Result<(vk::Image, Allocation), AllocatorError> {
+        let image = self.device.create_image(create_info, None)?;
+        let requirements = self.device.get_image_memory_requirements(image);
+
+        let kind = if create_info.tiling == vk::ImageTiling::LINEAR {
+            ResourceKind::Linear
+        } else {
+            ResourceKind::Optimal
+        };
+
+        let allocation = match self.allocate(requirements, usage, kind) {
+            Ok(allocation) => allocation,
+            Err(err) => {
+                self.device.destroy_image(image, None);
+                return Err(err);
+            }
+        };
+
+        if let Err(err) = self
+            .device
+            .bind_image_memory(image, allocation.memory, allocation.offset)
+        {
+            self.device.destroy_image(image, None);
+            self.free(allocation);
+            return Err(err.into());
+        }
+
+        Ok((image, allocation))
+    }
+
+    pub unsafe fn destroy_image(&mut self, image: vk::Image, allocation: Allocation) {
+        self.device.destroy_image(image, None);
+        self.free(allocation);
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let mut stats = AllocatorStats {
+            dedicated_count: self.dedicated_count,
+            allocation_count: self.dedicated_count,
+            bytes_allocated: self.dedicated_bytes,
+            bytes_used: self.dedicated_bytes,
+            ..Default::default()
+        };
+
+        for block in self
+            .pools
+            .iter()
+            .flat_map(|pool| pool.blocks.iter().filter_map(Option::as_ref))
+        {
+            stats.block_count += 1;
+            stats.allocation_count += block.allocation_count;
+            stats.bytes_allocated += block.size;
+            stats.bytes_used += block.used();
+        }
+
+        stats
+    }
+
+    // frees all device memory, every allocation has to be freed or leaked before
+    pub unsafe fn destroy(&mut self) {
+        info!("Allocator: {}", self.stats());
+
+        for pool in self.pools.drain(..) {
+            for block in pool.blocks.into_iter().flatten() {
+                self.device.free_memory(block.memory, None);
+            }
+        }
+    }
+}
+
+// Bump allocator inside of one persistently mapped buffer, split into one region per frame.
+// Meant for data that is rewritten every frame (uniforms, staging for uploads),
+// resetting a frame region is free.
+pub struct LinearAllocator {
+    buffer: vk::Buffer,
+    allocation: Option<Allocation>,
+    frame_size: vk::DeviceSize,
+    frame: usize,
+    head: vk::DeviceSize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LinearAllocation {
+    pub buffer: vk::Buffer,
+    // offset into the buffer
+    pub offset: vk::DeviceSize,
+    pub size: vk::DeviceSize,
+    ptr: *mut u8,
+}
+
+impl LinearAllocation {
+    pub fn write<T: Copy>(&self, data: &[T]) {
+        let size = std::mem::size_of_val(data);
+        assert!(
+            size as vk::DeviceSize <= self.size,
+            "Write out of allocation bounds"
+        );
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr() as *const u8, self.ptr, size);
+        }
+    }
+
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.ptr as *mut c_void
+    }
+}
+
+impl LinearAllocator {
+    pub unsafe fn new(
+        allocator: &mut Allocator,
+        usage: vk::BufferUsageFlags,
+        frame_size: vk::DeviceSize,
+        frame_count: usize,
+    ) -> Result<Self, AllocatorError> {
+        let buffer_create_info = vk::BufferCreateInfo::builder()
+            .size(frame_size * frame_count as vk::DeviceSize)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+        let (buffer, allocation) =
+            allocator.create_buffer(&buffer_create_info, MemoryUsage::CpuToGpu)?;
+
+        Ok(LinearAllocator {
+            buffer,
+            allocation: Some(allocation),
+            frame_size,
+            frame: 0,
+            head: 0,
+        })
+    }
+
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    // starts allocating from the region of `frame`, everything allocated
+    // the last time this frame was used is invalidated
+    pub fn reset(&mut self, frame: usize) {
+        self.frame = frame;
+        self.head = 0;
+    }
+
+    // None if the frame region is full
+    pub fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> Option<LinearAllocation> {
+        let offset = self.bump(size, alignment)?;
+        let base = self.allocation.as_ref()?.mapped_ptr()?;
+
+        Some(LinearAllocation {
+            buffer: self.buffer,
+            offset,
+            size,
+            ptr: unsafe { base.add(offset as usize) },
+        })
+    }
+
+    // the offset into the buffer, aligned there since `frame_size` doesn't have to be
+    fn bump(&mut self, size: vk::DeviceSize, alignment: vk::DeviceSize) -> Option<vk::DeviceSize> {
+        let frame_start = self.frame as vk::DeviceSize * self.frame_size;
+        let offset = align_up(frame_start + self.head, alignment.max(1));
+        if offset + size > frame_start + self.frame_size {
+            return None;
+        }
+        self.head = offset + size - frame_start;
+
+        Some(offset)
+    }
+
+    pub unsafe fn destroy(&mut self, allocator: &mut Allocator) {
+        if let Some(allocation) = self.allocation.take() {
+            allocator.destroy_buffer(self.buffer, allocation);
+        }
+    }
+}
+
+// large resources get their own allocation instead of filling half a block
+fn is_dedicated(size: vk::DeviceSize) -> bool {
+    size > BLOCK_SIZE / 2
+}
+
+pub fn align_up(value: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    value.div_ceil(alignment) * alignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(size: vk::DeviceSize) -> MemoryBlock {
+        MemoryBlock::new(vk::DeviceMemory::null(), size, None)
+    }
+
+    #[test]
+    fn aligned_allocations() {
+        let mut block = block(1024);
+
+        assert_eq!(block.allocate(10, 1), Some(0));
+        assert_eq!(block.allocate(16, 64), Some(64));
+        assert_eq!(block.allocate(8, 256), Some(256));
+
+        // the padding in front of the aligned allocations stays free
+        assert_eq!(block.free_ranges, vec![(10, 54), (80, 176), (264, 760)]);
+        assert_eq!(block.used(), 10 + 16 + 8);
+        assert_eq!(block.allocation_count, 3);
+    }
+
+    #[test]
+    fn best_fit() {
+        let mut block = block(1024);
+        let a = block.allocate(100, 1).unwrap();
+        let _b = block.allocate(10, 1).unwrap();
+        let c = block.allocate(40, 1).unwrap();
+        let _d = block.allocate(10, 1).unwrap();
+        block.free(a, 100);
+        block.free(c, 40);
+
+        // the 40 byte hole fits best
+        assert_eq!(block.allocate(30, 1), Some(c));
+    }
+
+    #[test]
+    fn free_merges_neighbours() {
+        let mut block = block(300);
+        let a = block.allocate(100, 1).unwrap();
+        let b = block.allocate(100, 1).unwrap();
+        let c = block.allocate(100, 1).unwrap();
+        assert!(block.free_ranges.is_empty());
+
+        block.free(a, 100);
+        block.free(c, 100);
+        assert_eq!(block.free_ranges, vec![(0, 100), (200, 100)]);
+
+        // freeing the middle joins both sides into one range
+        block.free(b, 100);
+        assert_eq!(block.free_ranges, vec![(0, 300)]);
+        assert!(block.is_empty());
+        assert_eq!(block.used(), 0);
+    }
+
+    #[test]
+    fn exhaustion() {
+        let mut block = block(256);
+
+        assert_eq!(block.allocate(200, 1), Some(0));
+        assert_eq!(block.allocate(64, 1), None);
+        // enough bytes left, but not at the required alignment
+        assert_eq!(block.allocate(32, 128), None);
+        assert_eq!(block.allocate(56, 1), Some(200));
+        assert_eq!(block.allocate(1, 1), None);
+        assert_eq!(block.allocation_count, 2);
+    }
+
+    #[test]
+    fn linear_offsets_are_aligned_in_the_buffer() {
+        let mut linear = LinearAllocator {
+            buffer: vk::Buffer::null(),
+            allocation: None,
+            frame_size: 100,
+            frame: 0,
+            head: 0,
+        };
+
+        assert_eq!(linear.bump(10, 64), Some(0));
+        assert_eq!(linear.bump(10, 64), Some(64));
+
+        // the second frame starts at 100, which isn't aligned to 64
+        linear.reset(1);
+        assert_eq!(linear.bump(10, 64), Some(128));
+        assert_eq!(linear.bump(8, 4), Some(140));
+        // the next multiple of 64 is past the frame's end
+        assert_eq!(linear.bump(10, 64), None);
+        assert_eq!(linear.bump(52, 1), Some(148));
+        assert_eq!(linear.bump(1, 1), None);
+    }
+
+    #[test]
+    fn large_allocations_are_dedicated() {
+        assert!(!is_dedicated(1));
+        assert!(!is_dedicated(BLOCK_SIZE / 2));
+        assert!(is_dedicated(BLOCK_SIZE / 2 + 1));
+        assert!(is_dedicated(BLOCK_SIZE));
+    }
+}
@@ -0,0 +1,245 @@
+// Render backends
+//
+// `RenderSystem` collects everything drawn this frame from the world and hands it to
+// a backend, the Vulkan `Renderer` or the `NullRenderer`, which only records the frames
+// so game logic can be tested without a Vulkan loader.
+
+use super::{CameraMatrices, Frame, Screenshot};
+use crate::{
+    components::{Material, Rotation2, Rotation3, Sprite, Text, Translation2, Translation3},
+    debug_draw::{DebugDraw, DebugLabel, DebugLine},
+    dev_ui::{DevUi, UiFrame},
+    profiler::{PassTiming, Profiler},
+    WindowSize,
+};
+use nalgebra_glm as glm;
+use specs::{Join, Read, ReadExpect, ReadStorage, System, Write};
+use std::sync::{Arc, Mutex};
+use winit::dpi::LogicalSize;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Draw<'a> {
+    pub pipeline: &'a str,
+    pub model: glm::Mat4,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteDraw<'a> {
+    pub sprite: &'a Sprite,
+    pub position: glm::Vec2,
+    // counterclockwise in radians
+    pub rotation: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextDraw<'a> {
+    pub text: &'a Text,
+    // in the text's space
+    pub position: glm::Vec2,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderFrame<'a> {
+    pub camera: &'a CameraMatrices,
+    pub window_size: LogicalSize,
+    // in no particular order
+    pub draws: &'a [Draw<'a>],
+    // sorted by the backend
+    pub sprites: &'a [SpriteDraw<'a>],
+    // drawn in order within a space and font
+    pub texts: &'a [TextDraw<'a>],
+    // empty outside of --dev
+    pub debug_lines: &'a [DebugLine],
+    pub debug_labels: &'a [DebugLabel],
+    // the developer UI, drawn over everything, None outside of --dev or while hidden
+    pub ui: Option<&'a UiFrame>,
+    // the backend should save this frame as a screenshot
+    pub screenshot: bool,
+}
+
+pub trait RenderBackend: Send {
+    // called once per dispatch
+    fn render(&mut self, frame: &RenderFrame);
+
+    // the last rendered frame, only for backends that read their frames back
+    fn read_frame(&self) -> Option<Frame> {
+        None
+    }
+
+    // CPU and GPU time of every pass of a recent frame
+    fn pass_timings(&self) -> Vec<PassTiming> {
+        Vec::new()
+    }
+}
+
+pub struct RenderSystem<B: RenderBackend> {
+    pub backend: B,
+}
+
+impl<B: RenderBackend> RenderSystem<B> {
+    pub fn new(backend: B) -> Self {
+        RenderSystem { backend }
+    }
+}
+
+impl<'a, B: RenderBackend> System<'a> for RenderSystem<B> {
+    type SystemData = (
+        Read<'a, CameraMatrices>,
+        ReadExpect<'a, WindowSize>,
+        Write<'a, Screenshot>,
+        Read<'a, Profiler>,
+        Read<'a, DebugDraw>,
+        Write<'a, DevUi>,
+        ReadStorage<'a, Material>,
+        ReadStorage<'a, Translation3<f32>>,
+        ReadStorage<'a, Rotation3<f32>>,
+        ReadStorage<'a, Sprite>,
+        ReadStorage<'a, Translation2<f32>>,
+        ReadStorage<'a, Rotation2<f32>>,
+        ReadStorage<'a, Text>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            camera,
+            window_size,
+            mut screenshot,
+            profiler,
+            debug_draw,
+            mut dev_ui,
+            materials,
+            translations,
+            rotations,
+            sprites,
+            translations_2d,
+            rotations_2d,
+            texts,
+        ): Self::SystemData,
+    ) {
+        let draws = (&materials, &translations, rotations.maybe())
+            .join()
+            .map(|(material, translation, rotation)| Draw {
+                pipeline: &material.pipeline,
+                model: match rotation {
+                    Some(rotation) => translation.0.to_homogeneous() * rotation.0.to_homogeneous(),
+                    None => translation.0.to_homogeneous(),
+                },
+            })
+            .collect::<Vec<_>>();
+
+        let sprites = (&sprites, &translations_2d, rotations_2d.maybe())
+            .join()
+            .map(|(sprite, translation, rotation)| SpriteDraw {
+                sprite,
+                position: translation.0.vector,
+                rotation: rotation.map_or(0.0, |rotation| rotation.0.angle()),
+            })
+            .collect::<Vec<_>>();
+
+        let texts = (&texts, &translations_2d)
+            .join()
+            .map(|(text, translation)| TextDraw {
+                text,
+                position: translation.0.vector,
+            })
+            .collect::<Vec<_>>();
+
+        let (debug_lines, debug_labels) = debug_draw.take();
+        let ui = dev_ui.take_frame();
+
+        self.backend.render(&RenderFrame {
+            camera: &camera,
+            window_size: window_size.0,
+            draws: &draws,
+            sprites: &sprites,
+            texts: &texts,
+            debug_lines: &debug_lines,
+            debug_labels: &debug_labels,
+            ui: ui.as_ref(),
+            screenshot: screenshot.requested,
+        });
+        screenshot.requested = false;
+
+        profiler.record_passes(self.backend.pass_timings());
+    }
+}
+
+// an owned copy of a rendered frame
+#[derive(Debug, Clone)]
+pub struct RecordedFrame {
+    pub camera: CameraMatrices,
+    pub window_size: LogicalSize,
+    // (pipeline, model matrix)
+    pub draws: Vec<(String, glm::Mat4)>,
+    // (sprite, position, rotation)
+    pub sprites: Vec<(Sprite, glm::Vec2, f32)>,
+    // (text, position)
+    pub texts: Vec<(Text, glm::Vec2)>,
+    pub debug_lines: Vec<DebugLine>,
+    pub debug_labels: Vec<DebugLabel>,
+    pub ui: Option<UiFrame>,
+    pub screenshot: bool,
+}
+
+// only the latest frames are kept so long running tests don't grow without bound
+const MAX_RECORDED_FRAMES: usize = 64;
+
+// clones share the recorded frames, keep one to inspect them after handing
+// the backend to the game
+#[derive(Debug, Clone, Default)]
+pub struct NullRenderer {
+    frames: Arc<Mutex<Vec<RecordedFrame>>>,
+}
+
+impl NullRenderer {
+    pub fn new() -> Self {
+        NullRenderer::default()
+    }
+
+    // the last MAX_RECORDED_FRAMES frames, oldest first
+    pub fn frames(&self) -> Vec<RecordedFrame> {
+        self.frames.lock().unwrap().clone()
+    }
+
+    pub fn last_frame(&self) -> Option<RecordedFrame> {
+        self.frames.lock().unwrap().last().cloned()
+    }
+
+    pub fn clear(&self) {
+        self.frames.lock().unwrap().clear();
+    }
+}
+
+impl RenderBackend for NullRenderer {
+    fn render(&mut self, frame: &RenderFrame) {
+        let mut frames = self.frames.lock().unwrap();
+        if frames.len() == MAX_RECORDED_FRAMES {
+            frames.remove(0);
+        }
+
+        frames.push(RecordedFrame {
+            camera: *frame.camera,
+            window_size: frame.window_size,
+            draws: frame
+                .draws
+                .iter()
+                .map(|draw| (draw.pipeline.to_owned(), draw.model))
+                .collect(),
+            sprites: frame
+                .sprites
+                .iter()
+                .map(|draw| (draw.sprite.clone(), draw.position, draw.rotation))
+                .collect(),
+            texts: frame
+                .texts
+                .iter()
+                .map(|draw| (draw.text.clone(), draw.position))
+                .collect(),
+            debug_lines: frame.debug_lines.to_vec(),
+            debug_labels: frame.debug_labels.to_vec(),
+            ui: frame.ui.cloned(),
+            screenshot: frame.screenshot,
+        });
+    }
+}
@@ -0,0 +1,57 @@
+// Object names and command buffer labels
+//
+// Shown by validation messages and capture tools instead of raw handles.
+// Without VK_EXT_debug_utils every call does nothing.
+
+use ash::{
+    extensions::ext::DebugUtils,
+    vk::{self, Handle},
+};
+use std::ffi::CString;
+
+pub struct DebugNames {
+    loader: Option<DebugUtils>,
+    device: vk::Device,
+}
+
+impl DebugNames {
+    pub fn new(loader: Option<DebugUtils>, device: vk::Device) -> Self {
+        DebugNames { loader, device }
+    }
+
+    pub unsafe fn name<H: Handle>(&self, handle: H, name: &str) {
+        if let Some(loader) = &self.loader {
+            let name = CString::new(name).unwrap();
+            let name_info = vk::DebugUtilsObjectNameInfoEXT::builder()
+                .object_type(H::TYPE)
+                .object_handle(handle.as_raw())
+                .object_name(&name);
+
+            // only used for debugging, not worth failing over
+            let _ = loader.debug_utils_set_object_name(self.device, &name_info);
+        }
+    }
+
+    // every object gets the name with its index appended
+    pub unsafe fn name_all<H: Handle + Copy>(&self, handles: &[H], name: &str) {
+        for (index, handle) in handles.iter().enumerate() {
+            self.name(*handle, &format!("{} {}", name, index));
+        }
+    }
+
+    // has to be ended with `end_label` in the same command buffer
+    pub unsafe fn begin_label(&self, command_buffer: vk::CommandBuffer, name: &str) {
+        if let Some(loader) = &self.loader {
+            let name = CString::new(name).unwrap();
+            let label = vk::DebugUtilsLabelEXT::builder().label_name(&name);
+
+            loader.cmd_begin_debug_utils_label(command_buffer, &label);
+        }
+    }
+
+    pub unsafe fn end_label(&self, command_buffer: vk::CommandBuffer) {
+        if let Some(loader) = &self.loader {
+            loader.cmd_end_debug_utils_label(command_buffer);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// Physical device ranking
+//
+// Devices are ranked by type first (discrete > integrated > virtual > CPU),
+// then by device local memory and finally by the maximum 2D image size.
+// The UUID is the device UUID from Vulkan 1.1, stable across driver updates.
+// Vulkan 1.0 devices fall back to their enumeration index instead.
+
+use super::{
+    device_candidate, instance::InstanceConfig, supports_device_extensions, RendererInitError,
+};
+use ash::{
+    prelude::VkResult,
+    version::{EntryV1_0, InstanceV1_0, InstanceV1_1},
+    vk, vk_make_version, Entry, Instance, InstanceError, LoadingError,
+};
+use std::{
+    ffi::{CStr, CString},
+    fmt,
+};
+
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub uuid: Option<String>,
+    // position in vkEnumeratePhysicalDevices
+    pub index: usize,
+    pub device_type: vk::PhysicalDeviceType,
+    // device local memory in bytes
+    pub memory: u64,
+    pub max_image_dimension: u32,
+}
+
+impl DeviceInfo {
+    // api_version is the instance's, the device UUID needs 1.1 on both sides
+    pub unsafe fn new(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        index: usize,
+        api_version: u32,
+    ) -> Self {
+        let properties = instance.get_physical_device_properties(physical_device);
+        let memory_properties = instance.get_physical_device_memory_properties(physical_device);
+
+        let memory = memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize]
+            .iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum();
+
+        let uuid = if api_version.min(properties.api_version) >= vk_make_version!(1, 1, 0) {
+            let mut id_properties = vk::PhysicalDeviceIDProperties::default();
+            let mut properties2 = vk::PhysicalDeviceProperties2::builder()
+                .next(&mut id_properties)
+                .build();
+            instance.get_physical_device_properties2(physical_device, &mut properties2);
+            Some(format_uuid(&id_properties.device_uuid))
+        } else {
+            None
+        };
+
+        DeviceInfo {
+            name: CStr::from_ptr(properties.device_name.as_ptr())
+                .to_string_lossy()
+                .into_owned(),
+            uuid,
+            index,
+            device_type: properties.device_type,
+            memory,
+            max_image_dimension: properties.limits.max_image_dimension2_d,
+        }
+    }
+
+    // higher is better, compared lexicographically
+    pub fn score(&self) -> (u32, u64, u32) {
+        let type_score = match self.device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+            vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+            vk::PhysicalDeviceType::CPU => 1,
+            _ => 0,
+        };
+
+        (type_score, self.memory, self.max_image_dimension)
+    }
+
+    // case insensitive, the UUID may be written with or without dashes
+    pub fn matches(&self, pin: &str) -> bool {
+        let pin = pin.trim().to_lowercase();
+        match &self.uuid {
+            Some(uuid) => {
+                self.name.to_lowercase() == pin || uuid.replace('-', "") == pin.replace('-', "")
+            }
+            None => self.name.to_lowercase() == pin || self.index.to_string() == pin,
+        }
+    }
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let device_type = match self.device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => "discrete",
+            vk::PhysicalDeviceType::INTEGRATED_GPU => "integrated",
+            vk::PhysicalDeviceType::VIRTUAL_GPU => "virtual",
+            vk::PhysicalDeviceType::CPU => "cpu",
+            _ => "other",
+        };
+
+        write!(
+            f,
+            "{} ({}, {} MiB, ",
+            self.name,
+            device_type,
+            self.memory / (1024 * 1024)
+        )?;
+        match &self.uuid {
+            Some(uuid) => write!(f, "{})", uuid),
+            None => write!(f, "index {})", self.index),
+        }
+    }
+}
+
+fn format_uuid(uuid: &[u8; vk::UUID_SIZE]) -> String {
+    let hex = uuid
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+// prints the usable devices ranked by score and then the unusable ones, used by --list-gpus
+// without a window there's no surface, so presentation support isn't checked
+pub fn list_gpus() -> Result<(), RendererInitError> {
+    unsafe {
+        let entry = Entry::new().map_err(|err| match err {
+            LoadingError::LibraryLoadError(err) => RendererInitError::LoadingError { err },
+        })?;
+
+        let instance_config = InstanceConfig::new(&entry, false, false)?;
+
+        let application_name = CString::new("evn").unwrap();
+        let app_info = vk::ApplicationInfo::builder()
+            .application_name(&application_name)
+            .api_version(instance_config.api_version);
+        let instance_create_info = vk::InstanceCreateInfo::builder().application_info(&app_info);
+
+        let instance = entry
+            .create_instance(&instance_create_info, None)
+            .map_err(|err| RendererInitError::InstanceError {
+                err: match err {
+                    InstanceError::LoadError(err) => format!("{:?}", err),
+                    InstanceError::VkError(result) => result.to_string(),
+                },
+            })?;
+
+        // usable means a graphics queue and swapchain support
+        let devices = instance
+            .enumerate_physical_devices()
+            .and_then(|physical_devices| {
+                physical_devices
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, physical_device)| {
+                        let info = DeviceInfo::new(
+                            &instance,
+                            physical_device,
+                            index,
+                            instance_config.api_version,
+                        );
+                        let usable = device_candidate(&instance, physical_device, None)?.is_some()
+                            && supports_device_extensions(&instance, physical_device)?;
+                        Ok((info, usable))
+                    })
+                    .collect::<VkResult<Vec<_>>>()
+            });
+        let mut devices = match devices {
+            Ok(devices) => devices,
+            Err(err) => {
+                instance.destroy_instance(None);
+                return Err(RendererInitError::PhysicalDeviceError {
+                    err: either::Either::Left(err),
+                });
+            }
+        };
+        devices.sort_by_key(|(device, _)| std::cmp::Reverse(device.score()));
+
+        // usable devices are numbered in the order they'd be picked
+        let (usable, unusable): (Vec<_>, Vec<_>) =
+            devices.into_iter().partition(|(_, usable)| *usable);
+        for (rank, (device, _)) in usable.iter().enumerate() {
+            println!("{}: {}", rank, device);
+        }
+        for (device, _) in &unusable {
+            println!("-: {} (not usable)", device);
+        }
+
+        instance.destroy_instance(None);
+    }
+
+    Ok(())
+}
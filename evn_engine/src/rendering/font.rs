@@ -0,0 +1,275 @@
+// TrueType fonts and glyph atlases
+//
+// Every font gets its own atlas. The printable ASCII glyphs are rasterized on the loader
+// thread at the common sizes, everything else is cached while rendering. Glyphs not drawn
+// in a frame may get evicted to make room for new ones.
+
+use super::texture::Texture;
+use crate::components::TextAlign;
+use rusttype::{
+    gpu_cache::{Cache, CacheWriteErr},
+    point, PositionedGlyph, Rect, Scale,
+};
+use std::{
+    fmt,
+    fs::File,
+    io::{Error as IoError, ErrorKind, Read},
+    path::Path,
+    sync::Mutex,
+};
+
+pub const ATLAS_SIZE: u32 = 1024;
+// the size world space text is rasterized at, in pixels
+pub const WORLD_RASTER_SIZE: f32 = 32.0;
+// the default size of screen space text and the world raster size
+const PRELOAD_SIZES: [f32; 2] = [16.0, WORLD_RASTER_SIZE];
+
+pub struct Font {
+    font: rusttype::Font<'static>,
+    // built on the loader thread, taken by the renderer, boxed to keep resources small
+    atlas: Mutex<Option<Box<GlyphAtlas>>>,
+}
+
+impl fmt::Debug for Font {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Font")
+            .field("glyph_count", &self.font.glyph_count())
+            .finish()
+    }
+}
+
+impl Font {
+    pub fn load(path: &Path) -> Result<Self, IoError> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        let font = rusttype::Font::from_bytes(bytes)
+            .map_err(|err| IoError::new(ErrorKind::InvalidData, err.to_string()))?;
+
+        let mut atlas = GlyphAtlas::new();
+        let preload = PRELOAD_SIZES
+            .iter()
+            .flat_map(|size| layout(&font, PRINTABLE_ASCII, *size, TextAlign::Left, None))
+            .collect::<Vec<_>>();
+        // only fails if the glyphs don't fit into the atlas
+        if let Err(err) = atlas.cache(&preload) {
+            return Err(IoError::new(ErrorKind::InvalidData, err.to_string()));
+        }
+
+        Ok(Font {
+            font,
+            atlas: Mutex::new(Some(Box::new(atlas))),
+        })
+    }
+
+    pub fn font(&self) -> &rusttype::Font<'static> {
+        &self.font
+    }
+
+    // the preloaded atlas the first time, an empty one afterwards
+    pub fn take_atlas(&self) -> GlyphAtlas {
+        self.atlas
+            .lock()
+            .unwrap()
+            .take()
+            .map(|atlas| *atlas)
+            .unwrap_or_default()
+    }
+}
+
+const PRINTABLE_ASCII: &str = " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ\
+                               [\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+
+pub struct GlyphAtlas {
+    cache: Cache<'static>,
+    // white, the coverage is the alpha channel
+    pixels: Vec<u8>,
+    // regions written since the last `take_dirty`
+    dirty: Vec<Rect<u32>>,
+}
+
+impl GlyphAtlas {
+    pub fn new() -> Self {
+        let mut pixels = vec![255; (ATLAS_SIZE * ATLAS_SIZE * 4) as usize];
+        for pixel in pixels.chunks_mut(4) {
+            pixel[3] = 0;
+        }
+
+        GlyphAtlas {
+            cache: Cache::builder().dimensions(ATLAS_SIZE, ATLAS_SIZE).build(),
+            pixels,
+            dirty: Vec::new(),
+        }
+    }
+
+    // makes sure all glyphs are in the atlas, glyphs not in `glyphs` may get evicted
+    pub fn cache(&mut self, glyphs: &[PositionedGlyph<'static>]) -> Result<(), CacheWriteErr> {
+        for glyph in glyphs {
+            self.cache.queue_glyph(0, glyph.clone());
+        }
+
+        let pixels = &mut self.pixels;
+        let dirty = &mut self.dirty;
+        self.cache
+            .cache_queued(|rect, coverage| {
+                let width = rect.width() as usize;
+                for (row, coverage) in coverage.chunks(width).enumerate() {
+                    let start = (((rect.min.y as usize + row) * ATLAS_SIZE as usize)
+                        + rect.min.x as usize)
+                        * 4;
+                    for (pixel, coverage) in
+                        pixels[start..start + width * 4].chunks_mut(4).zip(coverage)
+                    {
+                        pixel[3] = *coverage;
+                    }
+                }
+                dirty.push(rect);
+            })
+            .map(|_| ())
+    }
+
+    // (normalized texture coordinates, pixels relative to the layout origin),
+    // None for glyphs without an outline, like spaces
+    pub fn rect_for(&self, glyph: &PositionedGlyph<'static>) -> Option<(Rect<f32>, Rect<i32>)> {
+        self.cache.rect_for(0, glyph).ok().and_then(|rect| rect)
+    }
+
+    // the regions that changed since the last call, with their RGBA pixels
+    pub fn take_dirty(&mut self) -> Vec<(Rect<u32>, Vec<u8>)> {
+        let pixels = &self.pixels;
+
+        self.dirty
+            .drain(..)
+            .map(|rect| {
+                let mut region = Vec::with_capacity((rect.width() * rect.height() * 4) as usize);
+                for y in rect.min.y..rect.max.y {
+                    let start = ((y * ATLAS_SIZE + rect.min.x) * 4) as usize;
+                    region.extend_from_slice(&pixels[start..start + rect.width() as usize * 4]);
+                }
+
+                (rect, region)
+            })
+            .collect()
+    }
+
+    // the whole atlas gets uploaded again
+    pub fn invalidate(&mut self) {
+        self.dirty.clear();
+        self.dirty.push(Rect {
+            min: point(0, 0),
+            max: point(ATLAS_SIZE, ATLAS_SIZE),
+        });
+    }
+
+    // the whole atlas, marks everything as uploaded
+    pub fn texture(&mut self) -> Texture {
+        self.dirty.clear();
+
+        Texture {
+            width: ATLAS_SIZE,
+            height: ATLAS_SIZE,
+            pixels: self.pixels.clone(),
+        }
+    }
+}
+
+impl Default for GlyphAtlas {
+    fn default() -> Self {
+        GlyphAtlas::new()
+    }
+}
+
+// Lines are wrapped at spaces to fit `wrap_width` (words longer than that aren't split)
+// and aligned to the origin. Positioned in pixels with Y pointing down, (0, 0) is the
+// top of the first line.
+pub fn layout(
+    font: &rusttype::Font<'static>,
+    text: &str,
+    size: f32,
+    align: TextAlign,
+    wrap_width: Option<f32>,
+) -> Vec<PositionedGlyph<'static>> {
+    let scale = Scale::uniform(size);
+    let v_metrics = font.v_metrics(scale);
+    let line_height = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
+
+    let lines = text
+        .lines()
+        .flat_map(|paragraph| wrap(font, scale, paragraph, wrap_width))
+        .collect::<Vec<_>>();
+
+    let mut glyphs = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let width = line_width(font, scale, line);
+        let x = match align {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => -width / 2.0,
+            TextAlign::Right => -width,
+        };
+        let baseline = v_metrics.ascent + index as f32 * line_height;
+
+        let mut caret = 0.0;
+        let mut previous = None;
+        for c in line.chars() {
+            let glyph = font.glyph(c);
+            if let Some(previous) = previous {
+                caret += font.pair_kerning(scale, previous, glyph.id());
+            }
+            previous = Some(glyph.id());
+
+            let glyph = glyph.scaled(scale);
+            let advance = glyph.h_metrics().advance_width;
+            glyphs.push(glyph.positioned(point(x + caret, baseline)));
+            caret += advance;
+        }
+    }
+
+    glyphs
+}
+
+fn wrap(
+    font: &rusttype::Font<'static>,
+    scale: Scale,
+    paragraph: &str,
+    wrap_width: Option<f32>,
+) -> Vec<String> {
+    let wrap_width = match wrap_width {
+        Some(wrap_width) => wrap_width,
+        None => return vec![paragraph.to_owned()],
+    };
+
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in paragraph.split(' ') {
+        let candidate = if line.is_empty() {
+            word.to_owned()
+        } else {
+            format!("{} {}", line, word)
+        };
+
+        if !line.is_empty() && line_width(font, scale, &candidate) > wrap_width {
+            lines.push(line);
+            line = word.to_owned();
+        } else {
+            line = candidate;
+        }
+    }
+    lines.push(line);
+
+    lines
+}
+
+fn line_width(font: &rusttype::Font<'static>, scale: Scale, line: &str) -> f32 {
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in line.chars() {
+        let glyph = font.glyph(c);
+        if let Some(previous) = previous {
+            width += font.pair_kerning(scale, previous, glyph.id());
+        }
+        previous = Some(glyph.id());
+        width += glyph.scaled(scale).h_metrics().advance_width;
+    }
+
+    width
+}
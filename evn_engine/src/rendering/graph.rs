@@ -0,0 +1,1058 @@
+// Render graph
+//
+// Passes declare the images they draw into and the images they sample. From that the graph
+// derives the order of the passes, a render pass and framebuffers for each of them, the
+// load and store ops, the layout transitions and the barriers in front of passes sampling
+// earlier results. Transient images are sized relative to the output and recreated by
+// `resize`, the output itself (swapchain or offscreen images) belongs to the renderer.
+// Every frame starts over, the first use of an image in a frame has to clear or overwrite it.
+
+use super::{allocator::Allocator, debug::DebugNames, targets::AttachmentImage, RendererInitError};
+use ash::{version::DeviceV1_0, vk, Device};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PassId(usize);
+
+const OUTPUT: ImageId = ImageId(0);
+
+// a transient image, created and owned by the graph
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageDesc {
+    pub format: vk::Format,
+    pub samples: vk::SampleCountFlags,
+    // of the output extent, at least one pixel
+    pub scale: f32,
+}
+
+impl ImageDesc {
+    pub fn new(format: vk::Format) -> Self {
+        ImageDesc {
+            format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            scale: 1.0,
+        }
+    }
+
+    pub fn samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PassDesc {
+    name: String,
+    // without a clear color the previous contents are loaded
+    colors: Vec<(ImageId, Option<[f32; 4]>)>,
+    // none or one per color attachment
+    resolves: Vec<ImageId>,
+    depth: Option<(ImageId, Option<f32>)>,
+    // read in fragment shaders
+    sampled: Vec<ImageId>,
+}
+
+impl PassDesc {
+    pub fn new(name: impl AsRef<str>) -> Self {
+        PassDesc {
+            name: name.as_ref().to_owned(),
+            colors: Vec::new(),
+            resolves: Vec::new(),
+            depth: None,
+            sampled: Vec::new(),
+        }
+    }
+
+    pub fn color(mut self, image: ImageId, clear: Option<[f32; 4]>) -> Self {
+        self.colors.push((image, clear));
+        self
+    }
+
+    // the multisampled color attachment with the same index gets resolved into `image`
+    pub fn resolve(mut self, image: ImageId) -> Self {
+        self.resolves.push(image);
+        self
+    }
+
+    pub fn depth(mut self, image: ImageId, clear: Option<f32>) -> Self {
+        self.depth = Some((image, clear));
+        self
+    }
+
+    pub fn sample(mut self, image: ImageId) -> Self {
+        self.sampled.push(image);
+        self
+    }
+
+    // every image the pass renders into
+    fn writes(&self) -> impl Iterator<Item = ImageId> + '_ {
+        self.colors
+            .iter()
+            .map(|(image, _)| *image)
+            .chain(self.depth.map(|(image, _)| image))
+            .chain(self.resolves.iter().cloned())
+    }
+
+    // attachments whose previous contents are used
+    fn loads(&self) -> impl Iterator<Item = ImageId> + '_ {
+        self.colors
+            .iter()
+            .filter(|(_, clear)| clear.is_none())
+            .map(|(image, _)| *image)
+            .chain(
+                self.depth
+                    .filter(|(_, clear)| clear.is_none())
+                    .map(|(image, _)| image),
+            )
+    }
+}
+
+pub struct RenderGraphBuilder {
+    // the layout the output is left in after the last pass writing it
+    output_layout: vk::ImageLayout,
+    // the output is the first one
+    images: Vec<(String, ImageDesc)>,
+    passes: Vec<PassDesc>,
+}
+
+impl RenderGraphBuilder {
+    pub fn new(output_format: vk::Format, output_layout: vk::ImageLayout) -> Self {
+        RenderGraphBuilder {
+            output_layout,
+            images: vec![("output".to_owned(), ImageDesc::new(output_format))],
+            passes: Vec::new(),
+        }
+    }
+
+    // the swapchain or offscreen image of the frame, can't be sampled
+    pub fn output(&self) -> ImageId {
+        OUTPUT
+    }
+
+    pub fn image(&mut self, name: impl AsRef<str>, desc: ImageDesc) -> ImageId {
+        self.images.push((name.as_ref().to_owned(), desc));
+        ImageId(self.images.len() - 1)
+    }
+
+    // passes may be declared in any order, only passes writing the same image
+    // keep the order they were declared in
+    pub fn pass(&mut self, desc: PassDesc) -> PassId {
+        self.passes.push(desc);
+        PassId(self.passes.len() - 1)
+    }
+
+    // `output_views` are the views of the output images, one framebuffer is created for
+    // each of them per pass writing the output
+    pub unsafe fn build(
+        self,
+        device: &Device,
+        allocator: &mut Allocator,
+        extent: vk::Extent2D,
+        output_views: &[vk::ImageView],
+    ) -> Result<RenderGraph, RendererInitError> {
+        let (order, plans) = self.plan()?;
+
+        let mut usages = vec![vk::ImageUsageFlags::empty(); self.images.len()];
+        for pass in &self.passes {
+            for image in pass.writes() {
+                usages[image.0] |= if is_depth_format(self.images[image.0].1.format) {
+                    vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+                } else {
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT
+                };
+            }
+            for image in &pass.sampled {
+                usages[image.0] |= vk::ImageUsageFlags::SAMPLED;
+            }
+        }
+
+        let mut graph = RenderGraph {
+            images: self.images.clone(),
+            usages,
+            transients: Vec::new(),
+            passes: Vec::new(),
+            order: order.iter().map(|&index| PassId(index)).collect(),
+            extent,
+        };
+
+        for (index, plan) in plans.into_iter().enumerate() {
+            match plan.create(device, &self.passes[index], &self.images) {
+                Ok(compiled) => graph.passes.push(compiled),
+                Err(err) => {
+                    graph.destroy(device, allocator);
+                    return Err(err);
+                }
+            }
+        }
+
+        for (usage, stored) in graph.usages.iter_mut().zip(self.stored_images()) {
+            if !stored && !usage.contains(vk::ImageUsageFlags::SAMPLED) {
+                *usage |= vk::ImageUsageFlags::TRANSIENT_ATTACHMENT;
+            }
+        }
+
+        if let Err(err) = graph.create_targets(device, allocator, output_views) {
+            graph.destroy(device, allocator);
+            return Err(err);
+        }
+
+        Ok(graph)
+    }
+
+    // everything that doesn't need a device, the order of the passes and
+    // their plans in declaration order
+    fn plan(&self) -> Result<(Vec<usize>, Vec<PassPlan>), RendererInitError> {
+        let graph_error = |err| RendererInitError::RenderGraphError { err };
+
+        for pass in &self.passes {
+            self.validate(pass).map_err(graph_error)?;
+        }
+        let order = pass_order(&self.passes).map_err(graph_error)?;
+
+        // the state of every image while walking through the passes in order
+        let mut layouts = vec![vk::ImageLayout::UNDEFINED; self.images.len()];
+        let mut written = vec![false; self.images.len()];
+        let mut plans = (0..self.passes.len()).map(|_| None).collect::<Vec<_>>();
+        for (position, &index) in order.iter().enumerate() {
+            let later = order[position + 1..]
+                .iter()
+                .map(|&index| &self.passes[index])
+                .collect::<Vec<_>>();
+
+            plans[index] =
+                Some(self.compile_pass(&self.passes[index], &later, &mut layouts, &mut written)?);
+        }
+
+        Ok((order, plans.into_iter().flatten().collect()))
+    }
+
+    fn validate(&self, pass: &PassDesc) -> Result<(), String> {
+        let attachments = pass.writes().collect::<Vec<_>>();
+        if attachments.is_empty() {
+            return Err(format!("pass \"{}\" has no attachments", pass.name));
+        }
+
+        for (index, image) in attachments.iter().enumerate() {
+            if attachments[..index].contains(image) {
+                return Err(format!(
+                    "pass \"{}\" uses \"{}\" twice",
+                    pass.name, self.images[image.0].0
+                ));
+            }
+            if pass.sampled.contains(image) {
+                return Err(format!(
+                    "pass \"{}\" samples its own attachment \"{}\"",
+                    pass.name, self.images[image.0].0
+                ));
+            }
+        }
+
+        if pass.sampled.contains(&OUTPUT) {
+            return Err(format!("pass \"{}\" samples the output", pass.name));
+        }
+
+        if !pass.resolves.is_empty() && pass.resolves.len() != pass.colors.len() {
+            return Err(format!(
+                "pass \"{}\" needs one resolve target per color attachment",
+                pass.name
+            ));
+        }
+
+        // everything but the resolve targets has to have the same sample count
+        let samples = pass
+            .colors
+            .iter()
+            .map(|(image, _)| *image)
+            .chain(pass.depth.map(|(image, _)| image))
+            .map(|image| self.images[image.0].1.samples)
+            .collect::<Vec<_>>();
+        if samples.iter().any(|&count| count != samples[0])
+            || pass
+                .resolves
+                .iter()
+                .any(|image| self.images[image.0].1.samples != vk::SampleCountFlags::TYPE_1)
+        {
+            return Err(format!("pass \"{}\" mixes sample counts", pass.name));
+        }
+
+        let scale = self.images[attachments[0].0].1.scale;
+        if attachments
+            .iter()
+            .any(|image| (self.images[image.0].1.scale - scale).abs() > f32::EPSILON)
+        {
+            return Err(format!("pass \"{}\" mixes image sizes", pass.name));
+        }
+
+        Ok(())
+    }
+
+    // images whose contents outlive the pass writing them, the output always does
+    fn stored_images(&self) -> Vec<bool> {
+        let mut stored = vec![false; self.images.len()];
+        stored[OUTPUT.0] = true;
+        for pass in &self.passes {
+            for image in pass.loads().chain(pass.sampled.iter().cloned()) {
+                stored[image.0] = true;
+            }
+        }
+        stored
+    }
+
+    // the attachment descriptions of one pass, updates the image states
+    fn compile_pass(
+        &self,
+        desc: &PassDesc,
+        later: &[&PassDesc],
+        layouts: &mut [vk::ImageLayout],
+        written: &mut [bool],
+    ) -> Result<PassPlan, RendererInitError> {
+        let graph_error = |err| RendererInitError::RenderGraphError { err };
+
+        let mut barriers = Vec::new();
+        for &image in &desc.sampled {
+            if !written[image.0] {
+                return Err(graph_error(format!(
+                    "pass \"{}\" samples \"{}\" before it was written",
+                    desc.name, self.images[image.0].0
+                )));
+            }
+
+            let depth = is_depth_format(self.images[image.0].1.format);
+            let new_layout = if depth {
+                vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+            } else {
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            };
+            if layouts[image.0] != new_layout {
+                barriers.push(ImageBarrier {
+                    image,
+                    old_layout: layouts[image.0],
+                    new_layout,
+                    depth,
+                });
+                layouts[image.0] = new_layout;
+            }
+        }
+
+        // the previous contents are used by a later pass
+        let used_later = |image: ImageId| {
+            image == OUTPUT
+                || later.iter().any(|pass| {
+                    pass.sampled.contains(&image) || pass.loads().any(|load| load == image)
+                })
+        };
+        // later passes render into it again
+        let written_later = |image: ImageId| {
+            later
+                .iter()
+                .any(|pass| pass.writes().any(|write| write == image))
+        };
+
+        let mut attachments = Vec::new();
+        let mut attachment = |image: ImageId, clear: Option<vk::ClearValue>, load: bool| {
+            let depth = is_depth_format(self.images[image.0].1.format);
+            let attachment_layout = if depth {
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+            } else {
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+            };
+
+            let load_op = match (clear, load && written[image.0]) {
+                (Some(_), _) => vk::AttachmentLoadOp::CLEAR,
+                (None, true) => vk::AttachmentLoadOp::LOAD,
+                (None, false) => vk::AttachmentLoadOp::DONT_CARE,
+            };
+            let initial_layout = if load_op == vk::AttachmentLoadOp::LOAD {
+                layouts[image.0]
+            } else {
+                vk::ImageLayout::UNDEFINED
+            };
+            let final_layout = if image == OUTPUT && !written_later(image) {
+                self.output_layout
+            } else {
+                attachment_layout
+            };
+
+            let ImageDesc {
+                format, samples, ..
+            } = self.images[image.0].1;
+            let store_op = if used_later(image) {
+                vk::AttachmentStoreOp::STORE
+            } else {
+                vk::AttachmentStoreOp::DONT_CARE
+            };
+            attachments.push(AttachmentPlan {
+                image,
+                description: vk::AttachmentDescription::builder()
+                    .format(format)
+                    .samples(samples)
+                    .load_op(load_op)
+                    .store_op(store_op)
+                    .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                    .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                    .initial_layout(initial_layout)
+                    .final_layout(final_layout)
+                    .build(),
+                layout: attachment_layout,
+                clear: clear.unwrap_or(vk::ClearValue {
+                    color: vk::ClearColorValue { float32: [0.0; 4] },
+                }),
+            });
+
+            layouts[image.0] = final_layout;
+            written[image.0] = true;
+        };
+
+        // same order as the attachment references: colors, depth, resolves
+        for &(image, clear) in &desc.colors {
+            let clear = clear.map(|color| vk::ClearValue {
+                color: vk::ClearColorValue { float32: color },
+            });
+            attachment(image, clear, true);
+        }
+        if let Some((image, clear)) = desc.depth {
+            let clear = clear.map(|depth| vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue { depth, stencil: 0 },
+            });
+            attachment(image, clear, true);
+        }
+        for &image in &desc.resolves {
+            attachment(image, None, false);
+        }
+
+        Ok(PassPlan {
+            attachments,
+            barriers,
+            color_count: desc.colors.len(),
+            has_depth: desc.depth.is_some(),
+        })
+    }
+}
+
+// layout transition of a sampled image
+#[derive(Debug, Clone, Copy)]
+struct ImageBarrier {
+    image: ImageId,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    depth: bool,
+}
+
+struct AttachmentPlan {
+    image: ImageId,
+    description: vk::AttachmentDescription,
+    // while rendering
+    layout: vk::ImageLayout,
+    clear: vk::ClearValue,
+}
+
+// a compiled pass before its render pass is created
+struct PassPlan {
+    attachments: Vec<AttachmentPlan>,
+    barriers: Vec<ImageBarrier>,
+    color_count: usize,
+    has_depth: bool,
+}
+
+impl PassPlan {
+    unsafe fn create(
+        self,
+        device: &Device,
+        desc: &PassDesc,
+        images: &[(String, ImageDesc)],
+    ) -> Result<CompiledPass, RendererInitError> {
+        let references = self
+            .attachments
+            .iter()
+            .enumerate()
+            .map(|(index, attachment)| vk::AttachmentReference {
+                attachment: index as u32,
+                layout: attachment.layout,
+            })
+            .collect::<Vec<_>>();
+        let color_refs = &references[..self.color_count];
+        let depth_ref = if self.has_depth {
+            Some(references[self.color_count])
+        } else {
+            None
+        };
+        let resolve_start = self.color_count + self.has_depth as usize;
+        let resolve_refs = &references[resolve_start..];
+
+        let mut subpass = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(color_refs);
+        // the builder overwrites the color attachment count, so only set it when needed
+        if !resolve_refs.is_empty() {
+            subpass = subpass.resolve_attachments(resolve_refs);
+        }
+        if let Some(depth_ref) = &depth_ref {
+            subpass = subpass.depth_stencil_attachment(depth_ref);
+        }
+
+        // earlier passes and frames may still write the attachments or sample them
+        let dependencies = [vk::SubpassDependency {
+            src_subpass: vk::SUBPASS_EXTERNAL,
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::FRAGMENT_SHADER,
+            src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
+                | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            ..Default::default()
+        }];
+
+        let descriptions = self
+            .attachments
+            .iter()
+            .map(|attachment| attachment.description)
+            .collect::<Vec<_>>();
+        let subpasses = [subpass.build()];
+        let render_pass_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&descriptions)
+            .dependencies(&dependencies)
+            .subpasses(&subpasses);
+
+        let render_pass = device
+            .create_render_pass(&render_pass_info, None)
+            .map_err(|err| RendererInitError::PipelineCreationError { err })?;
+
+        let attachments = self
+            .attachments
+            .iter()
+            .map(|attachment| attachment.image)
+            .collect::<Vec<_>>();
+
+        Ok(CompiledPass {
+            name: desc.name.clone(),
+            render_pass,
+            scale: images[attachments[0].0].1.scale,
+            clear_values: self
+                .attachments
+                .iter()
+                .map(|attachment| attachment.clear)
+                .collect(),
+            attachments,
+            barriers: self.barriers,
+            framebuffers: Vec::new(),
+        })
+    }
+}
+
+struct CompiledPass {
+    name: String,
+    render_pass: vk::RenderPass,
+    // in the order of the render pass attachments
+    attachments: Vec<ImageId>,
+    clear_values: Vec<vk::ClearValue>,
+    barriers: Vec<ImageBarrier>,
+    scale: f32,
+    // one per output image if the pass renders into the output
+    framebuffers: Vec<vk::Framebuffer>,
+}
+
+pub struct RenderGraph {
+    images: Vec<(String, ImageDesc)>,
+    usages: Vec<vk::ImageUsageFlags>,
+    // None for the output and images no pass uses
+    transients: Vec<Option<AttachmentImage>>,
+    // indexed by `PassId`
+    passes: Vec<CompiledPass>,
+    order: Vec<PassId>,
+    extent: vk::Extent2D,
+}
+
+impl RenderGraph {
+    // the order the passes have to be recorded in
+    pub fn order(&self) -> &[PassId] {
+        &self.order
+    }
+
+    pub fn pass_name(&self, pass: PassId) -> &str {
+        &self.passes[pass.0].name
+    }
+
+    // for the pipelines drawing in the pass, stays the same on resize
+    pub fn render_pass(&self, pass: PassId) -> vk::RenderPass {
+        self.passes[pass.0].render_pass
+    }
+
+    pub fn pass_extent(&self, pass: PassId) -> vk::Extent2D {
+        scaled_extent(self.extent, self.passes[pass.0].scale)
+    }
+
+    // changes on resize, descriptor sets sampling it have to be written again
+    pub fn view(&self, image: ImageId) -> vk::ImageView {
+        self.transients[image.0]
+            .as_ref()
+            .expect("Called RenderGraph::view on the output or an unused image")
+            .view
+    }
+
+    // transitions the sampled images and begins the render pass of `pass`,
+    // `output_index` selects the output image
+    pub unsafe fn begin(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        pass: PassId,
+        output_index: usize,
+    ) {
+        let pass_extent = self.pass_extent(pass);
+        let pass = &self.passes[pass.0];
+
+        if !pass.barriers.is_empty() {
+            let mut src_stage = vk::PipelineStageFlags::empty();
+            let barriers = pass
+                .barriers
+                .iter()
+                .map(|barrier| {
+                    let (stage, access, aspect) = match barrier.old_layout {
+                        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL => (
+                            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                            vk::ImageAspectFlags::DEPTH,
+                        ),
+                        _ if barrier.depth => (
+                            vk::PipelineStageFlags::TOP_OF_PIPE,
+                            vk::AccessFlags::empty(),
+                            vk::ImageAspectFlags::DEPTH,
+                        ),
+                        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => (
+                            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                            vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                            vk::ImageAspectFlags::COLOR,
+                        ),
+                        _ => (
+                            vk::PipelineStageFlags::TOP_OF_PIPE,
+                            vk::AccessFlags::empty(),
+                            vk::ImageAspectFlags::COLOR,
+                        ),
+                    };
+                    src_stage |= stage;
+
+                    vk::ImageMemoryBarrier::builder()
+                        .src_access_mask(access)
+                        .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                        .old_layout(barrier.old_layout)
+                        .new_layout(barrier.new_layout)
+                        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                        .image(self.transients[barrier.image.0].as_ref().unwrap().image)
+                        .subresource_range(vk::ImageSubresourceRange {
+                            aspect_mask: aspect,
+                            base_mip_level: 0,
+                            level_count: 1,
+                            base_array_layer: 0,
+                            layer_count: 1,
+                        })
+                        .build()
+                })
+                .collect::<Vec<_>>();
+
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &barriers,
+            );
+        }
+
+        let framebuffer = pass.framebuffers[output_index % pass.framebuffers.len()];
+        let render_pass_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(pass.render_pass)
+            .framebuffer(framebuffer)
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: pass_extent,
+            })
+            .clear_values(&pass.clear_values);
+        device.cmd_begin_render_pass(
+            command_buffer,
+            &render_pass_info,
+            vk::SubpassContents::INLINE,
+        );
+    }
+
+    pub unsafe fn end(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        device.cmd_end_render_pass(command_buffer);
+    }
+
+    // recreates the transient images and the framebuffers, the device has to be idle
+    pub unsafe fn resize(
+        &mut self,
+        device: &Device,
+        allocator: &mut Allocator,
+        extent: vk::Extent2D,
+        output_views: &[vk::ImageView],
+    ) -> Result<(), RendererInitError> {
+        self.destroy_targets(device, allocator);
+        self.extent = extent;
+        self.create_targets(device, allocator, output_views)
+    }
+
+    unsafe fn create_targets(
+        &mut self,
+        device: &Device,
+        allocator: &mut Allocator,
+        output_views: &[vk::ImageView],
+    ) -> Result<(), RendererInitError> {
+        self.transients.push(None);
+        for index in 1..self.images.len() {
+            let desc = self.images[index].1;
+            let usage = self.usages[index];
+            if usage.is_empty() {
+                self.transients.push(None);
+                continue;
+            }
+
+            let aspect_mask = if is_depth_format(desc.format) {
+                vk::ImageAspectFlags::DEPTH
+            } else {
+                vk::ImageAspectFlags::COLOR
+            };
+            let image = AttachmentImage::new(
+                device,
+                allocator,
+                scaled_extent(self.extent, desc.scale),
+                desc.format,
+                desc.samples,
+                usage,
+                aspect_mask,
+            )?;
+            self.transients.push(Some(image));
+        }
+
+        let transients = &self.transients;
+        for pass in &mut self.passes {
+            let extent = scaled_extent(self.extent, pass.scale);
+            let output_views = if pass.attachments.contains(&OUTPUT) {
+                output_views
+            } else {
+                // one framebuffer, the output view is never used
+                &[vk::ImageView::null()]
+            };
+
+            for &output_view in output_views {
+                let attachments = pass
+                    .attachments
+                    .iter()
+                    .map(|image| match &transients[image.0] {
+                        Some(transient) => transient.view,
+                        None => output_view,
+                    })
+                    .collect::<Vec<_>>();
+
+                let framebuffer_create_info = vk::FramebufferCreateInfo::builder()
+                    .render_pass(pass.render_pass)
+                    .attachments(&attachments)
+                    .width(extent.width)
+                    .height(extent.height)
+                    .layers(1);
+
+                let framebuffer = device
+                    .create_framebuffer(&framebuffer_create_info, None)
+                    .map_err(|err| RendererInitError::FramebufferCreationError { err })?;
+                pass.framebuffers.push(framebuffer);
+            }
+        }
+
+        Ok(())
+    }
+
+    unsafe fn destroy_targets(&mut self, device: &Device, allocator: &mut Allocator) {
+        for pass in &mut self.passes {
+            for framebuffer in pass.framebuffers.drain(..) {
+                device.destroy_framebuffer(framebuffer, None);
+            }
+        }
+
+        for transient in self.transients.drain(..).flatten() {
+            transient.destroy(device, allocator);
+        }
+    }
+
+    // everything recreated on resize is named again by calling this after `resize`
+    pub unsafe fn name(&self, debug_names: &DebugNames) {
+        for pass in &self.passes {
+            debug_names.name(pass.render_pass, &format!("{} render pass", pass.name));
+            debug_names.name_all(&pass.framebuffers, &format!("{} framebuffer", pass.name));
+        }
+
+        for ((name, _), transient) in self.images.iter().zip(&self.transients) {
+            if let Some(transient) = transient {
+                transient.name(debug_names, name);
+            }
+        }
+    }
+
+    pub unsafe fn destroy(&mut self, device: &Device, allocator: &mut Allocator) {
+        self.destroy_targets(device, allocator);
+
+        for pass in &self.passes {
+            device.destroy_render_pass(pass.render_pass, None);
+        }
+    }
+}
+
+// Kahn's algorithm, ties are broken by the declaration order. Passes writing the same image
+// keep their declaration order, passes sampling an image come after all of its writers.
+fn pass_order(passes: &[PassDesc]) -> Result<Vec<usize>, String> {
+    let dependencies = passes
+        .iter()
+        .enumerate()
+        .map(|(index, pass)| {
+            passes
+                .iter()
+                .enumerate()
+                .filter(|&(other, other_pass)| {
+                    other != index
+                        && other_pass.writes().any(|image| {
+                            pass.sampled.contains(&image)
+                                || (other < index && pass.writes().any(|write| write == image))
+                        })
+                })
+                .map(|(other, _)| other)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut order = Vec::with_capacity(passes.len());
+    while order.len() < passes.len() {
+        let next = (0..passes.len()).find(|index| {
+            !order.contains(index)
+                && dependencies[*index]
+                    .iter()
+                    .all(|dependency| order.contains(dependency))
+        });
+
+        match next {
+            Some(next) => order.push(next),
+            None => {
+                let remaining = (0..passes.len())
+                    .filter(|index| !order.contains(index))
+                    .map(|index| format!("\"{}\"", passes[index].name))
+                    .collect::<Vec<_>>();
+                return Err(format!(
+                    "cyclic dependencies between the passes {}",
+                    remaining.join(", ")
+                ));
+            }
+        }
+    }
+
+    Ok(order)
+}
+
+fn scaled_extent(extent: vk::Extent2D, scale: f32) -> vk::Extent2D {
+    vk::Extent2D {
+        width: ((extent.width as f32 * scale).round() as u32).max(1),
+        height: ((extent.height as f32 * scale).round() as u32).max(1),
+    }
+}
+
+fn is_depth_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::D16_UNORM
+            | vk::Format::X8_D24_UNORM_PACK32
+            | vk::Format::D32_SFLOAT
+            | vk::Format::D16_UNORM_S8_UINT
+            | vk::Format::D24_UNORM_S8_UINT
+            | vk::Format::D32_SFLOAT_S8_UINT
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HDR: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+    const DEPTH: vk::Format = vk::Format::D32_SFLOAT;
+
+    // scene -> post -> overlay, declared out of order
+    fn frame_graph() -> RenderGraphBuilder {
+        let mut builder =
+            RenderGraphBuilder::new(vk::Format::B8G8R8A8_UNORM, vk::ImageLayout::PRESENT_SRC_KHR);
+        let output = builder.output();
+        let scene = builder.image("scene", ImageDesc::new(HDR));
+        let depth = builder.image("depth", ImageDesc::new(DEPTH));
+
+        builder.pass(PassDesc::new("post").color(output, None).sample(scene));
+        builder.pass(PassDesc::new("overlay").color(output, None));
+        builder.pass(
+            PassDesc::new("main")
+                .color(scene, Some([0.0; 4]))
+                .depth(depth, Some(1.0)),
+        );
+        builder
+    }
+
+    fn plan_error(builder: &RenderGraphBuilder) -> String {
+        match builder.plan() {
+            Err(RendererInitError::RenderGraphError { err }) => err,
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("the graph is valid"),
+        }
+    }
+
+    #[test]
+    fn topological_order() {
+        let builder = frame_graph();
+        let (order, _) = builder.plan().unwrap();
+
+        // the sampled scene comes first, post and overlay keep their declaration order
+        let names = order
+            .iter()
+            .map(|&index| builder.passes[index].name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["main", "post", "overlay"]);
+    }
+
+    #[test]
+    fn cycle() {
+        let mut builder = RenderGraphBuilder::new(HDR, vk::ImageLayout::GENERAL);
+        let a = builder.image("a", ImageDesc::new(HDR));
+        let b = builder.image("b", ImageDesc::new(HDR));
+        builder.pass(PassDesc::new("first").color(a, None).sample(b));
+        builder.pass(PassDesc::new("second").color(b, None).sample(a));
+
+        assert_eq!(
+            plan_error(&builder),
+            "cyclic dependencies between the passes \"first\", \"second\""
+        );
+    }
+
+    #[test]
+    fn unknown_input() {
+        let mut builder = RenderGraphBuilder::new(HDR, vk::ImageLayout::GENERAL);
+        let output = builder.output();
+        let unwritten = builder.image("unwritten", ImageDesc::new(HDR));
+        builder.pass(PassDesc::new("post").color(output, None).sample(unwritten));
+
+        assert_eq!(
+            plan_error(&builder),
+            "pass \"post\" samples \"unwritten\" before it was written"
+        );
+    }
+
+    #[test]
+    fn load_and_store_ops() {
+        let builder = frame_graph();
+        let (_, plans) = builder.plan().unwrap();
+        let ops = |pass: usize| {
+            plans[pass]
+                .attachments
+                .iter()
+                .map(|attachment| {
+                    (
+                        attachment.description.load_op,
+                        attachment.description.store_op,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // cleared scene is sampled later, depth isn't needed after the pass
+        assert_eq!(
+            ops(2),
+            [
+                (vk::AttachmentLoadOp::CLEAR, vk::AttachmentStoreOp::STORE),
+                (
+                    vk::AttachmentLoadOp::CLEAR,
+                    vk::AttachmentStoreOp::DONT_CARE
+                ),
+            ]
+        );
+        // the first use of the output overwrites it, the last one keeps it
+        assert_eq!(
+            ops(0),
+            [(
+                vk::AttachmentLoadOp::DONT_CARE,
+                vk::AttachmentStoreOp::STORE
+            )]
+        );
+        assert_eq!(
+            ops(1),
+            [(vk::AttachmentLoadOp::LOAD, vk::AttachmentStoreOp::STORE)]
+        );
+    }
+
+    #[test]
+    fn final_layouts() {
+        let builder = frame_graph();
+        let (_, plans) = builder.plan().unwrap();
+        let layouts = |pass: usize| {
+            plans[pass]
+                .attachments
+                .iter()
+                .map(|attachment| {
+                    (
+                        attachment.description.initial_layout,
+                        attachment.description.final_layout,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            layouts(2),
+            [
+                (
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+                ),
+                (
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+                ),
+            ]
+        );
+
+        // the scene is transitioned for sampling in front of the post pass
+        let barriers = &plans[0].barriers;
+        assert_eq!(barriers.len(), 1);
+        assert_eq!(
+            barriers[0].old_layout,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        );
+        assert_eq!(
+            barriers[0].new_layout,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        );
+
+        // only the last pass writing the output leaves it in the output layout
+        assert_eq!(
+            layouts(0),
+            [(
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+            )]
+        );
+        assert_eq!(
+            layouts(1),
+            [(
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                vk::ImageLayout::PRESENT_SRC_KHR
+            )]
+        );
+    }
+}
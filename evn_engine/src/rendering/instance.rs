@@ -0,0 +1,169 @@
+// Instance layers and extensions
+//
+// Everything is checked against what the loader offers before the instance is created.
+// Surface extensions are required for a window, validation is optional and only warned about.
+// Vulkan 1.1 is requested when the loader supports it, for the device UUID.
+
+use super::{platform, RendererInitError};
+use ash::{
+    extensions::{ext::DebugUtils, khr::Surface},
+    version::EntryV1_0,
+    vk, vk_make_version,
+};
+use log::{info, warn};
+use std::{
+    ffi::{CStr, CString},
+    mem,
+};
+
+// in order of preference, only the first available one is enabled
+const VALIDATION_LAYERS: [&str; 2] = [
+    "VK_LAYER_KHRONOS_validation",
+    "VK_LAYER_LUNARG_standard_validation",
+];
+
+pub struct InstanceConfig {
+    pub layers: Vec<CString>,
+    pub extensions: Vec<CString>,
+    // VK_EXT_debug_utils is enabled, validation messages can be logged
+    pub debug_utils: bool,
+    pub api_version: u32,
+}
+
+impl InstanceConfig {
+    pub unsafe fn new<E: EntryV1_0>(
+        entry: &E,
+        surface: bool,
+        validation: bool,
+    ) -> Result<Self, RendererInitError> {
+        let instance_error = |err: ash::vk::Result| RendererInitError::InstanceError {
+            err: err.to_string(),
+        };
+
+        let available_extensions = entry
+            .enumerate_instance_extension_properties()
+            .map_err(instance_error)?
+            .iter()
+            .map(|properties| CStr::from_ptr(properties.extension_name.as_ptr()).to_owned())
+            .collect::<Vec<_>>();
+        let available_layers = entry
+            .enumerate_instance_layer_properties()
+            .map_err(instance_error)?
+            .iter()
+            .map(|properties| CStr::from_ptr(properties.layer_name.as_ptr()).to_owned())
+            .collect::<Vec<_>>();
+
+        let is_available = |name: &CStr| available_extensions.iter().any(|ext| **ext == *name);
+
+        let mut extensions = Vec::new();
+        let mut missing = Vec::new();
+        if surface {
+            if is_available(Surface::name()) {
+                extensions.push(Surface::name().to_owned());
+            } else {
+                missing.push(Surface::name().to_string_lossy().into_owned());
+            }
+
+            // one of them is enough
+            let surface_extensions = platform::surface_extension_names();
+            let available_surface_extensions = surface_extensions
+                .iter()
+                .filter(|name| is_available(name))
+                .map(|name| (*name).to_owned())
+                .collect::<Vec<_>>();
+
+            if available_surface_extensions.is_empty() {
+                missing.push(
+                    surface_extensions
+                        .iter()
+                        .map(|name| name.to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(" or "),
+                );
+            }
+            extensions.extend(available_surface_extensions);
+        }
+
+        if !missing.is_empty() {
+            return Err(RendererInitError::MissingInstanceExtensions {
+                err: missing.join(", "),
+            });
+        }
+
+        let mut layers = Vec::new();
+        let mut debug_utils = false;
+        if validation {
+            let layer = VALIDATION_LAYERS
+                .iter()
+                .map(|layer| CString::new(*layer).unwrap())
+                .find(|layer| available_layers.contains(layer));
+
+            match layer {
+                Some(layer) => {
+                    info!("Using validation layer {}", layer.to_string_lossy());
+                    layers.push(layer);
+                }
+                None => warn!(
+                    "No validation layer available, tried {}",
+                    VALIDATION_LAYERS.join(", ")
+                ),
+            }
+
+            if is_available(DebugUtils::name()) {
+                extensions.push(DebugUtils::name().to_owned());
+                debug_utils = true;
+            } else {
+                warn!(
+                    "{} not available, validation messages won't be logged",
+                    DebugUtils::name().to_string_lossy()
+                );
+            }
+        }
+
+        Ok(InstanceConfig {
+            layers,
+            extensions,
+            debug_utils,
+            api_version: api_version(entry),
+        })
+    }
+
+    // the pointers are valid as long as self is
+    pub fn layer_names(&self) -> Vec<*const i8> {
+        self.layers.iter().map(|layer| layer.as_ptr()).collect()
+    }
+
+    pub fn extension_names(&self) -> Vec<*const i8> {
+        self.extensions
+            .iter()
+            .map(|extension| extension.as_ptr())
+            .collect()
+    }
+}
+
+// 1.0 loaders don't export vkEnumerateInstanceVersion at all
+unsafe fn api_version<E: EntryV1_0>(entry: &E) -> u32 {
+    let name = CString::new("vkEnumerateInstanceVersion").unwrap();
+    let function = entry
+        .static_fn()
+        .get_instance_proc_addr(vk::Instance::null(), name.as_ptr());
+
+    let loader_version = match function {
+        Some(function) => {
+            let enumerate_instance_version: extern "system" fn(*mut u32) -> vk::Result =
+                mem::transmute(function);
+            let mut version = 0;
+            match enumerate_instance_version(&mut version) {
+                vk::Result::SUCCESS => version,
+                _ => vk_make_version!(1, 0, 0),
+            }
+        }
+        None => vk_make_version!(1, 0, 0),
+    };
+
+    if loader_version >= vk_make_version!(1, 1, 0) {
+        vk_make_version!(1, 1, 0)
+    } else {
+        vk_make_version!(1, 0, 36)
+    }
+}
@@ -0,0 +1,279 @@
+// Debug line pass
+//
+// Draws the lines of `DebugDraw` as a line list with the camera of the main pass, on top
+// of the world and below screen space text. The vertices are rewritten every frame into
+// the frame's region of a persistently mapped buffer.
+
+use super::{
+    allocator::{Allocator, LinearAllocator},
+    debug::DebugNames,
+    pipeline::{self, BlendMode, PipelineTarget},
+    RendererInitError,
+};
+use crate::debug_draw::DebugLine;
+use ash::{version::DeviceV1_0, vk, Device};
+use log::warn;
+use std::{ffi::CString, mem};
+
+// per frame in flight, lines after that are dropped
+const MAX_LINES: usize = 65536;
+
+const VERTEX_SHADER: &[u8] = include_bytes!("../../../resources/closed/shaders/line.vert.spv");
+const FRAGMENT_SHADER: &[u8] = include_bytes!("../../../resources/closed/shaders/line.frag.spv");
+
+// locations 0 and 1
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+// the vertices of one frame, ready to be recorded
+#[derive(Debug, Default)]
+pub struct LineBatch {
+    buffer: vk::Buffer,
+    offset: vk::DeviceSize,
+    vertex_count: u32,
+}
+
+pub struct LinePass {
+    // the layout of the main pipelines, not owned
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    vertices: LinearAllocator,
+    // the last frame had too many lines, so the warning isn't logged every frame
+    overflowed: bool,
+}
+
+impl LinePass {
+    pub unsafe fn new(
+        device: &Device,
+        allocator: &mut Allocator,
+        target: &PipelineTarget,
+        pipeline_cache: vk::PipelineCache,
+        frames_in_flight: usize,
+    ) -> Result<Self, RendererInitError> {
+        let pipeline = create_pipeline(device, target, pipeline_cache)?;
+
+        let vertices = LinearAllocator::new(
+            allocator,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            (MAX_LINES * 2 * mem::size_of::<LineVertex>()) as vk::DeviceSize,
+            frames_in_flight,
+        )
+        .map_err(|err| RendererInitError::AllocationError { err })?;
+
+        Ok(LinePass {
+            pipeline_layout: target.layout,
+            pipeline,
+            vertices,
+            overflowed: false,
+        })
+    }
+
+    // writes the vertices of `frame`, whose fence has to be waited on
+    pub fn prepare(&mut self, frame: usize, lines: &[DebugLine]) -> LineBatch {
+        if lines.is_empty() {
+            return LineBatch::default();
+        }
+
+        let overflowed = lines.len() > MAX_LINES;
+        if overflowed && !self.overflowed {
+            warn!(
+                "{} debug lines in one frame, only drawing {}",
+                lines.len(),
+                MAX_LINES
+            );
+        }
+        self.overflowed = overflowed;
+
+        let vertices = lines[..lines.len().min(MAX_LINES)]
+            .iter()
+            .flat_map(|line| {
+                let color = [line.color.x, line.color.y, line.color.z, line.color.w];
+                vec![
+                    LineVertex {
+                        position: [line.start.x, line.start.y, line.start.z],
+                        color,
+                    },
+                    LineVertex {
+                        position: [line.end.x, line.end.y, line.end.z],
+                        color,
+                    },
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        self.vertices.reset(frame);
+        let allocation = match self.vertices.allocate(
+            mem::size_of_val(vertices.as_slice()) as vk::DeviceSize,
+            mem::align_of::<LineVertex>() as vk::DeviceSize,
+        ) {
+            Some(allocation) => allocation,
+            None => return LineBatch::default(),
+        };
+        allocation.write(&vertices);
+
+        LineBatch {
+            buffer: allocation.buffer,
+            offset: allocation.offset,
+            vertex_count: vertices.len() as u32,
+        }
+    }
+
+    // has to be recorded inside of the main render pass, with the camera viewport set
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        camera_set: vk::DescriptorSet,
+        batch: &LineBatch,
+    ) {
+        if batch.vertex_count == 0 {
+            return;
+        }
+
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        // the sprite pipelines may have disturbed set 0
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline_layout,
+            0,
+            &[camera_set],
+            &[],
+        );
+        device.cmd_bind_vertex_buffers(command_buffer, 0, &[batch.buffer], &[batch.offset]);
+        device.cmd_draw(command_buffer, batch.vertex_count, 1, 0, 0);
+    }
+
+    pub unsafe fn name(&self, debug_names: &DebugNames) {
+        debug_names.name(self.pipeline, "debug line pipeline");
+        debug_names.name(self.vertices.buffer(), "debug line vertices");
+    }
+
+    pub unsafe fn destroy(&mut self, device: &Device, allocator: &mut Allocator) {
+        self.vertices.destroy(allocator);
+        device.destroy_pipeline(self.pipeline, None);
+    }
+}
+
+unsafe fn create_pipeline(
+    device: &Device,
+    target: &PipelineTarget,
+    pipeline_cache: vk::PipelineCache,
+) -> Result<vk::Pipeline, RendererInitError> {
+    let vertex_shader_module =
+        pipeline::create_shader_module(device, "debug line vertex", VERTEX_SHADER)?;
+    let fragment_shader_module =
+        match pipeline::create_shader_module(device, "debug line fragment", FRAGMENT_SHADER) {
+            Ok(module) => module,
+            Err(err) => {
+                device.destroy_shader_module(vertex_shader_module, None);
+                return Err(err);
+            }
+        };
+
+    let entry_name = CString::new("main").unwrap();
+    let stages = [
+        vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(vertex_shader_module)
+            .name(&entry_name)
+            .build(),
+        vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(fragment_shader_module)
+            .name(&entry_name)
+            .build(),
+    ];
+
+    let vertex_bindings = [vk::VertexInputBindingDescription {
+        binding: 0,
+        stride: mem::size_of::<LineVertex>() as u32,
+        input_rate: vk::VertexInputRate::VERTEX,
+    }];
+    let vertex_attributes = [
+        vk::VertexInputAttributeDescription {
+            location: 0,
+            binding: 0,
+            format: vk::Format::R32G32B32_SFLOAT,
+            offset: 0,
+        },
+        vk::VertexInputAttributeDescription {
+            location: 1,
+            binding: 0,
+            format: vk::Format::R32G32B32A32_SFLOAT,
+            offset: mem::size_of::<[f32; 3]>() as u32,
+        },
+    ];
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder()
+        .vertex_binding_descriptions(&vertex_bindings)
+        .vertex_attribute_descriptions(&vertex_attributes);
+
+    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::LINE_LIST)
+        .primitive_restart_enable(false);
+
+    let rasterizer = vk::PipelineRasterizationStateCreateInfo::builder()
+        .depth_clamp_enable(false)
+        .rasterizer_discard_enable(false)
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.0)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::CLOCKWISE)
+        .depth_bias_enable(false);
+
+    // visible through everything
+    let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(false)
+        .depth_write_enable(false)
+        .depth_bounds_test_enable(false)
+        .stencil_test_enable(false);
+
+    let blend_attachments = [BlendMode::Alpha.attachment_state()];
+    let color_blending = vk::PipelineColorBlendStateCreateInfo::builder()
+        .logic_op_enable(false)
+        .attachments(&blend_attachments);
+
+    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissor_count(1);
+
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+
+    let multisampling = vk::PipelineMultisampleStateCreateInfo::builder()
+        .sample_shading_enable(false)
+        .rasterization_samples(target.samples);
+
+    let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&stages)
+        .vertex_input_state(&vertex_input_info)
+        .input_assembly_state(&input_assembly)
+        .viewport_state(&viewport_state)
+        .rasterization_state(&rasterizer)
+        .multisample_state(&multisampling)
+        .depth_stencil_state(&depth_stencil)
+        .color_blend_state(&color_blending)
+        .dynamic_state(&dynamic_state)
+        .layout(target.layout)
+        .render_pass(target.render_pass)
+        .subpass(0)
+        .build();
+
+    let pipelines = device.create_graphics_pipelines(pipeline_cache, &[pipeline_create_info], None);
+
+    device.destroy_shader_module(vertex_shader_module, None);
+    device.destroy_shader_module(fragment_shader_module, None);
+
+    pipelines
+        .map(|pipelines| pipelines[0])
+        .map_err(|err| RendererInitError::PipelineCreationError { err: err.1 })
+}
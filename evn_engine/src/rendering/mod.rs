@@ -1,7 +1,70 @@
+pub mod allocator;
+mod backend;
+mod debug;
+mod device;
+mod font;
+mod graph;
+mod instance;
+mod lines;
+mod pipeline;
+mod pipeline_cache;
 mod platform;
+mod post;
+mod readback;
+mod screenshot;
+mod settings;
+mod sprites;
+mod swapchain;
+mod targets;
+mod text;
+mod texture;
+mod timestamps;
+mod ui;
+mod uniforms;
+mod validation;
+
+pub use self::{
+    backend::{
+        Draw, NullRenderer, RecordedFrame, RenderBackend, RenderFrame, RenderSystem, SpriteDraw,
+        TextDraw,
+    },
+    device::list_gpus,
+    font::Font,
+    pipeline::{BlendMode, CullMode, PipelineDesc, PolygonMode, Topology},
+    readback::Frame,
+    screenshot::Screenshot,
+    settings::{
+        GraphicsSettings, OnValidationError, PostPassSettings, PresentMode, ValidationSettings,
+    },
+    texture::Texture,
+    uniforms::CameraMatrices,
+    validation::{Severity, ValidationCounts, ValidationMessage, ValidationObject},
+};
 
+use self::{
+    allocator::{Allocator, AllocatorError},
+    debug::DebugNames,
+    device::DeviceInfo,
+    graph::{ImageDesc, PassDesc, PassId, RenderGraph, RenderGraphBuilder},
+    instance::InstanceConfig,
+    lines::{LineBatch, LinePass},
+    pipeline::PipelineTarget,
+    pipeline_cache::PipelineCache,
+    post::PostChain,
+    readback::Readback,
+    sprites::{SpriteBatches, SpritePass},
+    swapchain::WindowOutput,
+    targets::AttachmentImage,
+    text::{TextBatches, TextPass},
+    timestamps::Timestamps,
+    ui::{UiBatches, UiPass},
+    uniforms::{FrameUniforms, PushConstants},
+    validation::{vulkan_debug_callback, Validation},
+};
 use crate::{
+    components::Viewport,
     logger::UnwrapOrLog,
+    profiler::PassTiming,
     resources::{Resource, ResourceState, ResourcesData},
 };
 use ash::{
@@ -11,27 +74,27 @@ use ash::{
     },
     prelude::VkResult,
     version::{DeviceV1_0, EntryV1_0, InstanceV1_0},
-    vk, vk_make_version, Device, Entry, Instance, InstanceError, LoadingError,
+    vk, Device, Entry, Instance, InstanceError, LoadingError,
 };
 use either::Either;
 use err_derive::Error;
-use fnv::FnvBuildHasher;
+use fnv::{FnvBuildHasher, FnvHashMap};
 use log::{error, info, warn};
-use specs::System;
+use nalgebra_glm as glm;
 use std::{
     collections::HashMap,
     ffi::{CStr, CString},
-    os::raw::c_void,
+    path::PathBuf,
     sync::{Arc, RwLock},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
-use winit::Window;
+use winit::{dpi::LogicalSize, Window};
 
-const VALIDATION_LAYERS: [&str; 1] = ["VK_LAYER_LUNARG_standard_validation"];
-const INSTANCE_EXTENSIONS: [&str; 1] = ["VK_EXT_debug_utils"];
 const DEVICE_EXTENSIONS: [&str; 1] = ["VK_KHR_swapchain"];
-const MAX_FRAMES_IN_FLIGHT: usize = 2;
+
+// format of the offscreen images of a headless renderer
+const HEADLESS_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
 
 #[derive(Debug)]
 pub struct Shader {
@@ -39,14 +102,28 @@ pub struct Shader {
     pub frag: Vec<u32>,
 }
 
+impl Shader {
+    // a pass of the post chain, drawn with the built in full screen vertex shader
+    pub fn post(frag: Vec<u32>) -> Self {
+        Shader {
+            vert: post::vertex_shader(),
+            frag,
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum RendererInitError {
     #[error(display = "Failed to load Vulkan: {}", err)]
     LoadingError { err: String },
     #[error(display = "Failed to create Instance: {}", err)]
     InstanceError { err: String },
+    #[error(display = "Missing instance extensions: {}", err)]
+    MissingInstanceExtensions { err: String },
     #[error(display = "Failed to create Debug Callback: {}", err)]
     DebugCallbackError { err: vk::Result },
+    #[error(display = "Validation error: {}", err)]
+    ValidationError { err: String },
     #[error(display = "Failed to create Surface: {}", err)]
     SurfaceCreationError { err: vk::Result },
     #[error(display = "Physical Device Error: {}", err)]
@@ -60,70 +137,150 @@ pub enum RendererInitError {
         name: String,
         err: Either<vk::Result, String>,
     },
+    #[error(display = "Failed to load Texture \"{}\"", name)]
+    TextureLoadingError { name: String },
+    #[error(display = "Failed to load Font \"{}\"", name)]
+    FontLoadingError { name: String },
+    #[error(display = "Failed to upload Textures: {}", err)]
+    TextureUploadError { err: vk::Result },
     #[error(display = "Failed to create Pipeline: {}", err)]
     PipelineCreationError { err: vk::Result },
+    #[error(display = "Failed to create Pipeline cache: {}", err)]
+    PipelineCacheError { err: vk::Result },
     #[error(display = "Failed to create Framebuffer: {}", err)]
     FramebufferCreationError { err: vk::Result },
     #[error(display = "Failed to create Command buffer: {}", err)]
     CommandBufferError { err: vk::Result },
     #[error(display = "Failed to create Sync: {}", err)]
     SyncCreationError { err: vk::Result },
+    #[error(display = "Failed to create Descriptors: {}", err)]
+    DescriptorCreationError { err: vk::Result },
+    #[error(display = "Failed to allocate memory: {}", err)]
+    AllocationError { err: AllocatorError },
+    #[error(display = "Failed to create Query pool: {}", err)]
+    QueryPoolCreationError { err: vk::Result },
+    #[error(display = "Failed to create Render target: {}", err)]
+    RenderTargetError { err: vk::Result },
+    #[error(display = "Invalid render graph: {}", err)]
+    RenderGraphError { err: String },
+    #[error(display = "Invalid post chain: {}", err)]
+    PostChainError { err: String },
+}
+
+// what the renderer draws into
+pub enum RenderOutput {
+    Window(Window),
+    // offscreen images of a fixed size, every frame gets read back (see `Renderer::read_frame`)
+    Headless { width: u32, height: u32 },
+}
+
+// only one exists per renderer, so the size difference doesn't matter
+#[allow(clippy::large_enum_variant)]
+enum Output {
+    Window(WindowOutput),
+    // one image per frame in flight
+    Offscreen(Vec<AttachmentImage>),
+}
+
+impl Output {
+    fn final_layout(&self) -> vk::ImageLayout {
+        match self {
+            Output::Window(_) => vk::ImageLayout::PRESENT_SRC_KHR,
+            Output::Offscreen(_) => vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        }
+    }
+}
+
+// everything prepared for the frame's command buffer
+struct FrameBatches {
+    // (pipeline index, model matrix), sorted by pipeline
+    draws: Vec<(usize, glm::Mat4)>,
+    sprites: SpriteBatches,
+    text: TextBatches,
+    lines: LineBatch,
+    ui: UiBatches,
 }
 
 pub struct Renderer {
-    window: Window,
     #[allow(dead_code)]
     entry: Entry,
     instance: Instance,
     debug: Option<(DebugUtils, vk::DebugUtilsMessengerEXT)>,
-    surface: vk::SurfaceKHR,
-    surface_loader: Surface,
+    debug_names: DebugNames,
+    validation: Box<Validation>,
+    // of the last frame
+    validation_counts: ValidationCounts,
+    frame_failed: bool,
     physical_device: vk::PhysicalDevice,
     device: Device,
+    allocator: Allocator,
     graphics_queue: vk::Queue,
     graphics_family_index: u32,
-    present_queue: vk::Queue,
-    present_family_index: u32,
-    swapchain_loader: Swapchain,
-    swapchain: vk::SwapchainKHR,
+    output: Output,
+    color_format: vk::Format,
+    extent: vk::Extent2D,
+    // the images of the output, the views are owned by the swapchain output only
+    images: Vec<vk::Image>,
     image_views: Vec<vk::ImageView>,
-    shader_modules: Vec<(vk::ShaderModule, vk::ShaderModule)>,
+    // the passes and their transient images
+    graph: RenderGraph,
+    // scene, sprites and text
+    main_pass: PassId,
+    // developer ui, after the post chain
+    overlay_pass: PassId,
+    readback: Option<Readback>,
+    // copy of the swapchain image, created on the first screenshot
+    screenshot_readback: Option<Readback>,
+    shader_modules: FnvHashMap<String, (vk::ShaderModule, vk::ShaderModule)>,
+    uniforms: FrameUniforms,
     pipeline_layout: vk::PipelineLayout,
-    render_pass: vk::RenderPass,
-    pipeline: vk::Pipeline,
-    swapchain_framebuffers: Vec<vk::Framebuffer>,
+    pipeline_cache: PipelineCache,
+    // pipeline name -> index into pipelines
+    pipeline_indices: FnvHashMap<String, usize>,
+    pipelines: Vec<vk::Pipeline>,
+    sprites: SpritePass,
+    text: TextPass,
+    lines: LinePass,
+    ui: UiPass,
+    post: PostChain,
     command_pool: vk::CommandPool,
     command_buffers: Vec<vk::CommandBuffer>,
-    image_available_semaphores: Vec<vk::Semaphore>,
-    render_finished_semaphores: Vec<vk::Semaphore>,
     in_flight_fences: Vec<vk::Fence>,
+    timestamps: Timestamps,
+    // of the last resolved frame
+    pass_timings: Vec<PassTiming>,
+    frames_in_flight: usize,
     current_frame: usize,
+    // frame in flight index of the last submitted frame
+    last_submitted: Option<usize>,
+    // minimum time between frames, set by max_fps
+    frame_time: Option<Duration>,
+    last_frame: Instant,
 }
 
 impl Renderer {
     pub fn new(
-        window: Window,
+        output: RenderOutput,
         validation: bool,
+        settings: GraphicsSettings,
         res: Arc<RwLock<ResourcesData>>,
         names: HashMap<String, Vec<String>, FnvBuildHasher>,
+        pipelines: Vec<(String, PipelineDesc)>,
+        pipeline_cache_path: PathBuf,
     ) -> Result<Self, RendererInitError> {
         unsafe {
             let entry = Entry::new().map_err(|err| match err {
                 LoadingError::LibraryLoadError(err) => RendererInitError::LoadingError { err },
             })?;
 
-            let mut instance_layer_names = Vec::new();
-            let mut instance_extension_names = platform::extension_names();
-
-            if validation {
-                for layer in VALIDATION_LAYERS.iter() {
-                    instance_layer_names.push(string_pointer(layer));
-                }
-
-                for extension in INSTANCE_EXTENSIONS.iter() {
-                    instance_extension_names.push(string_pointer(extension));
-                }
-            }
+            // a headless renderer doesn't need any surface extensions
+            let is_window = match &output {
+                RenderOutput::Window(_) => true,
+                RenderOutput::Headless { .. } => false,
+            };
+            let instance_config = InstanceConfig::new(&entry, is_window, validation)?;
+            let instance_layer_names = instance_config.layer_names();
+            let instance_extension_names = instance_config.extension_names();
 
             let application_name = CString::new("evn").unwrap();
             let engine_name = CString::new("evn_engine").unwrap();
@@ -132,7 +289,7 @@ impl Renderer {
                 .application_version(0)
                 .engine_name(&engine_name)
                 .engine_version(0)
-                .api_version(vk_make_version!(1, 0, 36));
+                .api_version(instance_config.api_version);
 
             let instance_create_info = vk::InstanceCreateInfo::builder()
                 .application_info(&app_info)
@@ -148,7 +305,9 @@ impl Renderer {
                     },
                 })?;
 
-            let debug = if validation {
+            // boxed so the callback's pointer stays valid when the renderer moves
+            let validation = Box::new(Validation::new(settings.validation.clone()));
+            let debug = if instance_config.debug_utils {
                 let debug_loader = DebugUtils::new(&entry, &instance);
 
                 let debug_create_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
@@ -164,6 +323,7 @@ impl Renderer {
                             | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
                     )
                     .pfn_user_callback(Some(vulkan_debug_callback))
+                    .user_data(validation.user_data())
                     .build();
 
                 let debug_messenger = debug_loader
@@ -175,120 +335,64 @@ impl Renderer {
                 None
             };
 
-            let surface = platform::create_surface(&entry, &instance, &window)
-                .map_err(|err| RendererInitError::SurfaceCreationError { err })?;
-            let surface_loader = Surface::new(&entry, &instance);
+            let surface = match &output {
+                RenderOutput::Window(window) => Some((
+                    Surface::new(&entry, &instance),
+                    platform::create_surface(
+                        &entry,
+                        &instance,
+                        &instance_config.extensions,
+                        window,
+                    )
+                    .map_err(|err| RendererInitError::SurfaceCreationError { err })?,
+                )),
+                RenderOutput::Headless { .. } => None,
+            };
 
-            // maybe provide gui for device selection down the line?
-            let (
-                physical_device,
-                graphics_family_index,
-                present_family_index,
-                surface_capabilites,
-                surface_present_modes,
-                surface_formats,
-            ) = instance
+            let candidates = instance
                 .enumerate_physical_devices()
                 .map_err(|err| RendererInitError::PhysicalDeviceError {
                     err: Either::Left(err),
                 })?
                 .into_iter()
-                .filter_map(|physical_device| {
-                    let device_extension_properties =
-                        instance.enumerate_device_extension_properties(physical_device);
-                    let device_extension_properties = match device_extension_properties {
-                        Ok(device_extension_properties) => device_extension_properties
-                            .iter()
-                            .map(|property| {
-                                CStr::from_ptr(&property.extension_name as *const i8)
-                                    .to_string_lossy()
-                                    .into_owned()
-                            })
-                            .collect::<Vec<_>>(),
-                        Err(err) => return Some(Err(err)),
-                    };
-
-                    if !DEVICE_EXTENSIONS.iter().all(|extension| {
-                        device_extension_properties.contains(&extension.to_string())
-                    }) {
-                        return None;
-                    };
-
-                    let (surface_capabilites, surface_present_modes, surface_formats) =
-                        match surface_information(&surface_loader, surface, physical_device) {
-                            Ok(information) => information,
-                            Err(err) => return Some(Err(err)),
-                        };
-
-                    if surface_formats.is_empty() || surface_present_modes.is_empty() {
-                        return None;
-                    }
-
-                    let queue_family_properties =
-                        instance.get_physical_device_queue_family_properties(physical_device);
-
-                    let graphics_family_index = queue_family_properties
-                        .iter()
-                        .enumerate()
-                        .filter_map(|(index, properties)| {
-                            let support = properties.queue_flags.contains(vk::QueueFlags::GRAPHICS);
-
-                            if support {
-                                Some(index as u32)
-                            } else {
-                                None
-                            }
-                        })
-                        .nth(0);
-
-                    let present_family_index = (0..queue_family_properties.len())
-                        .filter_map(|index| {
-                            let support = surface_loader.get_physical_device_surface_support(
-                                physical_device,
-                                index as u32,
-                                surface,
-                            );
-
-                            if support {
-                                Some(index as u32)
-                            } else {
-                                None
-                            }
+                .enumerate()
+                .filter_map(|(index, physical_device)| {
+                    device_candidate(&instance, physical_device, surface.as_ref())
+                        .map(|candidate| {
+                            candidate.map(|candidate| (index, physical_device, candidate))
                         })
-                        .nth(0);
-
-                    match (graphics_family_index, present_family_index) {
-                        (Some(graphics), Some(present)) => Some(Ok((
-                            physical_device,
-                            graphics,
-                            present,
-                            surface_capabilites,
-                            surface_present_modes,
-                            surface_formats,
-                        ))),
-                        _ => None,
-                    }
+                        .transpose()
                 })
-                .nth(0)
-                .ok_or(RendererInitError::PhysicalDeviceError {
-                    err: Either::Right("Failed to find suitable device".into()),
-                })?
+                .collect::<VkResult<Vec<_>>>()
                 .map_err(|err| RendererInitError::PhysicalDeviceError {
                     err: Either::Left(err),
                 })?;
 
-            let device_features = vk::PhysicalDeviceFeatures::builder();
-
-            let mut device_layer_names = Vec::new();
+            let (physical_device, (graphics_family_index, present_family_index, surface_formats)) =
+                select_physical_device(
+                    &instance,
+                    instance_config.api_version,
+                    candidates,
+                    settings.gpu.as_ref(),
+                )?;
+
+            // needed for line and point polygon modes
+            let non_solid_fill = instance
+                .get_physical_device_features(physical_device)
+                .fill_mode_non_solid
+                == vk::TRUE;
+
+            let device_features =
+                vk::PhysicalDeviceFeatures::builder().fill_mode_non_solid(non_solid_fill);
+
+            // device layers are deprecated, older loaders still expect the instance ones
+            let device_layer_names = instance_layer_names;
             let mut device_extension_names = Vec::new();
-            if validation {
-                for layer in VALIDATION_LAYERS.iter() {
-                    device_layer_names.push(string_pointer(layer));
-                }
-            }
 
-            for extension in DEVICE_EXTENSIONS.iter() {
-                device_extension_names.push(string_pointer(extension));
+            if surface.is_some() {
+                for extension in DEVICE_EXTENSIONS.iter() {
+                    device_extension_names.push(string_pointer(extension));
+                }
             }
 
             let mut queue_family_indices = vec![graphics_family_index, present_family_index];
@@ -315,18 +419,54 @@ impl Renderer {
                 .create_device(physical_device, &device_create_info, None)
                 .map_err(|err| RendererInitError::DeviceCreationError { err })?;
 
+            let mut allocator = Allocator::new(&instance, physical_device, &device);
+
+            let debug_names = DebugNames::new(
+                if instance_config.debug_utils {
+                    Some(DebugUtils::new(&entry, &instance))
+                } else {
+                    None
+                },
+                device.handle(),
+            );
+
+            let device_properties = instance.get_physical_device_properties(physical_device);
+
+            let pipeline_cache =
+                PipelineCache::new(&device, &device_properties, pipeline_cache_path)?;
+
+            let depth_format = if settings.depth_buffer {
+                let depth_format = targets::choose_depth_format(&instance, physical_device);
+                if depth_format.is_none() {
+                    warn!("No supported depth format, disabling depth buffer");
+                }
+                depth_format
+            } else {
+                None
+            };
+
+            let samples = targets::choose_sample_count(
+                &device_properties.limits,
+                settings.msaa_samples,
+                depth_format.is_some(),
+            );
+            if samples.as_raw() != settings.msaa_samples {
+                warn!(
+                    "{}x MSAA requested, using {}x",
+                    settings.msaa_samples,
+                    samples.as_raw()
+                );
+            }
+
             let graphics_queue = device.get_device_queue(graphics_family_index, 0);
-            let present_queue = device.get_device_queue(present_family_index, 0);
 
+            // post shaders don't get default pipelines
+            let post_shader_names = names.get("post_shaders").map_or(&[][..], Vec::as_slice);
             let shader_modules = names["shaders"]
                 .iter()
+                .chain(post_shader_names)
                 .map(|shader_name| {
-                    // wait until loaded
-                    while res.read().unwrap().get_resource(shader_name).is_loading() {
-                        thread::sleep(Duration::from_millis(10));
-                    }
-
-                    let resource = res.read().unwrap().get_resource(shader_name);
+                    let resource = res.read().unwrap().wait_for_resource(shader_name);
                     match *resource {
                         ResourceState::Loaded(ref resource) => match resource {
                             Resource::Shader(Shader { vert, frag }) => {
@@ -348,7 +488,10 @@ impl Renderer {
                                         err: Either::Left(err),
                                     })?;
 
-                                Ok((vertex_shader_module, fragment_shader_module))
+                                Ok((
+                                    shader_name.clone(),
+                                    (vertex_shader_module, fragment_shader_module),
+                                ))
                             }
                             _ => panic!("Non shader resource in shader List"),
                         },
@@ -358,110 +501,743 @@ impl Renderer {
                         }),
                     }
                 })
+                .collect::<Result<FnvHashMap<_, _>, _>>()?;
+
+            // every shader gets a default pipeline, unless one with its name was registered
+            let mut pipeline_descs = names["shaders"]
+                .iter()
+                .filter(|shader_name| !pipelines.iter().any(|(name, _)| name == *shader_name))
+                .map(|shader_name| (shader_name.clone(), PipelineDesc::new(shader_name)))
+                .collect::<Vec<_>>();
+            pipeline_descs.extend(pipelines);
+
+            let pipeline_indices = pipeline_descs
+                .iter()
+                .enumerate()
+                .map(|(index, (name, _))| (name.clone(), index))
+                .collect();
+
+            let uniforms = FrameUniforms::new(&device, &mut allocator, settings.frames_in_flight)?;
+
+            let texture_names = names.get("textures").map_or(&[][..], Vec::as_slice);
+            let texture_resources = texture_names
+                .iter()
+                .map(|texture_name| {
+                    let resource = res.read().unwrap().wait_for_resource(texture_name);
+                    match *resource {
+                        ResourceState::Loaded(Resource::Texture(_)) => Ok(resource.clone()),
+                        ResourceState::Loaded(_) => panic!("Non texture resource in texture List"),
+                        _ => Err(RendererInitError::TextureLoadingError {
+                            name: texture_name.clone(),
+                        }),
+                    }
+                })
                 .collect::<Result<Vec<_>, _>>()?;
+            let textures = texture_resources
+                .iter()
+                .map(|resource| match &**resource {
+                    ResourceState::Loaded(Resource::Texture(texture)) => texture,
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>();
+            let textures = texture::upload_textures(
+                &device,
+                &mut allocator,
+                graphics_queue,
+                graphics_family_index,
+                &textures,
+            )?;
 
-            let swapchain_loader = Swapchain::new(&instance, &device);
+            let font_names = names.get("fonts").map_or(&[][..], Vec::as_slice);
+            let fonts = font_names
+                .iter()
+                .map(|font_name| {
+                    let resource = res.read().unwrap().wait_for_resource(font_name);
+                    match *resource {
+                        ResourceState::Loaded(Resource::Font(_)) => {
+                            Ok((font_name.clone(), resource.clone()))
+                        }
+                        ResourceState::Loaded(_) => panic!("Non font resource in font List"),
+                        _ => Err(RendererInitError::FontLoadingError {
+                            name: font_name.clone(),
+                        }),
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
 
+            // command buffers are recorded every frame, one per frame in flight
             let command_pool_create_info = vk::CommandPoolCreateInfo::builder()
                 .queue_family_index(graphics_family_index)
-                .flags(vk::CommandPoolCreateFlags::empty());
+                .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
 
             let command_pool = device
                 .create_command_pool(&command_pool_create_info, None)
                 .map_err(|err| RendererInitError::CommandBufferError { err })?;
 
-            let (
-                swapchain,
-                image_views,
-                pipeline_layout,
-                render_pass,
-                pipeline,
-                swapchain_framebuffers,
-                command_buffers,
-            ) = create_swapchain(
-                surface_formats,
-                surface_present_modes,
-                surface_capabilites,
-                surface,
-                &window,
+            let command_buffer_alloc_info = vk::CommandBufferAllocateInfo::builder()
+                .command_pool(command_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(settings.frames_in_flight as u32);
+
+            let command_buffers = device
+                .allocate_command_buffers(&command_buffer_alloc_info)
+                .map_err(|err| RendererInitError::CommandBufferError { err })?;
+
+            let semaphore_create_info = vk::SemaphoreCreateInfo::builder();
+            let create_semaphores = || {
+                (0..settings.frames_in_flight)
+                    .map(|_| device.create_semaphore(&semaphore_create_info, None))
+                    .collect::<VkResult<Vec<_>>>()
+                    .map_err(|err| RendererInitError::SyncCreationError { err })
+            };
+
+            let (mut output, extent, images, image_views, color_format, readback) = match output {
+                RenderOutput::Window(window) => {
+                    let (surface_loader, surface) = surface.unwrap();
+
+                    let mut output = WindowOutput {
+                        window_size: window
+                            .get_inner_size()
+                            .unwrap_or_else(|| LogicalSize::new(0.0, 0.0)),
+                        window,
+                        surface_loader,
+                        surface,
+                        surface_format: swapchain::choose_swap_surface_format(surface_formats),
+                        swapchain_loader: Swapchain::new(&instance, &device),
+                        swapchain: vk::SwapchainKHR::null(),
+                        present_queue: device.get_device_queue(present_family_index, 0),
+                        present_family_index,
+                        present_mode: settings.present_mode,
+                        outdated: false,
+                        transfer_src: false,
+                        image_available_semaphores: create_semaphores()?,
+                        render_finished_semaphores: create_semaphores()?,
+                    };
+
+                    let (extent, images) =
+                        output.create_swapchain(physical_device, graphics_family_index)?;
+                    let color_format = output.surface_format.format;
+                    let image_views = create_image_views(&device, &images, color_format)?;
+
+                    (
+                        Output::Window(output),
+                        extent,
+                        images,
+                        image_views,
+                        color_format,
+                        None,
+                    )
+                }
+                RenderOutput::Headless { width, height } => {
+                    let extent = vk::Extent2D { width, height };
+
+                    let offscreen_images = (0..settings.frames_in_flight)
+                        .map(|_| {
+                            AttachmentImage::new(
+                                &device,
+                                &mut allocator,
+                                extent,
+                                HEADLESS_FORMAT,
+                                vk::SampleCountFlags::TYPE_1,
+                                vk::ImageUsageFlags::COLOR_ATTACHMENT
+                                    | vk::ImageUsageFlags::TRANSFER_SRC,
+                                vk::ImageAspectFlags::COLOR,
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let images = offscreen_images.iter().map(|image| image.image).collect();
+                    let image_views = offscreen_images.iter().map(|image| image.view).collect();
+
+                    let readback = Readback::new(
+                        &mut allocator,
+                        extent,
+                        HEADLESS_FORMAT,
+                        settings.frames_in_flight,
+                    )?;
+
+                    (
+                        Output::Offscreen(offscreen_images),
+                        extent,
+                        images,
+                        image_views,
+                        HEADLESS_FORMAT,
+                        Some(readback),
+                    )
+                }
+            };
+
+            // the output format doesn't change when the swapchain gets recreated,
+            // so the render passes and pipelines are only created once
+            let mut graph_builder = RenderGraphBuilder::new(color_format, output.final_layout());
+            // with a post chain the scene is rendered in HDR and the chain writes the output
+            let (scene, scene_format) = if settings.post.is_empty() {
+                (graph_builder.output(), color_format)
+            } else {
+                let scene = graph_builder.image("scene", ImageDesc::new(post::HDR_FORMAT));
+                (scene, post::HDR_FORMAT)
+            };
+            let clear_color = Some([0.0, 0.0, 0.0, 1.0]);
+            let mut main_pass = PassDesc::new("main pass");
+            main_pass = if samples != vk::SampleCountFlags::TYPE_1 {
+                // resolved into the scene
+                let msaa_color = graph_builder.image(
+                    "msaa color target",
+                    ImageDesc::new(scene_format).samples(samples),
+                );
+                main_pass.color(msaa_color, clear_color).resolve(scene)
+            } else {
+                main_pass.color(scene, clear_color)
+            };
+            if let Some(depth_format) = depth_format {
+                let depth = graph_builder.image(
+                    "depth target",
+                    ImageDesc::new(depth_format).samples(samples),
+                );
+                main_pass = main_pass.depth(depth, Some(1.0));
+            }
+            let main_pass = graph_builder.pass(main_pass);
+            let post_steps = post::declare_chain(&mut graph_builder, &settings.post, scene)?;
+            // drawn over the post-processed output
+            let overlay_pass =
+                graph_builder.pass(PassDesc::new("overlay").color(graph_builder.output(), None));
+            let graph = graph_builder.build(&device, &mut allocator, extent, &image_views)?;
+            let pipeline_layout =
+                create_pipeline_layout(&device, uniforms.descriptor_set_layout())?;
+            let pipeline_target = PipelineTarget {
+                layout: pipeline_layout,
+                render_pass: graph.render_pass(main_pass),
+                samples,
+                depth: depth_format.is_some(),
+                non_solid_fill,
+            };
+            let pipelines = pipeline::create_pipelines(
+                &device,
+                &pipeline_descs,
+                &shader_modules,
+                pipeline_cache.handle(),
+                &pipeline_target,
+            )?;
+            let sprites = SpritePass::new(
+                &device,
+                &mut allocator,
+                texture_names.iter().cloned().zip(textures).collect(),
+                uniforms.descriptor_set_layout(),
+                &pipeline_target,
+                pipeline_cache.handle(),
+                settings.frames_in_flight,
+            )?;
+            let text = TextPass::new(
+                &device,
+                &mut allocator,
+                graphics_queue,
                 graphics_family_index,
-                present_family_index,
+                fonts,
+                &sprites,
+                settings.frames_in_flight,
+            )?;
+
+            let lines = LinePass::new(
+                &device,
+                &mut allocator,
+                &pipeline_target,
+                pipeline_cache.handle(),
+                settings.frames_in_flight,
+            )?;
+            let overlay_target = PipelineTarget {
+                layout: pipeline_layout,
+                render_pass: graph.render_pass(overlay_pass),
+                samples: vk::SampleCountFlags::TYPE_1,
+                depth: false,
+                non_solid_fill,
+            };
+            let ui = UiPass::new(
                 &device,
-                &swapchain_loader,
+                &mut allocator,
+                &sprites,
+                &overlay_target,
+                pipeline_cache.handle(),
+                settings.frames_in_flight,
+            )?;
+            let post = PostChain::new(
+                &device,
+                &graph,
+                post_steps,
                 &shader_modules,
-                command_pool,
+                &sprites,
+                pipeline_cache.handle(),
             )?;
 
-            let semaphore_create_info = vk::SemaphoreCreateInfo::builder();
             let fence_create_info =
                 vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
-            let sync = (0..MAX_FRAMES_IN_FLIGHT)
-                .map(|_| {
-                    let image_available_semaphore = device
-                        .create_semaphore(&semaphore_create_info, None)
-                        .map_err(|err| RendererInitError::SyncCreationError { err })?;
-                    let render_finished_semaphore = device
-                        .create_semaphore(&semaphore_create_info, None)
-                        .map_err(|err| RendererInitError::SyncCreationError { err })?;
-                    let in_flight_fence = device
-                        .create_fence(&fence_create_info, None)
-                        .map_err(|err| RendererInitError::SyncCreationError { err })?;
-
-                    Ok((
-                        image_available_semaphore,
-                        render_finished_semaphore,
-                        in_flight_fence,
-                    ))
-                })
-                .collect::<Result<Vec<_>, _>>()?;
+            let in_flight_fences = (0..settings.frames_in_flight)
+                .map(|_| device.create_fence(&fence_create_info, None))
+                .collect::<VkResult<Vec<_>>>()
+                .map_err(|err| RendererInitError::SyncCreationError { err })?;
 
-            let mut image_available_semaphores = Vec::new();
-            let mut render_finished_semaphores = Vec::new();
-            let mut in_flight_fences = Vec::new();
+            let timestamps = Timestamps::new(
+                &instance,
+                &device,
+                physical_device,
+                graphics_family_index,
+                settings.frames_in_flight,
+            )?;
 
-            for (image_available_semaphore, render_finished_semaphore, in_flight_fence) in sync {
-                image_available_semaphores.push(image_available_semaphore);
-                render_finished_semaphores.push(render_finished_semaphore);
-                in_flight_fences.push(in_flight_fence);
+            if let Output::Window(output) = &mut output {
+                output.outdated = false;
             }
 
-            Ok(Renderer {
-                window,
+            let renderer = Renderer {
                 entry,
                 instance,
                 debug,
-                surface,
-                surface_loader,
+                debug_names,
+                validation,
+                validation_counts: ValidationCounts::default(),
+                frame_failed: false,
                 physical_device,
                 device,
+                allocator,
                 graphics_queue,
                 graphics_family_index,
-                present_queue,
-                present_family_index,
-                swapchain_loader,
-                swapchain,
+                output,
+                color_format,
+                extent,
+                images,
                 image_views,
+                graph,
+                main_pass,
+                overlay_pass,
+                readback,
+                screenshot_readback: None,
                 shader_modules,
+                uniforms,
                 pipeline_layout,
-                render_pass,
-                pipeline,
-                swapchain_framebuffers,
+                pipeline_cache,
+                pipeline_indices,
+                pipelines,
+                sprites,
+                text,
+                lines,
+                ui,
+                post,
                 command_pool,
                 command_buffers,
-                image_available_semaphores,
-                render_finished_semaphores,
                 in_flight_fences,
+                timestamps,
+                pass_timings: Vec::new(),
+                frames_in_flight: settings.frames_in_flight,
                 current_frame: 0,
-            })
+                last_submitted: None,
+                frame_time: settings
+                    .max_fps
+                    .map(|max_fps| Duration::from_secs(1) / max_fps),
+                last_frame: Instant::now(),
+            };
+            renderer.name_objects();
+            renderer.name_targets();
+
+            // errors during creation can't be attributed to a frame
+            let (_, first_error) = renderer.validation.end_frame();
+            match (first_error, renderer.validation.settings().on_error) {
+                (Some(_), OnValidationError::Log) | (None, _) => Ok(renderer),
+                (Some(error), _) => Err(RendererInitError::ValidationError {
+                    err: error.to_string(),
+                }),
+            }
+        }
+    }
+
+    // everything that lives as long as the renderer
+    unsafe fn name_objects(&self) {
+        let names = &self.debug_names;
+
+        names.name(self.graphics_queue, "graphics queue");
+        names.name(self.pipeline_layout, "pipeline layout");
+        names.name(self.pipeline_cache.handle(), "pipeline cache");
+        for (name, index) in &self.pipeline_indices {
+            names.name(self.pipelines[*index], &format!("{} pipeline", name));
+        }
+        for (name, (vertex, fragment)) in &self.shader_modules {
+            names.name(*vertex, &format!("{} vertex shader", name));
+            names.name(*fragment, &format!("{} fragment shader", name));
+        }
+        names.name(self.command_pool, "command pool");
+        names.name_all(&self.command_buffers, "command buffer");
+        names.name_all(&self.in_flight_fences, "in flight fence");
+        self.timestamps.name(names);
+        self.uniforms.name(names);
+        self.sprites.name(names);
+        self.text.name(names);
+        self.lines.name(names);
+        self.ui.name(names);
+        self.post.name(names);
+
+        match &self.output {
+            Output::Window(output) => {
+                names.name(output.present_queue, "present queue");
+                names.name_all(&output.image_available_semaphores, "image available");
+                names.name_all(&output.render_finished_semaphores, "render finished");
+            }
+            Output::Offscreen(images) => {
+                for (index, image) in images.iter().enumerate() {
+                    image.name(names, &format!("offscreen image {}", index));
+                }
+            }
+        }
+        if let Some(readback) = &self.readback {
+            readback.name(names, "readback");
+        }
+    }
+
+    // everything recreated with the swapchain
+    unsafe fn name_targets(&self) {
+        let names = &self.debug_names;
+
+        if let Output::Window(output) = &self.output {
+            names.name(output.swapchain, "swapchain");
+            names.name_all(&self.images, "swapchain image");
+            names.name_all(&self.image_views, "swapchain image view");
+        }
+        self.graph.name(names);
+    }
+
+    // validation messages of the last frame, all zero without --validation
+    pub fn validation_counts(&self) -> ValidationCounts {
+        self.validation_counts
+    }
+
+    // makes sure the next frame can be copied, offscreen frames are always read back
+    unsafe fn prepare_screenshot(&mut self) -> bool {
+        let output = match &self.output {
+            Output::Window(output) => output,
+            Output::Offscreen(_) => return true,
+        };
+
+        if !output.transfer_src {
+            warn!("Screenshots aren't supported by the surface");
+            return false;
+        }
+
+        if self.screenshot_readback.is_none() {
+            match Readback::new(&mut self.allocator, self.extent, self.color_format, 1) {
+                Ok(screenshot_readback) => {
+                    screenshot_readback.name(&self.debug_names, "screenshot");
+                    self.screenshot_readback = Some(screenshot_readback);
+                }
+                Err(err) => {
+                    error!("Failed to take screenshot: {}", err);
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    // rebuilds everything depending on the window size,
+    // returns false if the surface has no area (minimized) and nothing was created
+    unsafe fn recreate_swapchain(&mut self) -> Result<bool, RendererInitError> {
+        let output = match &mut self.output {
+            Output::Window(output) => output,
+            Output::Offscreen(_) => return Ok(true),
+        };
+
+        self.device.device_wait_idle().map_err(|err| {
+            RendererInitError::SwapchainCreationError {
+                err: Either::Left(err),
+            }
+        })?;
+
+        let extent = output.extent(self.physical_device).map_err(|err| {
+            RendererInitError::SwapchainCreationError {
+                err: Either::Left(err),
+            }
+        })?;
+        if extent.width == 0 || extent.height == 0 {
+            return Ok(false);
+        }
+
+        for image_view in self.image_views.drain(..) {
+            self.device.destroy_image_view(image_view, None);
+        }
+        // the size changed, the next screenshot creates a new one
+        if let Some(mut screenshot_readback) = self.screenshot_readback.take() {
+            screenshot_readback.destroy(&mut self.allocator);
+        }
+
+        let (extent, images) =
+            output.create_swapchain(self.physical_device, self.graphics_family_index)?;
+        output.outdated = false;
+
+        self.image_views = create_image_views(&self.device, &images, self.color_format)?;
+        self.images = images;
+        self.extent = extent;
+        self.graph
+            .resize(&self.device, &mut self.allocator, extent, &self.image_views)?;
+        self.post.write_sets(&self.device, &self.graph);
+        self.name_targets();
+
+        Ok(true)
+    }
+
+    unsafe fn record_command_buffer(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        image_index: u32,
+        camera: &CameraMatrices,
+        batches: &FrameBatches,
+        screenshot: bool,
+    ) -> VkResult<Vec<(String, Duration)>> {
+        self.device
+            .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())?;
+
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+        self.device
+            .begin_command_buffer(command_buffer, &begin_info)?;
+        self.timestamps
+            .reset(&self.device, command_buffer, self.current_frame);
+
+        // (name, CPU time) of every pass
+        let mut passes = Vec::new();
+
+        if batches.text.has_uploads() {
+            let start = self.begin_pass(command_buffer, &passes, "glyph upload");
+            self.text
+                .record_uploads(&self.device, command_buffer, &batches.text);
+            self.end_pass(command_buffer, &mut passes, "glyph upload", start);
+        }
+
+        for &pass in self.graph.order() {
+            let name = self.graph.pass_name(pass);
+            let start = self.begin_pass(command_buffer, &passes, name);
+            self.graph
+                .begin(&self.device, command_buffer, pass, image_index as usize);
+            if pass == self.main_pass {
+                self.record_main_pass(command_buffer, camera, batches);
+            } else if pass == self.overlay_pass {
+                self.record_overlay(command_buffer, batches);
+            } else {
+                self.post
+                    .record(&self.device, command_buffer, &self.graph, pass);
+            }
+            self.graph.end(&self.device, command_buffer);
+            self.end_pass(command_buffer, &mut passes, name, start);
+        }
+
+        if let Some(readback) = &self.readback {
+            let start = self.begin_pass(command_buffer, &passes, "readback");
+            readback.record(
+                &self.device,
+                command_buffer,
+                self.current_frame,
+                self.images[image_index as usize],
+                self.output.final_layout(),
+            );
+            self.end_pass(command_buffer, &mut passes, "readback", start);
+        }
+
+        if let (true, Some(screenshot_readback)) = (screenshot, &self.screenshot_readback) {
+            let start = self.begin_pass(command_buffer, &passes, "screenshot");
+            screenshot_readback.record(
+                &self.device,
+                command_buffer,
+                0,
+                self.images[image_index as usize],
+                self.output.final_layout(),
+            );
+            self.end_pass(command_buffer, &mut passes, "screenshot", start);
         }
+
+        self.device.end_command_buffer(command_buffer)?;
+
+        Ok(passes)
+    }
+
+    // scene draws, sprites, text and debug lines
+    unsafe fn record_main_pass(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        camera: &CameraMatrices,
+        batches: &FrameBatches,
+    ) {
+        let extent = self.graph.pass_extent(self.main_pass);
+
+        self.device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline_layout,
+            0,
+            &[self.uniforms.descriptor_set(self.current_frame)],
+            &[],
+        );
+
+        let (viewport, scissor) = camera_viewport(&camera.viewport, extent);
+        self.device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+        self.device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+
+        let mut bound_pipeline = None;
+        for (pipeline_index, model) in &batches.draws {
+            if bound_pipeline != Some(*pipeline_index) {
+                self.device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.pipelines[*pipeline_index],
+                );
+                bound_pipeline = Some(*pipeline_index);
+            }
+
+            self.device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                PushConstants::new(model).as_bytes(),
+            );
+
+            self.device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+
+        self.debug_names.begin_label(command_buffer, "sprites");
+        self.sprites.record(
+            &self.device,
+            command_buffer,
+            self.uniforms.descriptor_set(self.current_frame),
+            &batches.sprites,
+        );
+        self.debug_names.end_label(command_buffer);
+
+        self.debug_names.begin_label(command_buffer, "world text");
+        self.text.record_world(
+            &self.device,
+            command_buffer,
+            &self.sprites,
+            self.uniforms.descriptor_set(self.current_frame),
+            &batches.text,
+        );
+        self.debug_names.end_label(command_buffer);
+
+        self.debug_names.begin_label(command_buffer, "debug lines");
+        self.lines.record(
+            &self.device,
+            command_buffer,
+            self.uniforms.descriptor_set(self.current_frame),
+            &batches.lines,
+        );
+        self.debug_names.end_label(command_buffer);
+
+        let (viewport, scissor) = camera_viewport(&Viewport::default(), extent);
+        self.device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+        self.device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+
+        self.debug_names.begin_label(command_buffer, "screen text");
+        self.text.record_screen(
+            &self.device,
+            command_buffer,
+            &self.sprites,
+            self.current_frame,
+            &batches.text,
+        );
+        self.debug_names.end_label(command_buffer);
+    }
+
+    // the developer ui, not affected by the post chain
+    unsafe fn record_overlay(&self, command_buffer: vk::CommandBuffer, batches: &FrameBatches) {
+        let extent = self.graph.pass_extent(self.overlay_pass);
+        let (viewport, scissor) = camera_viewport(&Viewport::default(), extent);
+        self.device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+        self.device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+
+        self.debug_names.begin_label(command_buffer, "developer ui");
+        self.ui
+            .record(&self.device, command_buffer, extent, &batches.ui);
+        self.debug_names.end_label(command_buffer);
+    }
+
+    // labels the pass and writes its first timestamp
+    unsafe fn begin_pass(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        passes: &[(String, Duration)],
+        name: &str,
+    ) -> Instant {
+        self.debug_names.begin_label(command_buffer, name);
+        self.timestamps.write(
+            &self.device,
+            command_buffer,
+            self.current_frame,
+            passes.len(),
+            false,
+        );
+
+        Instant::now()
+    }
+
+    unsafe fn end_pass(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        passes: &mut Vec<(String, Duration)>,
+        name: &str,
+        start: Instant,
+    ) {
+        self.timestamps.write(
+            &self.device,
+            command_buffer,
+            self.current_frame,
+            passes.len(),
+            true,
+        );
+        self.debug_names.end_label(command_buffer);
+
+        passes.push((name.to_owned(), start.elapsed()));
     }
 }
 
-impl<'a> System<'a> for Renderer {
-    type SystemData = ();
+impl RenderBackend for Renderer {
+    fn render(&mut self, frame: &RenderFrame) {
+        // also applies while minimized, so the loop doesn't spin
+        if let Some(frame_time) = self.frame_time {
+            let elapsed = self.last_frame.elapsed();
+            if elapsed < frame_time {
+                thread::sleep(frame_time - elapsed);
+            }
+        }
+        self.last_frame = Instant::now();
 
-    fn run(&mut self, (): Self::SystemData) {
         unsafe {
+            // also while minimized, the texture changes are only sent once
+            if let Some(ui) = frame.ui {
+                self.ui.update_textures(
+                    &self.device,
+                    &mut self.allocator,
+                    self.graphics_queue,
+                    self.graphics_family_index,
+                    &self.sprites,
+                    ui,
+                );
+            }
+
+            if let Output::Window(output) = &mut self.output {
+                if frame.window_size != output.window_size {
+                    output.window_size = frame.window_size;
+                    output.outdated = true;
+                }
+
+                // nothing to render to while minimized
+                if output.window_size.width <= 0.0 || output.window_size.height <= 0.0 {
+                    return;
+                }
+
+                if output.outdated
+                    && !self
+                        .recreate_swapchain()
+                        .unwrap_or_log("Failed to recreate swapchain")
+                {
+                    return;
+                }
+            }
+
             self.device
                 .wait_for_fences(
                     &[self.in_flight_fences[self.current_frame]],
@@ -470,92 +1246,81 @@ impl<'a> System<'a> for Renderer {
                 )
                 .unwrap_or_log("Failed to wait for fences");
 
-            let next_image = self.swapchain_loader.acquire_next_image(
-                self.swapchain,
-                u64::max_value(),
-                self.image_available_semaphores[self.current_frame],
-                vk::Fence::null(),
-            );
-            let image_index = match next_image {
-                Ok((image_index, _)) => image_index,
-                Err(err) => match err {
-                    vk::Result::ERROR_OUT_OF_DATE_KHR => {
-                        // recreate swapchain
-                        self.device
-                            .queue_wait_idle(self.present_queue)
-                            .unwrap_or_log("Failed to wait on present queue");
-
-                        let (surface_capabilites, surface_present_modes, surface_formats) =
-                            surface_information(
-                                &self.surface_loader,
-                                self.surface,
-                                self.physical_device,
-                            )
-                            .unwrap_or_log("Failed to get surface information");
-
-                        cleanup_swapchain(
-                            &self.device,
-                            self.command_pool,
-                            &self.command_buffers,
-                            &self.swapchain_framebuffers,
-                            self.pipeline,
-                            self.pipeline_layout,
-                            self.render_pass,
-                            &self.image_views,
-                            &self.swapchain_loader,
-                            self.swapchain,
-                        );
-
-                        let (
-                            swapchain,
-                            image_views,
-                            pipeline_layout,
-                            render_pass,
-                            pipeline,
-                            swapchain_framebuffers,
-                            command_buffers,
-                        ) = create_swapchain(
-                            surface_formats,
-                            surface_present_modes,
-                            surface_capabilites,
-                            self.surface,
-                            &self.window,
-                            self.graphics_family_index,
-                            self.present_family_index,
-                            &self.device,
-                            &self.swapchain_loader,
-                            &self.shader_modules,
-                            self.command_pool,
-                        )
-                        .unwrap_or_log("Failed to recreate swapchain");
-
-                        self.image_views = image_views;
-                        self.pipeline_layout = pipeline_layout;
-                        self.render_pass = render_pass;
-                        self.pipeline = pipeline;
-                        self.swapchain_framebuffers = swapchain_framebuffers;
-                        self.command_buffers = command_buffers;
-                        self.swapchain = swapchain;
-
-                        return;
-                    }
-                    _ => Err(err).unwrap_or_log("Failed to acquire next image"),
+            if let Some(pass_timings) = self.timestamps.resolve(&self.device, self.current_frame) {
+                self.pass_timings = pass_timings;
+            }
+
+            let image_index = match &mut self.output {
+                Output::Window(output) => match output.acquire_image(self.current_frame) {
+                    Some(image_index) => image_index,
+                    None => return,
                 },
+                Output::Offscreen(_) => self.current_frame as u32,
             };
 
             self.device
                 .reset_fences(&[self.in_flight_fences[self.current_frame]])
                 .unwrap_or_log("Failed to reset fences");
 
-            let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
-            let signal_semaphores = [self.render_finished_semaphores[self.current_frame]];
+            self.uniforms.update(self.current_frame, frame.camera);
+
+            let command_buffer = self.command_buffers[self.current_frame];
+            // materials referring to unknown pipelines are skipped
+            let mut draws = frame
+                .draws
+                .iter()
+                .filter_map(|draw| Some((*self.pipeline_indices.get(draw.pipeline)?, draw.model)))
+                .collect::<Vec<_>>();
+            draws.sort_by_key(|(pipeline_index, _)| *pipeline_index);
+
+            let labels = self
+                .text
+                .label_texts(frame.debug_labels, frame.camera, frame.window_size);
+            let mut texts = frame.texts.to_vec();
+            texts.extend(labels.iter().map(|(text, position)| TextDraw {
+                text,
+                position: *position,
+            }));
+
+            let batches = FrameBatches {
+                draws,
+                sprites: self.sprites.prepare(self.current_frame, frame.sprites),
+                text: self
+                    .text
+                    .prepare(self.current_frame, &texts, frame.window_size),
+                lines: self.lines.prepare(self.current_frame, frame.debug_lines),
+                ui: self
+                    .ui
+                    .prepare(self.current_frame, frame.ui, frame.window_size),
+            };
+
+            let take_screenshot = frame.screenshot && self.prepare_screenshot();
+
+            let passes = self
+                .record_command_buffer(
+                    command_buffer,
+                    image_index,
+                    frame.camera,
+                    &batches,
+                    take_screenshot,
+                )
+                .unwrap_or_log("Failed to record command buffer");
+
+            // offscreen images don't need to wait for the presentation engine
+            let (wait_semaphores, signal_semaphores) = match &self.output {
+                Output::Window(output) => (
+                    vec![output.image_available_semaphores[self.current_frame]],
+                    vec![output.render_finished_semaphores[self.current_frame]],
+                ),
+                Output::Offscreen(_) => (Vec::new(), Vec::new()),
+            };
             let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
 
             let submit_info = vk::SubmitInfo::builder()
                 .wait_semaphores(&wait_semaphores)
-                .wait_dst_stage_mask(&wait_stages)
+                .wait_dst_stage_mask(&wait_stages[..wait_semaphores.len()])
                 .signal_semaphores(&signal_semaphores)
-                .command_buffers(&[self.command_buffers[image_index as usize]])
+                .command_buffers(&[command_buffer])
                 .build();
 
             self.device
@@ -565,21 +1330,69 @@ impl<'a> System<'a> for Renderer {
                     self.in_flight_fences[self.current_frame],
                 )
                 .unwrap_or_log("Failed to submit to queue");
+            self.last_submitted = Some(self.current_frame);
+            self.timestamps.submitted(self.current_frame, passes);
+
+            if take_screenshot {
+                self.device
+                    .wait_for_fences(
+                        &[self.in_flight_fences[self.current_frame]],
+                        true,
+                        u64::max_value(),
+                    )
+                    .unwrap_or_log("Failed to wait for fences");
+
+                let screenshot = match &self.screenshot_readback {
+                    Some(screenshot_readback) => screenshot_readback.read(0),
+                    None => self.readback.as_ref().unwrap().read(self.current_frame),
+                };
+                screenshot::save(screenshot);
+            }
+
+            if let Output::Window(output) = &mut self.output {
+                output.present(self.current_frame, image_index);
+            }
+
+            self.current_frame = (self.current_frame + 1) % self.frames_in_flight;
+        }
 
-            let swapchains = [self.swapchain];
+        let (counts, first_error) = self.validation.end_frame();
+        self.validation_counts = counts;
+        self.frame_failed = false;
+        if let Some(error) = first_error {
+            match self.validation.settings().on_error {
+                OnValidationError::Log => (),
+                OnValidationError::Panic => panic!("Vulkan validation error: {}", error),
+                OnValidationError::FailFrame => {
+                    error!("Frame failed validation");
+                    self.frame_failed = true;
+                }
+            }
+        }
+    }
 
-            let present_info_image_indices = [image_index];
-            let present_info = vk::PresentInfoKHR::builder()
-                .wait_semaphores(&signal_semaphores)
-                .swapchains(&swapchains)
-                .image_indices(&present_info_image_indices);
+    // waits for the last submitted frame and copies it into CPU memory,
+    // only headless renderers read their frames back
+    fn read_frame(&self) -> Option<Frame> {
+        if self.frame_failed {
+            return None;
+        }
 
-            self.swapchain_loader
-                .queue_present(self.present_queue, &present_info)
-                .unwrap_or_log("Failed to submit to present queue");
+        let readback = self.readback.as_ref()?;
+        let frame = self.last_submitted?;
 
-            self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+        unsafe {
+            self.device
+                .wait_for_fences(&[self.in_flight_fences[frame]], true, u64::max_value())
+                .unwrap_or_log("Failed to wait for fences");
         }
+
+        Some(readback.read(frame))
+    }
+
+    // resolved a few frames late, empty until then
+    fn pass_timings(&self) -> Vec<PassTiming> {
+        self.pass_timings.clone()
     }
 }
 
@@ -587,42 +1400,63 @@ impl Drop for Renderer {
     fn drop(&mut self) {
         unsafe {
             self.device
-                .queue_wait_idle(self.present_queue)
-                .unwrap_or_log("Failed to wait on present queue");
+                .device_wait_idle()
+                .unwrap_or_log("Failed to wait for device");
 
-            for semaphore in &self.image_available_semaphores {
-                self.device.destroy_semaphore(*semaphore, None);
+            for fence in &self.in_flight_fences {
+                self.device.destroy_fence(*fence, None);
             }
+            self.timestamps.destroy(&self.device);
 
-            for semaphore in &self.render_finished_semaphores {
-                self.device.destroy_semaphore(*semaphore, None);
+            self.graph.destroy(&self.device, &mut self.allocator);
+
+            if let Some(readback) = &mut self.readback {
+                readback.destroy(&mut self.allocator);
+            }
+            if let Some(screenshot_readback) = &mut self.screenshot_readback {
+                screenshot_readback.destroy(&mut self.allocator);
             }
 
-            for fence in &self.in_flight_fences {
-                self.device.destroy_fence(*fence, None);
+            match &mut self.output {
+                Output::Window(output) => {
+                    for image_view in &self.image_views {
+                        self.device.destroy_image_view(*image_view, None);
+                    }
+                    output.destroy(&self.device);
+                }
+                Output::Offscreen(images) => {
+                    for image in images.drain(..) {
+                        image.destroy(&self.device, &mut self.allocator);
+                    }
+                }
             }
 
-            cleanup_swapchain(
-                &self.device,
-                self.command_pool,
-                &self.command_buffers,
-                &self.swapchain_framebuffers,
-                self.pipeline,
-                self.pipeline_layout,
-                self.render_pass,
-                &self.image_views,
-                &self.swapchain_loader,
-                self.swapchain,
-            );
+            for pipeline in &self.pipelines {
+                self.device.destroy_pipeline(*pipeline, None);
+            }
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
 
+            self.device
+                .free_command_buffers(self.command_pool, &self.command_buffers);
             self.device.destroy_command_pool(self.command_pool, None);
 
-            for shader_module in &self.shader_modules {
+            self.uniforms.destroy(&self.device, &mut self.allocator);
+            self.sprites.destroy(&self.device, &mut self.allocator);
+            self.text.destroy(&self.device, &mut self.allocator);
+            self.lines.destroy(&self.device, &mut self.allocator);
+            self.ui.destroy(&self.device, &mut self.allocator);
+            self.post.destroy(&self.device);
+
+            self.pipeline_cache.save(&self.device);
+            self.pipeline_cache.destroy(&self.device);
+
+            for shader_module in self.shader_modules.values() {
                 self.device.destroy_shader_module(shader_module.0, None); // vertex
                 self.device.destroy_shader_module(shader_module.1, None); // fragment
             }
 
-            self.surface_loader.destroy_surface(self.surface, None);
+            self.allocator.destroy();
 
             self.device.destroy_device(None);
 
@@ -635,224 +1469,133 @@ impl Drop for Renderer {
     }
 }
 
-unsafe fn cleanup_swapchain(
-    device: &Device,
-    command_pool: vk::CommandPool,
-    command_buffers: &[vk::CommandBuffer],
-    swapchain_framebuffers: &Vec<vk::Framebuffer>,
-    pipeline: vk::Pipeline,
-    pipeline_layout: vk::PipelineLayout,
-    render_pass: vk::RenderPass,
-    image_views: &Vec<vk::ImageView>,
-    swapchain_loader: &Swapchain,
-    swapchain: vk::SwapchainKHR,
-) {
-    device.free_command_buffers(command_pool, command_buffers);
-
-    for framebuffer in swapchain_framebuffers {
-        device.destroy_framebuffer(*framebuffer, None);
-    }
-
-    device.destroy_pipeline(pipeline, None);
-
-    device.destroy_pipeline_layout(pipeline_layout, None);
-
-    device.destroy_render_pass(render_pass, None);
-
-    for image_view in image_views {
-        device.destroy_image_view(*image_view, None);
-    }
-
-    swapchain_loader.destroy_swapchain(swapchain, None);
-}
-
 fn string_pointer(string: &str) -> *const i8 {
     CString::new(string).unwrap().into_raw() as *const i8
 }
 
-unsafe fn surface_information(
-    surface_loader: &Surface,
-    surface: vk::SurfaceKHR,
+unsafe fn supports_device_extensions(
+    instance: &Instance,
     physical_device: vk::PhysicalDevice,
-) -> VkResult<(
-    vk::SurfaceCapabilitiesKHR,
-    Vec<vk::PresentModeKHR>,
-    Vec<vk::SurfaceFormatKHR>,
-)> {
-    let surface_capabilites =
-        surface_loader.get_physical_device_surface_capabilities(physical_device, surface)?;
+) -> VkResult<bool> {
+    let device_extension_properties = instance
+        .enumerate_device_extension_properties(physical_device)?
+        .iter()
+        .map(|property| {
+            CStr::from_ptr(&property.extension_name as *const i8)
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect::<Vec<_>>();
 
-    let surface_present_modes =
-        surface_loader.get_physical_device_surface_present_modes(physical_device, surface)?;
+    Ok(DEVICE_EXTENSIONS
+        .iter()
+        .all(|extension| device_extension_properties.contains(&extension.to_string())))
+}
 
-    let surface_formats =
-        surface_loader.get_physical_device_surface_formats(physical_device, surface)?;
+// queue families (graphics, present) and surface formats if the device can be used,
+// without a surface any device with a graphics queue works
+unsafe fn device_candidate(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    surface: Option<&(Surface, vk::SurfaceKHR)>,
+) -> VkResult<Option<(u32, u32, Vec<vk::SurfaceFormatKHR>)>> {
+    let queue_family_properties =
+        instance.get_physical_device_queue_family_properties(physical_device);
 
-    Ok((surface_capabilites, surface_present_modes, surface_formats))
-}
+    let graphics_family_index = queue_family_properties
+        .iter()
+        .position(|properties| properties.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+        .map(|index| index as u32);
 
-fn choose_swap_surface_format(
-    available_formats: Vec<vk::SurfaceFormatKHR>,
-) -> vk::SurfaceFormatKHR {
-    if available_formats.len() == 1 && available_formats[0].format == vk::Format::UNDEFINED {
-        return vk::SurfaceFormatKHR::builder()
-            .format(vk::Format::B8G8R8A8_UNORM)
-            .color_space(vk::ColorSpaceKHR::SRGB_NONLINEAR)
-            .build();
-    }
+    let graphics_family_index = match graphics_family_index {
+        Some(graphics_family_index) => graphics_family_index,
+        None => return Ok(None),
+    };
 
-    for available_format in &available_formats {
-        if available_format.format == vk::Format::B8G8R8A8_UNORM
-            && available_format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
-        {
-            return available_format.clone();
+    let (surface_loader, surface) = match surface {
+        Some(surface) => surface,
+        None => {
+            return Ok(Some((
+                graphics_family_index,
+                graphics_family_index,
+                Vec::new(),
+            )))
         }
-    }
+    };
 
-    available_formats[0]
-}
+    if !supports_device_extensions(instance, physical_device)? {
+        return Ok(None);
+    };
 
-fn choose_swap_present_mode(
-    available_present_modes: Vec<vk::PresentModeKHR>,
-) -> vk::PresentModeKHR {
-    let mut best_mode = vk::PresentModeKHR::FIFO;
+    let (_, surface_present_modes, surface_formats) =
+        swapchain::surface_information(surface_loader, *surface, physical_device)?;
 
-    for available_present_mode in available_present_modes {
-        if available_present_mode == vk::PresentModeKHR::MAILBOX {
-            return available_present_mode;
-        } else if available_present_mode == vk::PresentModeKHR::IMMEDIATE {
-            best_mode = available_present_mode;
-        }
+    if surface_formats.is_empty() || surface_present_modes.is_empty() {
+        return Ok(None);
     }
 
-    best_mode
-}
+    let present_family_index = (0..queue_family_properties.len() as u32).find(|&index| {
+        surface_loader.get_physical_device_surface_support(physical_device, index, *surface)
+    });
 
-fn choose_swap_extent(
-    window_size: winit::dpi::LogicalSize,
-    capabilites: vk::SurfaceCapabilitiesKHR,
-) -> vk::Extent2D {
-    if capabilites.current_extent.width != u32::max_value() {
-        capabilites.current_extent
-    } else {
-        let (width, height) = window_size.into();
-
-        vk::Extent2D {
-            width: capabilites
-                .min_image_extent
-                .width
-                .max(capabilites.max_image_extent.width.min(width)),
-            height: capabilites
-                .min_image_extent
-                .height
-                .max(capabilites.max_image_extent.height.min(height)),
-        }
-    }
+    Ok(present_family_index
+        .map(|present_family_index| (graphics_family_index, present_family_index, surface_formats)))
 }
 
-unsafe fn create_swapchain(
-    surface_formats: Vec<vk::SurfaceFormatKHR>,
-    surface_present_modes: Vec<vk::PresentModeKHR>,
-    surface_capabilites: vk::SurfaceCapabilitiesKHR,
-    surface: vk::SurfaceKHR,
-    window: &Window,
-    graphics_family_index: u32,
-    present_family_index: u32,
-    device: &Device,
-    swapchain_loader: &Swapchain,
-    shader_modules: &Vec<(vk::ShaderModule, vk::ShaderModule)>,
-    command_pool: vk::CommandPool,
-) -> Result<
-    (
-        vk::SwapchainKHR,
-        Vec<vk::ImageView>,
-        vk::PipelineLayout,
-        vk::RenderPass,
-        vk::Pipeline,
-        Vec<vk::Framebuffer>,
-        Vec<vk::CommandBuffer>,
-    ),
-    RendererInitError,
-> {
-    let p_name = string_pointer("main");
-    let mut stage_create_infos = Vec::new();
-    for (vertex_shader_module, fragment_shader_module) in shader_modules {
-        let vertex_shader_stage_create_info = vk::PipelineShaderStageCreateInfo {
-            stage: vk::ShaderStageFlags::VERTEX,
-            module: *vertex_shader_module,
-            p_name,
-            ..Default::default()
-        };
-
-        stage_create_infos.push(vertex_shader_stage_create_info);
-
-        let fragment_shader_stage_create_info = vk::PipelineShaderStageCreateInfo {
-            stage: vk::ShaderStageFlags::FRAGMENT,
-            module: *fragment_shader_module,
-            p_name,
-            ..Default::default()
-        };
+// picks the pinned device if there is one, otherwise the best scoring one
+// candidates carry their enumeration index
+unsafe fn select_physical_device<T>(
+    instance: &Instance,
+    api_version: u32,
+    candidates: Vec<(usize, vk::PhysicalDevice, T)>,
+    pin: Option<&String>,
+) -> Result<(vk::PhysicalDevice, T), RendererInitError> {
+    let mut candidates = candidates
+        .into_iter()
+        .map(|(index, physical_device, candidate)| {
+            (
+                DeviceInfo::new(instance, physical_device, index, api_version),
+                (physical_device, candidate),
+            )
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|(info, _)| std::cmp::Reverse(info.score()));
 
-        stage_create_infos.push(fragment_shader_stage_create_info);
+    for (info, _) in &candidates {
+        info!("Suitable device: {}", info);
     }
 
-    let surface_format = choose_swap_surface_format(surface_formats);
-    let present_mode = choose_swap_present_mode(surface_present_modes);
-    let extent = choose_swap_extent(
-        window
-            .get_inner_size()
-            .ok_or(RendererInitError::SwapchainCreationError {
-                err: Either::Right("Failed to get window size".into()),
-            })?,
-        surface_capabilites,
-    );
-
-    let mut image_count = surface_capabilites.min_image_count + 1;
-    if surface_capabilites.max_image_count > 0 && image_count > surface_capabilites.max_image_count
-    {
-        image_count = surface_capabilites.max_image_count;
-    }
+    let pinned = pin.and_then(|pin| {
+        let position = candidates.iter().position(|(info, _)| info.matches(pin));
+        if position.is_none() {
+            warn!("No suitable device matches \"{}\"", pin);
+        }
+        position
+    });
 
-    let mut swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
-        .surface(surface)
-        .min_image_count(image_count)
-        .image_format(surface_format.format)
-        .image_color_space(surface_format.color_space)
-        .image_extent(extent)
-        .image_array_layers(1)
-        .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
-        .pre_transform(surface_capabilites.current_transform)
-        .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
-        .present_mode(present_mode)
-        .clipped(true);
-
-    let swapchain_queue_family_indices = [graphics_family_index, present_family_index];
-    if graphics_family_index != present_family_index {
-        swapchain_create_info = swapchain_create_info
-            .image_sharing_mode(vk::SharingMode::CONCURRENT)
-            .queue_family_indices(&swapchain_queue_family_indices)
-    } else {
-        swapchain_create_info = swapchain_create_info.image_sharing_mode(vk::SharingMode::EXCLUSIVE)
-    };
+    if candidates.is_empty() {
+        return Err(RendererInitError::PhysicalDeviceError {
+            err: Either::Right("Failed to find suitable device".into()),
+        });
+    }
 
-    let swapchain = swapchain_loader
-        .create_swapchain(&swapchain_create_info, None)
-        .map_err(|err| RendererInitError::SwapchainCreationError {
-            err: Either::Left(err),
-        })?;
+    let (info, candidate) = candidates.swap_remove(pinned.unwrap_or(0));
+    info!("Using device: {}", info);
 
-    let images = swapchain_loader
-        .get_swapchain_images(swapchain)
-        .map_err(|err| RendererInitError::SwapchainCreationError {
-            err: Either::Left(err),
-        })?;
+    Ok(candidate)
+}
 
-    let image_views = images
-        .into_iter()
-        .map(|image| {
+unsafe fn create_image_views(
+    device: &Device,
+    images: &[vk::Image],
+    format: vk::Format,
+) -> Result<Vec<vk::ImageView>, RendererInitError> {
+    images
+        .iter()
+        .map(|&image| {
             let view_create_info = vk::ImageViewCreateInfo::builder()
                 .view_type(vk::ImageViewType::TYPE_2D)
-                .format(surface_format.format)
+                .format(format)
                 .components(vk::ComponentMapping {
                     r: vk::ComponentSwizzle::IDENTITY,
                     g: vk::ComponentSwizzle::IDENTITY,
@@ -873,227 +1616,48 @@ unsafe fn create_swapchain(
         .collect::<VkResult<Vec<_>>>()
         .map_err(|err| RendererInitError::SwapchainCreationError {
             err: Either::Left(err),
-        })?;
-
-    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder();
+        })
+}
 
-    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::builder()
-        .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
-        .primitive_restart_enable(false);
+// converts the normalized camera viewport to pixels
+fn camera_viewport(viewport: &Viewport, extent: vk::Extent2D) -> (vk::Viewport, vk::Rect2D) {
+    let (width, height) = (extent.width as f32, extent.height as f32);
 
     let viewport = vk::Viewport::builder()
-        .x(0.0)
-        .y(0.0)
-        .width(extent.width as f32)
-        .height(extent.width as f32)
+        .x(viewport.x * width)
+        .y(viewport.y * height)
+        .width(viewport.width * width)
+        .height(viewport.height * height)
         .min_depth(0.0)
         .max_depth(1.0)
         .build();
 
     let scissor = vk::Rect2D::builder()
-        .offset(vk::Offset2D { x: 0, y: 0 })
-        .extent(extent)
-        .build();
-
-    let viewport_state_viewports = [viewport];
-    let viewport_state_scissors = [scissor];
-    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
-        .viewports(&viewport_state_viewports)
-        .scissors(&viewport_state_scissors);
-
-    let rasterizer = vk::PipelineRasterizationStateCreateInfo::builder()
-        .depth_clamp_enable(false)
-        .rasterizer_discard_enable(false)
-        .polygon_mode(vk::PolygonMode::FILL)
-        .line_width(1.0)
-        .cull_mode(vk::CullModeFlags::BACK)
-        .front_face(vk::FrontFace::CLOCKWISE)
-        .depth_bias_enable(false);
-
-    let multisampling = vk::PipelineMultisampleStateCreateInfo::builder()
-        .sample_shading_enable(false)
-        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
-
-    let color_blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
-        .color_write_mask(
-            vk::ColorComponentFlags::R
-                | vk::ColorComponentFlags::G
-                | vk::ColorComponentFlags::B
-                | vk::ColorComponentFlags::A,
-        )
-        .blend_enable(false)
+        .offset(vk::Offset2D {
+            x: viewport.x as i32,
+            y: viewport.y as i32,
+        })
+        .extent(vk::Extent2D {
+            width: viewport.width as u32,
+            height: viewport.height as u32,
+        })
         .build();
 
-    let color_blending_attachments = [color_blend_attachment];
-    let color_blending = vk::PipelineColorBlendStateCreateInfo::builder()
-        .logic_op_enable(false)
-        .attachments(&color_blending_attachments);
+    (viewport, scissor)
+}
 
-    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder();
+unsafe fn create_pipeline_layout(
+    device: &Device,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+) -> Result<vk::PipelineLayout, RendererInitError> {
+    let set_layouts = [descriptor_set_layout];
+    let push_constant_ranges = [PushConstants::range()];
+    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(&set_layouts)
+        .push_constant_ranges(&push_constant_ranges);
 
     let pipeline_layout = device
         .create_pipeline_layout(&pipeline_layout_create_info, None)
         .map_err(|err| RendererInitError::PipelineCreationError { err })?;
-
-    let color_attachment = vk::AttachmentDescription::builder()
-        .format(surface_format.format)
-        .samples(vk::SampleCountFlags::TYPE_1)
-        .load_op(vk::AttachmentLoadOp::CLEAR)
-        .store_op(vk::AttachmentStoreOp::STORE)
-        .initial_layout(vk::ImageLayout::UNDEFINED)
-        .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)
-        .build();
-
-    let color_attachment_ref = vk::AttachmentReference::builder()
-        .attachment(0)
-        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-        .build();
-
-    let subpass = vk::SubpassDescription::builder()
-        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-        .color_attachments(&[color_attachment_ref])
-        .build();
-
-    let dependencies = [vk::SubpassDependency {
-        src_subpass: vk::SUBPASS_EXTERNAL,
-        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-        dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
-            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-        dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-        ..Default::default()
-    }];
-
-    let render_pass_info_attachments = [color_attachment];
-    let render_pass_info_subpasses = [subpass];
-    let render_pass_info = vk::RenderPassCreateInfo::builder()
-        .attachments(&render_pass_info_attachments)
-        .dependencies(&dependencies)
-        .subpasses(&render_pass_info_subpasses);
-
-    let render_pass = device
-        .create_render_pass(&render_pass_info, None)
-        .map_err(|err| RendererInitError::PipelineCreationError { err })?;
-
-    let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
-        .stages(&stage_create_infos)
-        .vertex_input_state(&vertex_input_info)
-        .input_assembly_state(&input_assembly)
-        .viewport_state(&viewport_state)
-        .rasterization_state(&rasterizer)
-        .multisample_state(&multisampling)
-        .color_blend_state(&color_blending)
-        .layout(pipeline_layout)
-        .render_pass(render_pass)
-        .subpass(0)
-        .build();
-
-    // just create one pipeline for now because we only have one shader
-    let pipeline = device
-        .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_create_info], None)
-        .map_err(|err| RendererInitError::PipelineCreationError { err: err.1 })?[0];
-
-    let swapchain_framebuffers = image_views
-        .iter()
-        .map(|&image_view| {
-            let attachments = [image_view];
-
-            let framebuffer_create_info = vk::FramebufferCreateInfo::builder()
-                .render_pass(render_pass)
-                .attachments(&attachments)
-                .width(extent.width)
-                .height(extent.height)
-                .layers(1);
-
-            device
-                .create_framebuffer(&framebuffer_create_info, None)
-                .map_err(|err| RendererInitError::FramebufferCreationError { err })
-        })
-        .collect::<Result<Vec<_>, _>>()?;
-
-    let command_buffer_alloc_info = vk::CommandBufferAllocateInfo::builder()
-        .command_pool(command_pool)
-        .level(vk::CommandBufferLevel::PRIMARY)
-        .command_buffer_count(swapchain_framebuffers.len() as u32);
-
-    let command_buffers = device
-        .allocate_command_buffers(&command_buffer_alloc_info)
-        .map_err(|err| RendererInitError::CommandBufferError { err })?;
-
-    for (i, &command_buffer) in command_buffers.iter().enumerate() {
-        let begin_info = vk::CommandBufferBeginInfo::builder()
-            .flags(vk::CommandBufferUsageFlags::SIMULTANEOUS_USE);
-
-        device
-            .begin_command_buffer(command_buffer, &begin_info)
-            .map_err(|err| RendererInitError::CommandBufferError { err })?;
-
-        let clear_values = [vk::ClearValue {
-            color: vk::ClearColorValue {
-                float32: [0.0, 0.0, 0.0, 1.0],
-            },
-        }];
-
-        let render_pass_info = vk::RenderPassBeginInfo::builder()
-            .render_pass(render_pass)
-            .framebuffer(swapchain_framebuffers[i])
-            .render_area(vk::Rect2D {
-                offset: vk::Offset2D { x: 0, y: 0 },
-                extent: extent,
-            })
-            .clear_values(&clear_values);
-
-        device.cmd_begin_render_pass(
-            command_buffer,
-            &render_pass_info,
-            vk::SubpassContents::INLINE,
-        );
-
-        device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
-
-        device.cmd_draw(command_buffer, 3, 1, 0, 0);
-
-        device.cmd_end_render_pass(command_buffer);
-
-        device
-            .end_command_buffer(command_buffer)
-            .map_err(|err| RendererInitError::CommandBufferError { err })?;
-    }
-
-    Ok((
-        swapchain,
-        image_views,
-        pipeline_layout,
-        render_pass,
-        pipeline,
-        swapchain_framebuffers,
-        command_buffers,
-    ))
-}
-
-unsafe extern "system" fn vulkan_debug_callback(
-    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
-    _message_type: vk::DebugUtilsMessageTypeFlagsEXT,
-    callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _user_data: *mut c_void,
-) -> u32 {
-    let message = CStr::from_ptr((*callback_data).p_message).to_string_lossy();
-    let message = message.trim();
-
-    match message_severity {
-        vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE => {
-            info!("Vulkan: {}", message);
-        }
-        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => {
-            warn!("Vulkan: {}", message);
-        }
-        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => {
-            error!("Vulkan: {}", message);
-        }
-        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => {
-            info!("Vulkan: {}", message);
-        }
-        _ => (),
-    };
-
-    vk::FALSE
+    Ok(pipeline_layout)
 }
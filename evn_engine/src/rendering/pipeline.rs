@@ -0,0 +1,304 @@
+// Graphics pipeline descriptions
+//
+// Every registered shader gets a pipeline with the default state under its own name,
+// additional pipelines can be registered with `ResourceBuilder::with_pipeline`.
+
+use super::RendererInitError;
+use ash::{version::DeviceV1_0, vk, Device};
+use either::Either;
+use fnv::FnvHashMap;
+use log::warn;
+use std::{ffi::CString, io::Cursor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    TriangleList,
+    TriangleStrip,
+    LineList,
+    LineStrip,
+    PointList,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CullMode {
+    None,
+    Front,
+    Back,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Opaque,
+    Alpha,
+    // the color is already multiplied with its alpha
+    Premultiplied,
+    Additive,
+}
+
+// Line and Point need the fillModeNonSolid device feature and fall back to Fill without it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolygonMode {
+    Fill,
+    Line,
+    Point,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipelineDesc {
+    // name of the shader resource
+    pub shader: String,
+    pub topology: Topology,
+    pub cull_mode: CullMode,
+    pub blend_mode: BlendMode,
+    // only opaque pipelines write depth
+    pub depth_test: bool,
+    pub polygon_mode: PolygonMode,
+}
+
+impl PipelineDesc {
+    pub fn new(shader: impl AsRef<str>) -> Self {
+        PipelineDesc {
+            shader: shader.as_ref().to_owned(),
+            topology: Topology::TriangleList,
+            cull_mode: CullMode::Back,
+            blend_mode: BlendMode::Opaque,
+            depth_test: true,
+            polygon_mode: PolygonMode::Fill,
+        }
+    }
+}
+
+impl Topology {
+    fn to_vk(self) -> vk::PrimitiveTopology {
+        match self {
+            Topology::TriangleList => vk::PrimitiveTopology::TRIANGLE_LIST,
+            Topology::TriangleStrip => vk::PrimitiveTopology::TRIANGLE_STRIP,
+            Topology::LineList => vk::PrimitiveTopology::LINE_LIST,
+            Topology::LineStrip => vk::PrimitiveTopology::LINE_STRIP,
+            Topology::PointList => vk::PrimitiveTopology::POINT_LIST,
+        }
+    }
+}
+
+impl CullMode {
+    fn to_vk(self) -> vk::CullModeFlags {
+        match self {
+            CullMode::None => vk::CullModeFlags::NONE,
+            CullMode::Front => vk::CullModeFlags::FRONT,
+            CullMode::Back => vk::CullModeFlags::BACK,
+        }
+    }
+}
+
+impl PolygonMode {
+    fn to_vk(self) -> vk::PolygonMode {
+        match self {
+            PolygonMode::Fill => vk::PolygonMode::FILL,
+            PolygonMode::Line => vk::PolygonMode::LINE,
+            PolygonMode::Point => vk::PolygonMode::POINT,
+        }
+    }
+}
+
+impl BlendMode {
+    pub fn attachment_state(self) -> vk::PipelineColorBlendAttachmentState {
+        let builder = vk::PipelineColorBlendAttachmentState::builder().color_write_mask(
+            vk::ColorComponentFlags::R
+                | vk::ColorComponentFlags::G
+                | vk::ColorComponentFlags::B
+                | vk::ColorComponentFlags::A,
+        );
+
+        let (src_color, dst_color) = match self {
+            BlendMode::Opaque => return builder.blend_enable(false).build(),
+            BlendMode::Alpha => (
+                vk::BlendFactor::SRC_ALPHA,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            ),
+            BlendMode::Premultiplied => {
+                (vk::BlendFactor::ONE, vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            }
+            BlendMode::Additive => (vk::BlendFactor::SRC_ALPHA, vk::BlendFactor::ONE),
+        };
+
+        builder
+            .blend_enable(true)
+            .src_color_blend_factor(src_color)
+            .dst_color_blend_factor(dst_color)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .build()
+    }
+}
+
+// state shared by all pipelines of a render pass
+pub struct PipelineTarget {
+    pub layout: vk::PipelineLayout,
+    pub render_pass: vk::RenderPass,
+    pub samples: vk::SampleCountFlags,
+    pub depth: bool,
+    pub non_solid_fill: bool,
+}
+
+// creates the pipelines in the order of `descs`
+pub unsafe fn create_pipelines(
+    device: &Device,
+    descs: &[(String, PipelineDesc)],
+    shader_modules: &FnvHashMap<String, (vk::ShaderModule, vk::ShaderModule)>,
+    cache: vk::PipelineCache,
+    target: &PipelineTarget,
+) -> Result<Vec<vk::Pipeline>, RendererInitError> {
+    let entry_name = CString::new("main").unwrap();
+
+    // everything referenced by the create infos has to outlive the create call
+    let stages = descs
+        .iter()
+        .map(|(name, desc)| {
+            let (vertex_shader_module, fragment_shader_module) =
+                shader_modules.get(&desc.shader).ok_or_else(|| {
+                    RendererInitError::ShaderLoadingError {
+                        name: desc.shader.clone(),
+                        err: Either::Right(format!("Unknown shader in pipeline {}", name)),
+                    }
+                })?;
+
+            Ok([
+                vk::PipelineShaderStageCreateInfo::builder()
+                    .stage(vk::ShaderStageFlags::VERTEX)
+                    .module(*vertex_shader_module)
+                    .name(&entry_name)
+                    .build(),
+                vk::PipelineShaderStageCreateInfo::builder()
+                    .stage(vk::ShaderStageFlags::FRAGMENT)
+                    .module(*fragment_shader_module)
+                    .name(&entry_name)
+                    .build(),
+            ])
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let input_assemblies = descs
+        .iter()
+        .map(|(_, desc)| {
+            vk::PipelineInputAssemblyStateCreateInfo::builder()
+                .topology(desc.topology.to_vk())
+                .primitive_restart_enable(false)
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    let rasterizers = descs
+        .iter()
+        .map(|(name, desc)| {
+            let polygon_mode = if desc.polygon_mode != PolygonMode::Fill && !target.non_solid_fill {
+                warn!(
+                    "Pipeline \"{}\": {:?} polygon mode not supported, using Fill",
+                    name, desc.polygon_mode
+                );
+                vk::PolygonMode::FILL
+            } else {
+                desc.polygon_mode.to_vk()
+            };
+
+            vk::PipelineRasterizationStateCreateInfo::builder()
+                .depth_clamp_enable(false)
+                .rasterizer_discard_enable(false)
+                .polygon_mode(polygon_mode)
+                .line_width(1.0)
+                .cull_mode(desc.cull_mode.to_vk())
+                .front_face(vk::FrontFace::CLOCKWISE)
+                .depth_bias_enable(false)
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    let depth_stencils = descs
+        .iter()
+        .map(|(_, desc)| {
+            let depth_test = target.depth && desc.depth_test;
+
+            vk::PipelineDepthStencilStateCreateInfo::builder()
+                .depth_test_enable(depth_test)
+                .depth_write_enable(depth_test && desc.blend_mode == BlendMode::Opaque)
+                .depth_compare_op(vk::CompareOp::LESS)
+                .depth_bounds_test_enable(false)
+                .stencil_test_enable(false)
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    let blend_attachments = descs
+        .iter()
+        .map(|(_, desc)| [desc.blend_mode.attachment_state()])
+        .collect::<Vec<_>>();
+
+    let color_blendings = blend_attachments
+        .iter()
+        .map(|attachments| {
+            vk::PipelineColorBlendStateCreateInfo::builder()
+                .logic_op_enable(false)
+                .attachments(attachments)
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder();
+
+    // viewport and scissor are set from the camera while recording
+    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissor_count(1);
+
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+
+    let multisampling = vk::PipelineMultisampleStateCreateInfo::builder()
+        .sample_shading_enable(false)
+        .rasterization_samples(target.samples);
+
+    let pipeline_create_infos = (0..descs.len())
+        .map(|i| {
+            vk::GraphicsPipelineCreateInfo::builder()
+                .stages(&stages[i])
+                .vertex_input_state(&vertex_input_info)
+                .input_assembly_state(&input_assemblies[i])
+                .viewport_state(&viewport_state)
+                .rasterization_state(&rasterizers[i])
+                .multisample_state(&multisampling)
+                .depth_stencil_state(&depth_stencils[i])
+                .color_blend_state(&color_blendings[i])
+                .dynamic_state(&dynamic_state)
+                .layout(target.layout)
+                .render_pass(target.render_pass)
+                .subpass(0)
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    device
+        .create_graphics_pipelines(cache, &pipeline_create_infos, None)
+        .map_err(|err| RendererInitError::PipelineCreationError { err: err.1 })
+}
+
+// for shaders embedded into the engine, `name` is only used for errors
+pub unsafe fn create_shader_module(
+    device: &Device,
+    name: &str,
+    code: &[u8],
+) -> Result<vk::ShaderModule, RendererInitError> {
+    let shader_error = |err| RendererInitError::ShaderLoadingError {
+        name: name.to_owned(),
+        err,
+    };
+
+    let code = ash::util::read_spv(&mut Cursor::new(code))
+        .map_err(|err| shader_error(Either::Right(err.to_string())))?;
+    let create_info = vk::ShaderModuleCreateInfo::builder().code(&code);
+
+    device
+        .create_shader_module(&create_info, None)
+        .map_err(|err| shader_error(Either::Left(err)))
+}
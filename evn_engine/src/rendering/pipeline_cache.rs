@@ -0,0 +1,141 @@
+// Pipeline cache persisted between runs
+//
+// The file starts with our own header identifying the device and driver the data
+// was created with, followed by the data returned by vkGetPipelineCacheData.
+// A cache from a different device or driver is discarded instead of handed to the driver.
+
+use super::RendererInitError;
+use ash::{version::DeviceV1_0, vk, Device};
+use log::{info, warn};
+use std::{fs, path::PathBuf};
+
+const MAGIC: [u8; 4] = *b"EVPC";
+const HEADER_SIZE: usize = 4 + 4 * 3 + vk::UUID_SIZE;
+
+#[derive(Debug, PartialEq)]
+struct CacheHeader {
+    vendor_id: u32,
+    device_id: u32,
+    driver_version: u32,
+    uuid: [u8; vk::UUID_SIZE],
+}
+
+impl CacheHeader {
+    fn new(properties: &vk::PhysicalDeviceProperties) -> Self {
+        CacheHeader {
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            driver_version: properties.driver_version,
+            uuid: properties.pipeline_cache_uuid,
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&self.vendor_id.to_le_bytes());
+        bytes.extend_from_slice(&self.device_id.to_le_bytes());
+        bytes.extend_from_slice(&self.driver_version.to_le_bytes());
+        bytes.extend_from_slice(&self.uuid);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_SIZE || bytes[0..4] != MAGIC {
+            return None;
+        }
+
+        let read_u32 = |offset: usize| {
+            let mut word = [0; 4];
+            word.copy_from_slice(&bytes[offset..offset + 4]);
+            u32::from_le_bytes(word)
+        };
+
+        let mut uuid = [0; vk::UUID_SIZE];
+        uuid.copy_from_slice(&bytes[16..HEADER_SIZE]);
+
+        Some(CacheHeader {
+            vendor_id: read_u32(4),
+            device_id: read_u32(8),
+            driver_version: read_u32(12),
+            uuid,
+        })
+    }
+}
+
+pub struct PipelineCache {
+    cache: vk::PipelineCache,
+    header: CacheHeader,
+    path: PathBuf,
+}
+
+impl PipelineCache {
+    // a missing or invalid cache file results in an empty cache
+    pub unsafe fn new(
+        device: &Device,
+        properties: &vk::PhysicalDeviceProperties,
+        path: PathBuf,
+    ) -> Result<Self, RendererInitError> {
+        let header = CacheHeader::new(properties);
+
+        let initial_data = match fs::read(&path) {
+            Ok(bytes) => match CacheHeader::from_bytes(&bytes) {
+                Some(ref file_header) if *file_header == header => {
+                    info!("Loaded pipeline cache from {}", path.display());
+                    bytes[HEADER_SIZE..].to_vec()
+                }
+                Some(_) => {
+                    info!("Pipeline cache was created by another device or driver, discarding it");
+                    Vec::new()
+                }
+                None => {
+                    warn!("Invalid pipeline cache file {}", path.display());
+                    Vec::new()
+                }
+            },
+            Err(_) => Vec::new(),
+        };
+
+        let create_info = vk::PipelineCacheCreateInfo::builder().initial_data(&initial_data);
+        let cache = device
+            .create_pipeline_cache(&create_info, None)
+            .map_err(|err| RendererInitError::PipelineCacheError { err })?;
+
+        Ok(PipelineCache {
+            cache,
+            header,
+            path,
+        })
+    }
+
+    pub fn handle(&self) -> vk::PipelineCache {
+        self.cache
+    }
+
+    // failing to save only costs the next startup some time, so it's just logged
+    pub unsafe fn save(&self, device: &Device) {
+        let data = match device.get_pipeline_cache_data(self.cache) {
+            Ok(data) => data,
+            Err(err) => {
+                warn!("Failed to get pipeline cache data: {}", err);
+                return;
+            }
+        };
+
+        let mut bytes = self.header.to_bytes();
+        bytes.extend_from_slice(&data);
+
+        match fs::write(&self.path, &bytes) {
+            Ok(()) => info!("Saved pipeline cache to {}", self.path.display()),
+            Err(err) => warn!(
+                "Failed to save pipeline cache to {}: {}",
+                self.path.display(),
+                err
+            ),
+        }
+    }
+
+    pub unsafe fn destroy(&self, device: &Device) {
+        device.destroy_pipeline_cache(self.cache, None);
+    }
+}
@@ -1,25 +1,59 @@
 use ash::{
-    extensions::{ext::DebugReport, khr::Surface},
     version::{EntryV1_0, InstanceV1_0},
     vk,
 };
+use std::ffi::{CStr, CString};
+
+// the instance may have been created without the extension the window needs
+fn require_extension(extensions: &[CString], name: &CStr) -> Result<(), vk::Result> {
+    if extensions
+        .iter()
+        .any(|extension| extension.as_c_str() == name)
+    {
+        Ok(())
+    } else {
+        Err(vk::Result::ERROR_EXTENSION_NOT_PRESENT)
+    }
+}
 
 // -----
 // LINUX
 // -----
 #[cfg(target_os = "linux")]
-use ash::extensions::khr::XlibSurface;
+use ash::extensions::khr::{WaylandSurface, XlibSurface};
 
+// winit picks Wayland or X11 at runtime, the surface has to match the window,
+// `extensions` are the ones the instance was created with
 #[cfg(target_os = "linux")]
 pub unsafe fn create_surface<E: EntryV1_0, I: InstanceV1_0>(
     entry: &E,
     instance: &I,
+    extensions: &[CString],
     window: &winit::Window,
 ) -> Result<vk::SurfaceKHR, vk::Result> {
     use winit::os::unix::WindowExt;
 
-    let x11_display = window.get_xlib_display().unwrap();
-    let x11_window = window.get_xlib_window().unwrap();
+    if let (Some(wayland_display), Some(wayland_surface)) =
+        (window.get_wayland_display(), window.get_wayland_surface())
+    {
+        require_extension(extensions, WaylandSurface::name())?;
+
+        let wayland_create_info = vk::WaylandSurfaceCreateInfoKHR::builder()
+            .display(wayland_display as *mut vk::wl_display)
+            .surface(wayland_surface as *mut vk::wl_surface);
+
+        let wayland_surface_loader = WaylandSurface::new(entry, instance);
+        return wayland_surface_loader.create_wayland_surface(&wayland_create_info, None);
+    }
+
+    require_extension(extensions, XlibSurface::name())?;
+
+    let x11_display = window
+        .get_xlib_display()
+        .ok_or(vk::Result::ERROR_INITIALIZATION_FAILED)?;
+    let x11_window = window
+        .get_xlib_window()
+        .ok_or(vk::Result::ERROR_INITIALIZATION_FAILED)?;
     let x11_create_info = vk::XlibSurfaceCreateInfoKHR::builder()
         .window(x11_window)
         .dpy(x11_display as *mut vk::Display);
@@ -28,13 +62,10 @@ pub unsafe fn create_surface<E: EntryV1_0, I: InstanceV1_0>(
     xlib_surface_loader.create_xlib_surface(&x11_create_info, None)
 }
 
+// the window type is only known later, so every one the loader supports is enabled
 #[cfg(target_os = "linux")]
-pub fn extension_names() -> Vec<*const i8> {
-    vec![
-        Surface::name().as_ptr(),
-        XlibSurface::name().as_ptr(),
-        DebugReport::name().as_ptr(),
-    ]
+pub fn surface_extension_names() -> Vec<&'static CStr> {
+    vec![XlibSurface::name(), WaylandSurface::name()]
 }
 
 // -------
@@ -47,12 +78,15 @@ use ash::extensions::khr::Win32Surface;
 pub unsafe fn create_surface<E: EntryV1_0, I: InstanceV1_0>(
     entry: &E,
     instance: &I,
+    extensions: &[CString],
     window: &winit::Window,
 ) -> Result<vk::SurfaceKHR, vk::Result> {
     use std::{os::raw::c_void, ptr};
     use winapi::{shared::windef::HWND, um::libloaderapi::GetModuleHandleW};
     use winit::os::windows::WindowExt;
 
+    require_extension(extensions, Win32Surface::name())?;
+
     let hwnd = window.get_hwnd() as HWND;
     let hinstance = GetModuleHandleW(ptr::null()) as *const c_void;
     let win32_create_info = vk::Win32SurfaceCreateInfoKHR::builder()
@@ -64,10 +98,6 @@ pub unsafe fn create_surface<E: EntryV1_0, I: InstanceV1_0>(
 }
 
 #[cfg(target_os = "windows")]
-pub fn extension_names() -> Vec<*const i8> {
-    vec![
-        Surface::name().as_ptr(),
-        Win32Surface::name().as_ptr(),
-        DebugReport::name().as_ptr(),
-    ]
+pub fn surface_extension_names() -> Vec<&'static CStr> {
+    vec![Win32Surface::name()]
 }
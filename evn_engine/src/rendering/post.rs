@@ -0,0 +1,497 @@
+// Post-processing chain
+//
+// Full screen passes configured in `graphics.post`, between the HDR scene and the output.
+// Each pass draws one triangle with its shader resource, samples up to `MAX_INPUTS` earlier
+// passes, the scene or textures and gets its parameters as push constants. Every pass but
+// the last renders into its own RGBA16F image, the render graph orders them and inserts the
+// barriers. The descriptor sets are written again when the graph recreates its images.
+
+use super::{
+    debug::DebugNames,
+    graph::{ImageDesc, ImageId, PassDesc, PassId, RenderGraph, RenderGraphBuilder},
+    pipeline::{BlendMode, PipelineTarget},
+    settings::{PostPassSettings, SCENE_INPUT},
+    sprites::SpritePass,
+    RendererInitError,
+};
+use ash::{version::DeviceV1_0, vk, Device};
+use fnv::FnvHashMap;
+use std::{ffi::CString, io::Cursor, mem, slice};
+
+const VERTEX_SHADER: &[u8] = include_bytes!("../../../resources/closed/shaders/post.vert.spv");
+
+pub const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+// sampled images per pass, bindings 1 to 4 next to the sampler at binding 0
+const MAX_INPUTS: usize = 4;
+const MAX_PARAMS: usize = 8;
+
+#[derive(Debug, Clone)]
+enum PostInput {
+    Image(ImageId),
+    // a texture resource, looked up once the sprite pass loaded them
+    Texture(String),
+}
+
+// vec4 params[2], vec2 texelSize
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+struct PostConstants {
+    params: [f32; MAX_PARAMS],
+    texel_size: [f32; 2],
+}
+
+impl PostConstants {
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self as *const Self as *const u8, mem::size_of::<Self>()) }
+    }
+}
+
+// shared by every post shader resource
+pub fn vertex_shader() -> Vec<u32> {
+    ash::util::read_spv(&mut Cursor::new(VERTEX_SHADER)).expect("Invalid post vertex shader")
+}
+
+// a pass declared in the graph, its pipeline is created once the graph is built
+pub struct PostStep {
+    name: String,
+    shader: String,
+    pass: PassId,
+    inputs: Vec<PostInput>,
+    params: [f32; MAX_PARAMS],
+    pipeline: vk::Pipeline,
+    descriptor_set: vk::DescriptorSet,
+}
+
+// adds the passes of the chain to the graph, the last one renders into the output
+pub fn declare_chain(
+    builder: &mut RenderGraphBuilder,
+    settings: &[PostPassSettings],
+    scene: ImageId,
+) -> Result<Vec<PostStep>, RendererInitError> {
+    let chain_error = |err| RendererInitError::PostChainError { err };
+
+    let mut images = FnvHashMap::default();
+    images.insert(SCENE_INPUT.to_owned(), scene);
+
+    let mut steps = Vec::with_capacity(settings.len());
+    for (index, pass) in settings.iter().enumerate() {
+        if images.contains_key(&pass.name) {
+            return Err(chain_error(format!(
+                "there's more than one pass called \"{}\"",
+                pass.name
+            )));
+        }
+        if pass.inputs.is_empty() || pass.inputs.len() > MAX_INPUTS {
+            return Err(chain_error(format!(
+                "pass \"{}\" needs between 1 and {} inputs",
+                pass.name, MAX_INPUTS
+            )));
+        }
+        if pass.params.len() > MAX_PARAMS {
+            return Err(chain_error(format!(
+                "pass \"{}\" has more than {} params",
+                pass.name, MAX_PARAMS
+            )));
+        }
+
+        let inputs = pass
+            .inputs
+            .iter()
+            .map(|input| match images.get(input) {
+                Some(image) => PostInput::Image(*image),
+                None => PostInput::Texture(input.clone()),
+            })
+            .collect::<Vec<_>>();
+
+        let target = if index == settings.len() - 1 {
+            builder.output()
+        } else {
+            let image = builder.image(&pass.name, ImageDesc::new(HDR_FORMAT).scale(pass.scale));
+            images.insert(pass.name.clone(), image);
+            image
+        };
+
+        // every pixel is overwritten, nothing has to be cleared
+        let mut desc = PassDesc::new(&pass.name).color(target, None);
+        for input in &inputs {
+            if let PostInput::Image(image) = input {
+                desc = desc.sample(*image);
+            }
+        }
+
+        let mut params = [0.0; MAX_PARAMS];
+        params[..pass.params.len()].copy_from_slice(&pass.params);
+
+        steps.push(PostStep {
+            name: pass.name.clone(),
+            shader: pass.shader.clone(),
+            pass: builder.pass(desc),
+            inputs,
+            params,
+            pipeline: vk::Pipeline::null(),
+            descriptor_set: vk::DescriptorSet::null(),
+        });
+    }
+
+    Ok(steps)
+}
+
+pub struct PostChain {
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    pipeline_layout: vk::PipelineLayout,
+    // the sprite sampler, linear and clamped
+    sampler: vk::Sampler,
+    steps: Vec<PostStep>,
+    // views of the texture inputs
+    textures: FnvHashMap<String, vk::ImageView>,
+}
+
+impl PostChain {
+    // `shader_modules` are the modules of the shader resources, usually from `with_post_shader`
+    pub unsafe fn new(
+        device: &Device,
+        graph: &RenderGraph,
+        steps: Vec<PostStep>,
+        shader_modules: &FnvHashMap<String, (vk::ShaderModule, vk::ShaderModule)>,
+        sprites: &SpritePass,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<Self, RendererInitError> {
+        let descriptor_error = |err| RendererInitError::DescriptorCreationError { err };
+
+        // layout(binding = 0) uniform sampler, layout(binding = 1..=4) uniform texture2D
+        let mut bindings = vec![vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build()];
+        bindings.extend((1..=MAX_INPUTS as u32).map(|binding| {
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build()
+        }));
+        let descriptor_set_layout_create_info =
+            vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+        let descriptor_set_layout = device
+            .create_descriptor_set_layout(&descriptor_set_layout_create_info, None)
+            .map_err(descriptor_error)?;
+
+        // at least one set, the pool can't be empty
+        let sets = steps.len().max(1) as u32;
+        let pool_sizes = [
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::SAMPLER,
+                descriptor_count: sets,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::SAMPLED_IMAGE,
+                descriptor_count: sets * MAX_INPUTS as u32,
+            },
+        ];
+        let descriptor_pool_create_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&pool_sizes)
+            .max_sets(sets);
+        let descriptor_pool = device
+            .create_descriptor_pool(&descriptor_pool_create_info, None)
+            .map_err(descriptor_error)?;
+
+        let set_layouts = [descriptor_set_layout];
+        let push_constant_ranges = [vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: mem::size_of::<PostConstants>() as u32,
+        }];
+        let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(&push_constant_ranges);
+        let pipeline_layout = device
+            .create_pipeline_layout(&pipeline_layout_create_info, None)
+            .map_err(|err| RendererInitError::PipelineCreationError { err })?;
+
+        let mut chain = PostChain {
+            descriptor_set_layout,
+            descriptor_pool,
+            pipeline_layout,
+            sampler: sprites.sampler(),
+            steps,
+            textures: FnvHashMap::default(),
+        };
+
+        for index in 0..chain.steps.len() {
+            let step = &chain.steps[index];
+            for input in &step.inputs {
+                if let PostInput::Texture(name) = input {
+                    match sprites.texture_view(name) {
+                        Some(view) => {
+                            chain.textures.insert(name.clone(), view);
+                        }
+                        None => {
+                            let err = format!(
+                                "pass \"{}\" has the unknown input \"{}\"",
+                                step.name, name
+                            );
+                            chain.destroy(device);
+                            return Err(RendererInitError::PostChainError { err });
+                        }
+                    }
+                }
+            }
+
+            let modules = match shader_modules.get(&step.shader) {
+                Some(modules) => *modules,
+                None => {
+                    let err = format!(
+                        "pass \"{}\" uses the unknown shader \"{}\"",
+                        step.name, step.shader
+                    );
+                    chain.destroy(device);
+                    return Err(RendererInitError::PostChainError { err });
+                }
+            };
+
+            let target = PipelineTarget {
+                layout: chain.pipeline_layout,
+                render_pass: graph.render_pass(step.pass),
+                samples: vk::SampleCountFlags::TYPE_1,
+                depth: false,
+                non_solid_fill: false,
+            };
+            match create_pipeline(device, &target, modules, pipeline_cache) {
+                Ok(pipeline) => chain.steps[index].pipeline = pipeline,
+                Err(err) => {
+                    chain.destroy(device);
+                    return Err(err);
+                }
+            }
+        }
+
+        let set_layouts = vec![descriptor_set_layout; chain.steps.len()];
+        if !set_layouts.is_empty() {
+            let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&set_layouts);
+            let descriptor_sets =
+                match device.allocate_descriptor_sets(&descriptor_set_allocate_info) {
+                    Ok(descriptor_sets) => descriptor_sets,
+                    Err(err) => {
+                        chain.destroy(device);
+                        return Err(descriptor_error(err));
+                    }
+                };
+            for (step, descriptor_set) in chain.steps.iter_mut().zip(descriptor_sets) {
+                step.descriptor_set = descriptor_set;
+            }
+        }
+        chain.write_sets(device, graph);
+
+        Ok(chain)
+    }
+
+    // the views of the graph images change when they're recreated
+    pub unsafe fn write_sets(&self, device: &Device, graph: &RenderGraph) {
+        for step in &self.steps {
+            let mut views = step
+                .inputs
+                .iter()
+                .map(|input| match input {
+                    PostInput::Image(image) => graph.view(*image),
+                    PostInput::Texture(name) => self.textures[name],
+                })
+                .collect::<Vec<_>>();
+            // unused bindings still need a valid image
+            views.resize(MAX_INPUTS, views[0]);
+
+            let sampler_info = [vk::DescriptorImageInfo {
+                sampler: self.sampler,
+                image_view: vk::ImageView::null(),
+                image_layout: vk::ImageLayout::UNDEFINED,
+            }];
+            let image_infos = views
+                .iter()
+                .map(|&view| vk::DescriptorImageInfo {
+                    sampler: vk::Sampler::null(),
+                    image_view: view,
+                    image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                })
+                .collect::<Vec<_>>();
+
+            let mut descriptor_writes = vec![vk::WriteDescriptorSet::builder()
+                .dst_set(step.descriptor_set)
+                .dst_binding(0)
+                .descriptor_type(vk::DescriptorType::SAMPLER)
+                .image_info(&sampler_info)
+                .build()];
+            descriptor_writes.extend(image_infos.iter().enumerate().map(|(index, info)| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(step.descriptor_set)
+                    .dst_binding(index as u32 + 1)
+                    .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                    .image_info(slice::from_ref(info))
+                    .build()
+            }));
+            device.update_descriptor_sets(&descriptor_writes, &[]);
+        }
+    }
+
+    // false if `pass` isn't part of the chain
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        graph: &RenderGraph,
+        pass: PassId,
+    ) -> bool {
+        let step = match self.steps.iter().find(|step| step.pass == pass) {
+            Some(step) => step,
+            None => return false,
+        };
+
+        let extent = graph.pass_extent(pass);
+        let viewport = vk::Viewport::builder()
+            .width(extent.width as f32)
+            .height(extent.height as f32)
+            .max_depth(1.0)
+            .build();
+        let scissor = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+        device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            step.pipeline,
+        );
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline_layout,
+            0,
+            &[step.descriptor_set],
+            &[],
+        );
+
+        let constants = PostConstants {
+            params: step.params,
+            texel_size: [1.0 / extent.width as f32, 1.0 / extent.height as f32],
+        };
+        device.cmd_push_constants(
+            command_buffer,
+            self.pipeline_layout,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            constants.as_bytes(),
+        );
+        device.cmd_draw(command_buffer, 3, 1, 0, 0);
+
+        true
+    }
+
+    pub unsafe fn name(&self, debug_names: &DebugNames) {
+        debug_names.name(self.descriptor_set_layout, "post input layout");
+        debug_names.name(self.descriptor_pool, "post input pool");
+        debug_names.name(self.pipeline_layout, "post pipeline layout");
+        for step in &self.steps {
+            debug_names.name(step.pipeline, &format!("{} pipeline", step.name));
+            debug_names.name(step.descriptor_set, &format!("{} inputs", step.name));
+        }
+    }
+
+    // the sampler belongs to the sprite pass
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        for step in &self.steps {
+            if step.pipeline != vk::Pipeline::null() {
+                device.destroy_pipeline(step.pipeline, None);
+            }
+        }
+        device.destroy_pipeline_layout(self.pipeline_layout, None);
+        device.destroy_descriptor_pool(self.descriptor_pool, None);
+        device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+    }
+}
+
+// a full screen triangle without vertex buffers or depth
+unsafe fn create_pipeline(
+    device: &Device,
+    target: &PipelineTarget,
+    (vertex_shader_module, fragment_shader_module): (vk::ShaderModule, vk::ShaderModule),
+    pipeline_cache: vk::PipelineCache,
+) -> Result<vk::Pipeline, RendererInitError> {
+    let entry_name = CString::new("main").unwrap();
+    let stages = [
+        vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(vertex_shader_module)
+            .name(&entry_name)
+            .build(),
+        vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(fragment_shader_module)
+            .name(&entry_name)
+            .build(),
+    ];
+
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder();
+
+    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+        .primitive_restart_enable(false);
+
+    let rasterizer = vk::PipelineRasterizationStateCreateInfo::builder()
+        .depth_clamp_enable(false)
+        .rasterizer_discard_enable(false)
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.0)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::CLOCKWISE)
+        .depth_bias_enable(false);
+
+    let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(target.depth)
+        .depth_write_enable(false)
+        .depth_bounds_test_enable(false)
+        .stencil_test_enable(false);
+
+    let blend_attachments = [BlendMode::Opaque.attachment_state()];
+    let color_blending = vk::PipelineColorBlendStateCreateInfo::builder()
+        .logic_op_enable(false)
+        .attachments(&blend_attachments);
+
+    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissor_count(1);
+
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+
+    let multisampling = vk::PipelineMultisampleStateCreateInfo::builder()
+        .sample_shading_enable(false)
+        .rasterization_samples(target.samples);
+
+    let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&stages)
+        .vertex_input_state(&vertex_input_info)
+        .input_assembly_state(&input_assembly)
+        .viewport_state(&viewport_state)
+        .rasterization_state(&rasterizer)
+        .multisample_state(&multisampling)
+        .depth_stencil_state(&depth_stencil)
+        .color_blend_state(&color_blending)
+        .dynamic_state(&dynamic_state)
+        .layout(target.layout)
+        .render_pass(target.render_pass)
+        .subpass(0)
+        .build();
+
+    device
+        .create_graphics_pipelines(pipeline_cache, &[pipeline_create_info], None)
+        .map(|pipelines| pipelines[0])
+        .map_err(|err| RendererInitError::PipelineCreationError { err: err.1 })
+}
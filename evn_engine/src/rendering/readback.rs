@@ -0,0 +1,252 @@
+// Copying rendered images back into CPU memory
+//
+// There's one host visible buffer per frame in flight, the copy is recorded into the
+// frame's command buffer and can be read once the frame's fence is signaled.
+
+use super::{
+    allocator::{Allocation, Allocator, MemoryUsage},
+    debug::DebugNames,
+    RendererInitError,
+};
+use ash::{version::DeviceV1_0, vk, Device};
+use png::HasParameters;
+use std::{
+    fs::File,
+    io::{BufWriter, Error as IoError, ErrorKind},
+    path::Path,
+};
+
+// a rendered image, tightly packed RGBA8 rows from top to bottom
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Frame {
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let offset = ((y * self.width + x) * 4) as usize;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.pixels[offset..offset + 4]);
+        pixel
+    }
+
+    // 8 bit RGB or RGBA images only
+    pub fn load_png(path: &Path) -> Result<Self, IoError> {
+        let decoder = png::Decoder::new(File::open(path)?);
+        let (info, mut reader) = decoder.read_info()?;
+
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data)?;
+
+        let pixels = match (info.color_type, info.bit_depth) {
+            (png::ColorType::RGBA, png::BitDepth::Eight) => data,
+            (png::ColorType::RGB, png::BitDepth::Eight) => data
+                .chunks(3)
+                .flat_map(|pixel| vec![pixel[0], pixel[1], pixel[2], 255])
+                .collect(),
+            (color_type, bit_depth) => {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    format!("Unsupported PNG format {:?} {:?}", color_type, bit_depth),
+                ))
+            }
+        };
+
+        Ok(Frame {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), IoError> {
+        let file = BufWriter::new(File::create(path)?);
+
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)?;
+
+        Ok(())
+    }
+}
+
+pub struct Readback {
+    buffers: Vec<(vk::Buffer, Allocation)>,
+    extent: vk::Extent2D,
+    format: vk::Format,
+}
+
+impl Readback {
+    pub unsafe fn new(
+        allocator: &mut Allocator,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        frame_count: usize,
+    ) -> Result<Self, RendererInitError> {
+        // fail here instead of when the first frame is read
+        to_rgba8(format, &[]).map_err(|err| RendererInitError::RenderTargetError { err })?;
+
+        let buffer_create_info = vk::BufferCreateInfo::builder()
+            .size(vk::DeviceSize::from(extent.width * extent.height * 4))
+            .usage(vk::BufferUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+        let mut buffers = Vec::with_capacity(frame_count);
+        for _ in 0..frame_count {
+            match allocator.create_buffer(&buffer_create_info, MemoryUsage::GpuToCpu) {
+                Ok(buffer) => buffers.push(buffer),
+                Err(err) => {
+                    for (buffer, allocation) in buffers {
+                        allocator.destroy_buffer(buffer, allocation);
+                    }
+                    return Err(RendererInitError::AllocationError { err });
+                }
+            }
+        }
+
+        Ok(Readback {
+            buffers,
+            extent,
+            format,
+        })
+    }
+
+    // records the copy of `image`, which has to be in `layout` and is left in it,
+    // must be recorded outside of a render pass
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        image: vk::Image,
+        layout: vk::ImageLayout,
+    ) {
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+
+        let to_transfer = vk::ImageMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .old_layout(layout)
+            .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range)
+            .build();
+
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[to_transfer],
+        );
+
+        let region = vk::BufferImageCopy::builder()
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_extent(vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            })
+            .build();
+
+        device.cmd_copy_image_to_buffer(
+            command_buffer,
+            image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            self.buffers[frame].0,
+            &[region],
+        );
+
+        let to_host = vk::BufferMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::HOST_READ)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(self.buffers[frame].0)
+            .offset(0)
+            .size(vk::WHOLE_SIZE)
+            .build();
+
+        let from_transfer = vk::ImageMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .dst_access_mask(vk::AccessFlags::empty())
+            .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .new_layout(layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range)
+            .build();
+
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::HOST | vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[to_host],
+            &[from_transfer],
+        );
+    }
+
+    // the frame's fence has to be waited on before calling this
+    pub fn read(&self, frame: usize) -> Frame {
+        let allocation = &self.buffers[frame].1;
+        let size = (self.extent.width * self.extent.height * 4) as usize;
+        let data = unsafe {
+            std::slice::from_raw_parts(
+                allocation
+                    .mapped_ptr()
+                    .expect("Readback buffer isn't mapped"),
+                size,
+            )
+        };
+
+        Frame {
+            width: self.extent.width,
+            height: self.extent.height,
+            pixels: to_rgba8(self.format, data).expect("Readback format checked on creation"),
+        }
+    }
+
+    pub unsafe fn name(&self, debug_names: &DebugNames, name: &str) {
+        for (index, (buffer, _)) in self.buffers.iter().enumerate() {
+            debug_names.name(*buffer, &format!("{} buffer {}", name, index));
+        }
+    }
+
+    pub unsafe fn destroy(&mut self, allocator: &mut Allocator) {
+        for (buffer, allocation) in self.buffers.drain(..) {
+            allocator.destroy_buffer(buffer, allocation);
+        }
+    }
+}
+
+// only 4 byte formats are used for color targets, anything else can't be read back
+fn to_rgba8(format: vk::Format, data: &[u8]) -> Result<Vec<u8>, vk::Result> {
+    match format {
+        vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB => Ok(data
+            .chunks(4)
+            .flat_map(|pixel| vec![pixel[2], pixel[1], pixel[0], pixel[3]])
+            .collect()),
+        vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB => Ok(data.to_vec()),
+        _ => Err(vk::Result::ERROR_FORMAT_NOT_SUPPORTED),
+    }
+}
@@ -0,0 +1,56 @@
+// Screenshots
+//
+// Requesting one (F12 or `Game::screenshot`) makes the renderer copy the next frame
+// into a host visible buffer, it's saved as a PNG in ./screenshots, next to ./logs.
+
+use super::Frame;
+use crate::logger;
+use log::{error, info};
+use std::{
+    fs,
+    io::{Error as IoError, ErrorKind},
+    path::PathBuf,
+    thread,
+};
+
+#[derive(Debug, Default)]
+pub struct Screenshot {
+    // reset by the renderer once the frame is captured
+    pub requested: bool,
+}
+
+impl Screenshot {
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+}
+
+// encoding takes a while, so it's done on its own thread
+pub fn save(frame: Frame) {
+    thread::spawn(move || match screenshot_path() {
+        Ok(path) => match frame.save_png(&path) {
+            Ok(()) => info!("Saved screenshot to {}", path.display()),
+            Err(err) => error!("Failed to save screenshot to {}: {}", path.display(), err),
+        },
+        Err(err) => error!("Failed to create screenshots folder: {}", err),
+    });
+}
+
+// timestamped, a number is appended if there already is a screenshot from the same second
+fn screenshot_path() -> Result<PathBuf, IoError> {
+    if let Err(err) = fs::create_dir("screenshots") {
+        if err.kind() != ErrorKind::AlreadyExists {
+            return Err(err);
+        }
+    }
+
+    let time = logger::format_yyyymmdd_hhmmss();
+    let mut path = PathBuf::from(format!("./screenshots/{}.png", time));
+    let mut index = 1;
+    while path.exists() {
+        path = PathBuf::from(format!("./screenshots/{}-{}.png", time, index));
+        index += 1;
+    }
+
+    Ok(path)
+}
@@ -0,0 +1,280 @@
+// The `graphics` section of the game config
+
+use crate::resources::{Resource, ResourceState, ResourcesData};
+use ash::vk;
+use log::warn;
+use serde_yaml::Value;
+
+// falls back to the next supported mode, fifo is always available
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentMode {
+    // vsync
+    Fifo,
+    // vsync, tears when a frame is late
+    FifoRelaxed,
+    // no tearing, renders as fast as possible
+    Mailbox,
+    // no vsync
+    Immediate,
+}
+
+impl PresentMode {
+    fn from_str(name: &str) -> Option<Self> {
+        match name {
+            "fifo" => Some(PresentMode::Fifo),
+            "fifo_relaxed" => Some(PresentMode::FifoRelaxed),
+            "mailbox" => Some(PresentMode::Mailbox),
+            "immediate" => Some(PresentMode::Immediate),
+            _ => None,
+        }
+    }
+
+    // this mode followed by its fallbacks
+    pub fn preference(self) -> &'static [vk::PresentModeKHR] {
+        match self {
+            PresentMode::Fifo => &[vk::PresentModeKHR::FIFO],
+            PresentMode::FifoRelaxed => {
+                &[vk::PresentModeKHR::FIFO_RELAXED, vk::PresentModeKHR::FIFO]
+            }
+            PresentMode::Mailbox => &[
+                vk::PresentModeKHR::MAILBOX,
+                vk::PresentModeKHR::IMMEDIATE,
+                vk::PresentModeKHR::FIFO,
+            ],
+            PresentMode::Immediate => &[
+                vk::PresentModeKHR::IMMEDIATE,
+                vk::PresentModeKHR::MAILBOX,
+                vk::PresentModeKHR::FIFO,
+            ],
+        }
+    }
+}
+
+// what happens after a frame with a validation error, besides logging it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnValidationError {
+    Log,
+    Panic,
+    // `Renderer::read_frame` returns None for the frame
+    FailFrame,
+}
+
+impl OnValidationError {
+    fn from_str(name: &str) -> Option<Self> {
+        match name {
+            "log" => Some(OnValidationError::Log),
+            "panic" => Some(OnValidationError::Panic),
+            "fail_frame" => Some(OnValidationError::FailFrame),
+            _ => None,
+        }
+    }
+}
+
+// only used with --validation
+#[derive(Debug, Clone)]
+pub struct ValidationSettings {
+    // message ID names, e.g. VUID-vkCmdDraw-None-02859
+    pub ignore: Vec<String>,
+    pub on_error: OnValidationError,
+}
+
+impl Default for ValidationSettings {
+    fn default() -> Self {
+        ValidationSettings {
+            ignore: Vec::new(),
+            on_error: OnValidationError::Log,
+        }
+    }
+}
+
+impl ValidationSettings {
+    fn from_value(validation: &Value) -> Self {
+        let default = ValidationSettings::default();
+
+        ValidationSettings {
+            ignore: validation
+                .get("ignore")
+                .and_then(Value::as_sequence)
+                .map_or_else(Vec::new, |ignore| {
+                    ignore
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(str::to_owned)
+                        .collect()
+                }),
+            on_error: match validation.get("on_error").and_then(Value::as_str) {
+                Some(name) => OnValidationError::from_str(name).unwrap_or_else(|| {
+                    warn!("Unknown validation error action \"{}\"", name);
+                    default.on_error
+                }),
+                None => default.on_error,
+            },
+        }
+    }
+}
+
+// one full screen pass of the post chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostPassSettings {
+    // referenced by the inputs of later passes, unique
+    pub name: String,
+    // registered with `ResourceBuilder::with_shader`
+    pub shader: String,
+    // earlier passes, `scene` or texture resources, bound in order
+    pub inputs: Vec<String>,
+    // of the window size, the last pass always covers the window
+    pub scale: f32,
+    // passed to the fragment shader as push constants
+    pub params: Vec<f32>,
+}
+
+impl PostPassSettings {
+    // None without a shader, the name defaults to the shader and the input to the previous pass
+    fn from_value(pass: &Value, previous: &str) -> Option<Self> {
+        let shader = match pass.get("shader").and_then(Value::as_str) {
+            Some(shader) => shader.to_owned(),
+            None => {
+                warn!("Post pass without a shader");
+                return None;
+            }
+        };
+
+        Some(PostPassSettings {
+            name: pass
+                .get("name")
+                .and_then(Value::as_str)
+                .map_or_else(|| shader.clone(), str::to_owned),
+            inputs: pass.get("inputs").and_then(Value::as_sequence).map_or_else(
+                || vec![previous.to_owned()],
+                |inputs| {
+                    inputs
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(str::to_owned)
+                        .collect()
+                },
+            ),
+            scale: pass
+                .get("scale")
+                .and_then(Value::as_f64)
+                .map_or(1.0, |scale| scale.max(0.0) as f32),
+            params: pass.get("params").and_then(Value::as_sequence).map_or_else(
+                Vec::new,
+                |params| {
+                    params
+                        .iter()
+                        .filter_map(Value::as_f64)
+                        .map(|param| param as f32)
+                        .collect()
+                },
+            ),
+            shader,
+        })
+    }
+
+    fn chain_from_value(post: &Value) -> Vec<Self> {
+        let mut chain: Vec<PostPassSettings> = Vec::new();
+        for pass in post.as_sequence().map_or(&[][..], Vec::as_slice) {
+            let previous = chain.last().map_or(SCENE_INPUT, |pass| &pass.name);
+            if let Some(pass) = PostPassSettings::from_value(pass, previous) {
+                chain.push(pass);
+            }
+        }
+        chain
+    }
+}
+
+// the input of the post chain, the HDR image the main pass renders into
+pub const SCENE_INPUT: &str = "scene";
+
+#[derive(Debug, Clone)]
+pub struct GraphicsSettings {
+    // clamped to what the device supports, 1 disables multisampling
+    pub msaa_samples: u32,
+    pub depth_buffer: bool,
+    pub present_mode: PresentMode,
+    // CPU side frame limiter, None renders as fast as the present mode allows
+    pub max_fps: Option<u32>,
+    // frames the CPU may record ahead of the GPU, at least 1
+    pub frames_in_flight: usize,
+    // device name or UUID (index for Vulkan 1.0 devices) as printed by --list-gpus, overridden by --gpu
+    pub gpu: Option<String>,
+    pub validation: ValidationSettings,
+    // empty renders the scene straight into the window, without HDR
+    pub post: Vec<PostPassSettings>,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        GraphicsSettings {
+            msaa_samples: 1,
+            depth_buffer: true,
+            present_mode: PresentMode::Mailbox,
+            max_fps: None,
+            frames_in_flight: 2,
+            gpu: None,
+            validation: ValidationSettings::default(),
+            post: Vec::new(),
+        }
+    }
+}
+
+impl GraphicsSettings {
+    // uses the first loaded config that contains a `graphics` section
+    pub fn load(res: &ResourcesData, config_names: &[String]) -> Self {
+        for name in config_names {
+            if let ResourceState::Loaded(Resource::Config(config)) = &*res.wait_for_resource(name) {
+                if let Some(graphics) = config.get().get("graphics") {
+                    return GraphicsSettings::from_value(graphics);
+                }
+            }
+        }
+
+        warn!("No graphics config found, using defaults");
+        GraphicsSettings::default()
+    }
+
+    // missing or invalid values fall back to the defaults
+    pub fn from_value(graphics: &Value) -> Self {
+        let default = GraphicsSettings::default();
+
+        GraphicsSettings {
+            msaa_samples: graphics
+                .get("msaa_samples")
+                .and_then(Value::as_u64)
+                .map_or(default.msaa_samples, |samples| samples.max(1) as u32),
+            depth_buffer: graphics
+                .get("depth_buffer")
+                .and_then(Value::as_bool)
+                .unwrap_or(default.depth_buffer),
+            present_mode: match graphics.get("present_mode").and_then(Value::as_str) {
+                Some(name) => PresentMode::from_str(name).unwrap_or_else(|| {
+                    warn!("Unknown present mode \"{}\"", name);
+                    default.present_mode
+                }),
+                None => default.present_mode,
+            },
+            // 0 disables the limiter too
+            max_fps: graphics
+                .get("max_fps")
+                .and_then(Value::as_u64)
+                .filter(|&max_fps| max_fps > 0)
+                .map(|max_fps| max_fps as u32),
+            frames_in_flight: graphics
+                .get("frames_in_flight")
+                .and_then(Value::as_u64)
+                .map_or(default.frames_in_flight, |frames| frames.max(1) as usize),
+            gpu: graphics
+                .get("gpu")
+                .and_then(Value::as_str)
+                .filter(|gpu| !gpu.is_empty())
+                .map(str::to_owned),
+            validation: graphics
+                .get("validation")
+                .map_or_else(ValidationSettings::default, ValidationSettings::from_value),
+            post: graphics
+                .get("post")
+                .map_or_else(Vec::new, PostPassSettings::chain_from_value),
+        }
+    }
+}
@@ -0,0 +1,640 @@
+// Sprite pass
+//
+// Sprites are sorted by layer and texture and drawn as instanced quads, one draw call for
+// every run of sprites sharing a texture. The instance data is rewritten every frame into
+// the frame's region of a persistently mapped buffer. Textures are bound at set 1.
+
+use super::{
+    allocator::{Allocator, LinearAllocator},
+    backend::SpriteDraw,
+    debug::DebugNames,
+    pipeline::{self, BlendMode, PipelineTarget},
+    targets::AttachmentImage,
+    RendererInitError,
+};
+use ash::{version::DeviceV1_0, vk, Device};
+use fnv::{FnvHashMap, FnvHashSet};
+use log::warn;
+use nalgebra_glm as glm;
+use std::{ffi::CString, mem};
+
+// per frame in flight, sprites after that are dropped
+const MAX_SPRITES: usize = 65536;
+
+const VERTEX_SHADER: &[u8] = include_bytes!("../../../resources/closed/shaders/sprite.vert.spv");
+const FRAGMENT_SHADER: &[u8] = include_bytes!("../../../resources/closed/shaders/sprite.frag.spv");
+
+// per instance vertex input, locations 0 to 3
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteInstance {
+    position_size: [f32; 4],
+    // cos and sin of the rotation
+    rotation_pivot: [f32; 4],
+    uv_rect: [f32; 4],
+    tint: [f32; 4],
+}
+
+impl SpriteInstance {
+    // `uv` is (x, y, width, height), see `Sprite` for the rest
+    pub fn new(
+        position: glm::Vec2,
+        size: glm::Vec2,
+        rotation: f32,
+        pivot: glm::Vec2,
+        uv: [f32; 4],
+        tint: glm::Vec4,
+    ) -> Self {
+        SpriteInstance {
+            position_size: [position.x, position.y, size.x, size.y],
+            rotation_pivot: [rotation.cos(), rotation.sin(), pivot.x, pivot.y],
+            uv_rect: uv,
+            tint: [tint.x, tint.y, tint.z, tint.w],
+        }
+    }
+}
+
+// instances drawn with the same texture
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteBatch {
+    pub texture: usize,
+    pub first_instance: u32,
+    pub instance_count: u32,
+}
+
+// the instances of one frame, ready to be recorded
+#[derive(Debug, Default)]
+pub struct SpriteBatches {
+    buffer: vk::Buffer,
+    offset: vk::DeviceSize,
+    batches: Vec<SpriteBatch>,
+}
+
+pub struct SpritePass {
+    // texture name -> index into textures
+    texture_indices: FnvHashMap<String, usize>,
+    textures: Vec<AttachmentImage>,
+    sampler: vk::Sampler,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    // one per texture
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    instances: LinearAllocator,
+    // the last frame had too many sprites, so the warning isn't logged every frame
+    overflowed: bool,
+    // textures that were drawn but aren't loaded, each one is only warned about once
+    missing_textures: FnvHashSet<String>,
+}
+
+impl SpritePass {
+    pub unsafe fn new(
+        device: &Device,
+        allocator: &mut Allocator,
+        // (name, uploaded texture)
+        textures: Vec<(String, AttachmentImage)>,
+        camera_layout: vk::DescriptorSetLayout,
+        target: &PipelineTarget,
+        pipeline_cache: vk::PipelineCache,
+        frames_in_flight: usize,
+    ) -> Result<Self, RendererInitError> {
+        let descriptor_error = |err| RendererInitError::DescriptorCreationError { err };
+
+        let (texture_indices, textures): (FnvHashMap<_, _>, Vec<_>) = textures
+            .into_iter()
+            .enumerate()
+            .map(|(index, (name, texture))| ((name, index), texture))
+            .unzip();
+
+        let sampler_create_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .max_lod(0.0);
+        let sampler = device
+            .create_sampler(&sampler_create_info, None)
+            .map_err(descriptor_error)?;
+
+        // layout(set = 1, binding = 0) uniform texture2D, layout(set = 1, binding = 1) uniform sampler
+        let bindings = [
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build(),
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(1)
+                .descriptor_type(vk::DescriptorType::SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build(),
+        ];
+        let descriptor_set_layout_create_info =
+            vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+        let descriptor_set_layout = device
+            .create_descriptor_set_layout(&descriptor_set_layout_create_info, None)
+            .map_err(descriptor_error)?;
+
+        // pools can't be empty
+        let set_count = textures.len().max(1) as u32;
+        let pool_sizes = [
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::SAMPLED_IMAGE,
+                descriptor_count: set_count,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::SAMPLER,
+                descriptor_count: set_count,
+            },
+        ];
+        let descriptor_pool_create_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&pool_sizes)
+            .max_sets(set_count);
+        let descriptor_pool = device
+            .create_descriptor_pool(&descriptor_pool_create_info, None)
+            .map_err(descriptor_error)?;
+
+        let descriptor_sets = if textures.is_empty() {
+            Vec::new()
+        } else {
+            let set_layouts = vec![descriptor_set_layout; textures.len()];
+            let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&set_layouts);
+
+            device
+                .allocate_descriptor_sets(&descriptor_set_allocate_info)
+                .map_err(descriptor_error)?
+        };
+
+        for (&descriptor_set, texture) in descriptor_sets.iter().zip(&textures) {
+            write_texture_set(device, descriptor_set, texture.view, sampler);
+        }
+
+        let set_layouts = [camera_layout, descriptor_set_layout];
+        let pipeline_layout_create_info =
+            vk::PipelineLayoutCreateInfo::builder().set_layouts(&set_layouts);
+        let pipeline_layout = device
+            .create_pipeline_layout(&pipeline_layout_create_info, None)
+            .map_err(|err| RendererInitError::PipelineCreationError { err })?;
+
+        let pipeline = create_pipeline(device, pipeline_layout, target, pipeline_cache)?;
+
+        let instances = LinearAllocator::new(
+            allocator,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            (MAX_SPRITES * mem::size_of::<SpriteInstance>()) as vk::DeviceSize,
+            frames_in_flight,
+        )
+        .map_err(|err| RendererInitError::AllocationError { err })?;
+
+        Ok(SpritePass {
+            texture_indices,
+            textures,
+            sampler,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            pipeline_layout,
+            pipeline,
+            instances,
+            overflowed: false,
+            missing_textures: FnvHashSet::default(),
+        })
+    }
+
+    // writes the instances of `frame`, whose fence has to be waited on,
+    // sprites with unknown textures are skipped
+    pub fn prepare(&mut self, frame: usize, sprites: &[SpriteDraw]) -> SpriteBatches {
+        let texture_indices = &self.texture_indices;
+        let missing_textures = &mut self.missing_textures;
+        let mut sprites = sprites
+            .iter()
+            .filter_map(|draw| match texture_indices.get(&draw.sprite.texture) {
+                Some(&texture) => Some((texture, draw)),
+                None => {
+                    if missing_textures.insert(draw.sprite.texture.clone()) {
+                        warn!(
+                            "Sprite texture \"{}\" isn't loaded, sprites using it aren't drawn",
+                            draw.sprite.texture
+                        );
+                    }
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        if sprites.is_empty() {
+            return SpriteBatches::default();
+        }
+
+        sort_sprites(&mut sprites);
+
+        let overflowed = sprites.len() > MAX_SPRITES;
+        if overflowed && !self.overflowed {
+            warn!(
+                "{} sprites in one frame, only drawing {}",
+                sprites.len(),
+                MAX_SPRITES
+            );
+        }
+        self.overflowed = overflowed;
+        sprites.truncate(MAX_SPRITES);
+
+        let instances = sprites
+            .iter()
+            .map(|(_, draw)| {
+                let sprite = draw.sprite;
+                SpriteInstance::new(
+                    draw.position,
+                    sprite.size,
+                    draw.rotation,
+                    sprite.pivot,
+                    [sprite.uv.x, sprite.uv.y, sprite.uv.width, sprite.uv.height],
+                    sprite.tint,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        self.instances.reset(frame);
+        let allocation = match self.instances.allocate(
+            mem::size_of_val(instances.as_slice()) as vk::DeviceSize,
+            mem::align_of::<SpriteInstance>() as vk::DeviceSize,
+        ) {
+            Some(allocation) => allocation,
+            None => return SpriteBatches::default(),
+        };
+        allocation.write(&instances);
+
+        SpriteBatches {
+            buffer: allocation.buffer,
+            offset: allocation.offset,
+            batches: batch_sprites(&sprites),
+        }
+    }
+
+    pub fn descriptor_set_layout(&self) -> vk::DescriptorSetLayout {
+        self.descriptor_set_layout
+    }
+
+    pub fn sampler(&self) -> vk::Sampler {
+        self.sampler
+    }
+
+    // also sampled by the post chain
+    pub fn texture_view(&self, name: &str) -> Option<vk::ImageView> {
+        self.texture_indices
+            .get(name)
+            .map(|&index| self.textures[index].view)
+    }
+
+    // has to be recorded inside of the main render pass
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        camera_set: vk::DescriptorSet,
+        batches: &SpriteBatches,
+    ) {
+        if batches.batches.is_empty() {
+            return;
+        }
+
+        self.bind(
+            device,
+            command_buffer,
+            camera_set,
+            batches.buffer,
+            batches.offset,
+        );
+        for batch in &batches.batches {
+            self.draw(
+                device,
+                command_buffer,
+                self.descriptor_sets[batch.texture],
+                batch,
+            );
+        }
+    }
+
+    // binds the pipeline, the camera and the instances, also used to draw text
+    pub unsafe fn bind(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        camera_set: vk::DescriptorSet,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+    ) {
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        // the layout differs from the one of the other pipelines, set 0 has to be bound again
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline_layout,
+            0,
+            &[camera_set],
+            &[],
+        );
+        device.cmd_bind_vertex_buffers(command_buffer, 0, &[buffer], &[offset]);
+    }
+
+    // `texture_set` has to be written with `write_texture_set`
+    pub unsafe fn draw(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        texture_set: vk::DescriptorSet,
+        batch: &SpriteBatch,
+    ) {
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline_layout,
+            1,
+            &[texture_set],
+            &[],
+        );
+        device.cmd_draw(
+            command_buffer,
+            4,
+            batch.instance_count,
+            0,
+            batch.first_instance,
+        );
+    }
+
+    pub unsafe fn name(&self, debug_names: &DebugNames) {
+        for (name, index) in &self.texture_indices {
+            self.textures[*index].name(debug_names, &format!("{} texture", name));
+            debug_names.name(
+                self.descriptor_sets[*index],
+                &format!("{} texture set", name),
+            );
+        }
+        debug_names.name(self.sampler, "sprite sampler");
+        debug_names.name(self.descriptor_set_layout, "sprite texture layout");
+        debug_names.name(self.descriptor_pool, "sprite texture pool");
+        debug_names.name(self.pipeline_layout, "sprite pipeline layout");
+        debug_names.name(self.pipeline, "sprite pipeline");
+        debug_names.name(self.instances.buffer(), "sprite instances");
+    }
+
+    pub unsafe fn destroy(&mut self, device: &Device, allocator: &mut Allocator) {
+        self.instances.destroy(allocator);
+
+        device.destroy_pipeline(self.pipeline, None);
+        device.destroy_pipeline_layout(self.pipeline_layout, None);
+        device.destroy_descriptor_pool(self.descriptor_pool, None);
+        device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        device.destroy_sampler(self.sampler, None);
+
+        for texture in self.textures.drain(..) {
+            texture.destroy(device, allocator);
+        }
+    }
+}
+
+// the set has to be allocated with the layout of the sprite pass
+pub unsafe fn write_texture_set(
+    device: &Device,
+    descriptor_set: vk::DescriptorSet,
+    view: vk::ImageView,
+    sampler: vk::Sampler,
+) {
+    let image_info = [vk::DescriptorImageInfo {
+        sampler: vk::Sampler::null(),
+        image_view: view,
+        image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    }];
+    let sampler_info = [vk::DescriptorImageInfo {
+        sampler,
+        image_view: vk::ImageView::null(),
+        image_layout: vk::ImageLayout::UNDEFINED,
+    }];
+
+    let descriptor_writes = [
+        vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+            .image_info(&image_info)
+            .build(),
+        vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(1)
+            .descriptor_type(vk::DescriptorType::SAMPLER)
+            .image_info(&sampler_info)
+            .build(),
+    ];
+    device.update_descriptor_sets(&descriptor_writes, &[]);
+}
+
+unsafe fn create_pipeline(
+    device: &Device,
+    layout: vk::PipelineLayout,
+    target: &PipelineTarget,
+    pipeline_cache: vk::PipelineCache,
+) -> Result<vk::Pipeline, RendererInitError> {
+    let vertex_shader_module =
+        pipeline::create_shader_module(device, "sprite vertex", VERTEX_SHADER)?;
+    let fragment_shader_module =
+        match pipeline::create_shader_module(device, "sprite fragment", FRAGMENT_SHADER) {
+            Ok(module) => module,
+            Err(err) => {
+                device.destroy_shader_module(vertex_shader_module, None);
+                return Err(err);
+            }
+        };
+
+    let entry_name = CString::new("main").unwrap();
+    let stages = [
+        vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(vertex_shader_module)
+            .name(&entry_name)
+            .build(),
+        vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(fragment_shader_module)
+            .name(&entry_name)
+            .build(),
+    ];
+
+    let vertex_bindings = [vk::VertexInputBindingDescription {
+        binding: 0,
+        stride: mem::size_of::<SpriteInstance>() as u32,
+        input_rate: vk::VertexInputRate::INSTANCE,
+    }];
+    let vertex_attributes = (0..4)
+        .map(|location| vk::VertexInputAttributeDescription {
+            location,
+            binding: 0,
+            format: vk::Format::R32G32B32A32_SFLOAT,
+            offset: location * mem::size_of::<[f32; 4]>() as u32,
+        })
+        .collect::<Vec<_>>();
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder()
+        .vertex_binding_descriptions(&vertex_bindings)
+        .vertex_attribute_descriptions(&vertex_attributes);
+
+    // 4 vertices per instance
+    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::TRIANGLE_STRIP)
+        .primitive_restart_enable(false);
+
+    let rasterizer = vk::PipelineRasterizationStateCreateInfo::builder()
+        .depth_clamp_enable(false)
+        .rasterizer_discard_enable(false)
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.0)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::CLOCKWISE)
+        .depth_bias_enable(false);
+
+    // drawn in order, on top of everything
+    let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(false)
+        .depth_write_enable(false)
+        .depth_bounds_test_enable(false)
+        .stencil_test_enable(false);
+
+    let blend_attachments = [BlendMode::Alpha.attachment_state()];
+    let color_blending = vk::PipelineColorBlendStateCreateInfo::builder()
+        .logic_op_enable(false)
+        .attachments(&blend_attachments);
+
+    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissor_count(1);
+
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+
+    let multisampling = vk::PipelineMultisampleStateCreateInfo::builder()
+        .sample_shading_enable(false)
+        .rasterization_samples(target.samples);
+
+    let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&stages)
+        .vertex_input_state(&vertex_input_info)
+        .input_assembly_state(&input_assembly)
+        .viewport_state(&viewport_state)
+        .rasterization_state(&rasterizer)
+        .multisample_state(&multisampling)
+        .depth_stencil_state(&depth_stencil)
+        .color_blend_state(&color_blending)
+        .dynamic_state(&dynamic_state)
+        .layout(layout)
+        .render_pass(target.render_pass)
+        .subpass(0)
+        .build();
+
+    let pipelines = device.create_graphics_pipelines(pipeline_cache, &[pipeline_create_info], None);
+
+    device.destroy_shader_module(vertex_shader_module, None);
+    device.destroy_shader_module(fragment_shader_module, None);
+
+    pipelines
+        .map(|pipelines| pipelines[0])
+        .map_err(|err| RendererInitError::PipelineCreationError { err: err.1 })
+}
+
+// by layer and then texture, stable so sprites with the same layer and texture keep their order
+fn sort_sprites(sprites: &mut [(usize, &SpriteDraw)]) {
+    sprites.sort_by_key(|(texture, draw)| (draw.sprite.layer, *texture));
+}
+
+// one batch for every run of sorted sprites sharing a texture
+fn batch_sprites(sprites: &[(usize, &SpriteDraw)]) -> Vec<SpriteBatch> {
+    let mut batches = Vec::<SpriteBatch>::new();
+    for (index, (texture, _)) in sprites.iter().enumerate() {
+        match batches.last_mut() {
+            Some(batch) if batch.texture == *texture => batch.instance_count += 1,
+            _ => batches.push(SpriteBatch {
+                texture: *texture,
+                first_instance: index as u32,
+                instance_count: 1,
+            }),
+        }
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Sprite;
+
+    fn draw(sprite: &Sprite) -> SpriteDraw<'_> {
+        SpriteDraw {
+            sprite,
+            position: glm::vec2(0.0, 0.0),
+            rotation: 0.0,
+        }
+    }
+
+    #[test]
+    fn sorted_by_layer_then_texture() {
+        let mut sprites = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|name| Sprite::new(*name, 1.0, 1.0))
+            .collect::<Vec<_>>();
+        sprites[0].layer = 1;
+        sprites[2].layer = -1;
+        sprites[4].layer = 1;
+
+        let draws = sprites.iter().map(draw).collect::<Vec<_>>();
+        // (texture, draw)
+        let mut sorted = vec![
+            (0, &draws[0]),
+            (2, &draws[1]),
+            (1, &draws[2]),
+            (1, &draws[3]),
+            (0, &draws[4]),
+        ];
+        sort_sprites(&mut sorted);
+
+        let order = sorted
+            .iter()
+            .map(|(texture, draw)| (draw.sprite.layer, *texture, draw.sprite.texture.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            [
+                (-1, 1, "c"),
+                (0, 1, "d"),
+                (0, 2, "b"),
+                (1, 0, "a"),
+                (1, 0, "e"),
+            ]
+        );
+    }
+
+    #[test]
+    fn batches_runs_of_textures() {
+        let sprite = Sprite::new("a", 1.0, 1.0);
+        let draw = draw(&sprite);
+        let sprites = [0, 0, 1, 0, 0, 0]
+            .iter()
+            .map(|&texture| (texture, &draw))
+            .collect::<Vec<_>>();
+
+        let batches = batch_sprites(&sprites)
+            .iter()
+            .map(|batch| (batch.texture, batch.first_instance, batch.instance_count))
+            .collect::<Vec<_>>();
+        // a texture used again after another one starts a new batch
+        assert_eq!(batches, [(0, 0, 2), (1, 2, 1), (0, 3, 3)]);
+        assert!(batch_sprites(&[]).is_empty());
+    }
+}
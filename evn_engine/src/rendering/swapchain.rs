@@ -0,0 +1,268 @@
+// Presenting to a window
+//
+// Everything the renderer only needs when it has a surface, headless renderers don't have one.
+
+use super::{settings::PresentMode, RendererInitError};
+use crate::logger::UnwrapOrLog;
+use ash::{
+    extensions::khr::{Surface, Swapchain},
+    prelude::VkResult,
+    version::DeviceV1_0,
+    vk, Device,
+};
+use either::Either;
+use log::warn;
+use winit::{dpi::LogicalSize, Window};
+
+pub struct WindowOutput {
+    // kept alive as long as its surface
+    #[allow(dead_code)]
+    pub window: Window,
+    pub surface_loader: Surface,
+    pub surface: vk::SurfaceKHR,
+    pub surface_format: vk::SurfaceFormatKHR,
+    pub swapchain_loader: Swapchain,
+    pub swapchain: vk::SwapchainKHR,
+    pub present_queue: vk::Queue,
+    pub present_family_index: u32,
+    pub present_mode: PresentMode,
+    pub window_size: LogicalSize,
+    // set by resizes and suboptimal/out of date results, recreated before the next frame
+    pub outdated: bool,
+    // the swapchain images can be copied from, needed for screenshots
+    pub transfer_src: bool,
+    // one per frame in flight
+    pub image_available_semaphores: Vec<vk::Semaphore>,
+    pub render_finished_semaphores: Vec<vk::Semaphore>,
+}
+
+impl WindowOutput {
+    // the extent the swapchain would get, zero while minimized
+    pub unsafe fn extent(&self, physical_device: vk::PhysicalDevice) -> VkResult<vk::Extent2D> {
+        let surface_capabilites = self
+            .surface_loader
+            .get_physical_device_surface_capabilities(physical_device, self.surface)?;
+
+        Ok(choose_swap_extent(self.window_size, surface_capabilites))
+    }
+
+    // replaces the current swapchain (if any) with a new one, returns its images
+    pub unsafe fn create_swapchain(
+        &mut self,
+        physical_device: vk::PhysicalDevice,
+        graphics_family_index: u32,
+    ) -> Result<(vk::Extent2D, Vec<vk::Image>), RendererInitError> {
+        let (surface_capabilites, surface_present_modes, _) =
+            surface_information(&self.surface_loader, self.surface, physical_device).map_err(
+                |err| RendererInitError::SwapchainCreationError {
+                    err: Either::Left(err),
+                },
+            )?;
+
+        let present_mode = choose_swap_present_mode(surface_present_modes, self.present_mode);
+        let extent = choose_swap_extent(self.window_size, surface_capabilites);
+
+        let mut image_count = surface_capabilites.min_image_count + 1;
+        if surface_capabilites.max_image_count > 0
+            && image_count > surface_capabilites.max_image_count
+        {
+            image_count = surface_capabilites.max_image_count;
+        }
+
+        self.transfer_src = surface_capabilites
+            .supported_usage_flags
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC);
+        let mut image_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT;
+        if self.transfer_src {
+            image_usage |= vk::ImageUsageFlags::TRANSFER_SRC;
+        }
+
+        let mut swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
+            .surface(self.surface)
+            .min_image_count(image_count)
+            .image_format(self.surface_format.format)
+            .image_color_space(self.surface_format.color_space)
+            .image_extent(extent)
+            .image_array_layers(1)
+            .image_usage(image_usage)
+            .pre_transform(surface_capabilites.current_transform)
+            .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+            .present_mode(present_mode)
+            .clipped(true)
+            .old_swapchain(self.swapchain);
+
+        let swapchain_queue_family_indices = [graphics_family_index, self.present_family_index];
+        if graphics_family_index != self.present_family_index {
+            swapchain_create_info = swapchain_create_info
+                .image_sharing_mode(vk::SharingMode::CONCURRENT)
+                .queue_family_indices(&swapchain_queue_family_indices)
+        } else {
+            swapchain_create_info =
+                swapchain_create_info.image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+        };
+
+        let swapchain = self
+            .swapchain_loader
+            .create_swapchain(&swapchain_create_info, None)
+            .map_err(|err| RendererInitError::SwapchainCreationError {
+                err: Either::Left(err),
+            })?;
+
+        // the old swapchain is retired now
+        self.swapchain_loader
+            .destroy_swapchain(self.swapchain, None);
+        self.swapchain = swapchain;
+
+        let images = self
+            .swapchain_loader
+            .get_swapchain_images(swapchain)
+            .map_err(|err| RendererInitError::SwapchainCreationError {
+                err: Either::Left(err),
+            })?;
+
+        Ok((extent, images))
+    }
+
+    // None if the frame has to be skipped
+    pub unsafe fn acquire_image(&mut self, frame: usize) -> Option<u32> {
+        let next_image = self.swapchain_loader.acquire_next_image(
+            self.swapchain,
+            u64::max_value(),
+            self.image_available_semaphores[frame],
+            vk::Fence::null(),
+        );
+
+        // a suboptimal image can still be presented, recreate after this frame
+        match next_image {
+            Ok((image_index, suboptimal)) => {
+                self.outdated |= suboptimal;
+                Some(image_index)
+            }
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.outdated = true;
+                None
+            }
+            Err(err) => Err(err).unwrap_or_log("Failed to acquire next image"),
+        }
+    }
+
+    pub unsafe fn present(&mut self, frame: usize, image_index: u32) {
+        let wait_semaphores = [self.render_finished_semaphores[frame]];
+        let swapchains = [self.swapchain];
+        let image_indices = [image_index];
+        let present_info = vk::PresentInfoKHR::builder()
+            .wait_semaphores(&wait_semaphores)
+            .swapchains(&swapchains)
+            .image_indices(&image_indices);
+
+        match self
+            .swapchain_loader
+            .queue_present(self.present_queue, &present_info)
+        {
+            Ok(suboptimal) => self.outdated |= suboptimal,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.outdated = true,
+            Err(err) => Err(err).unwrap_or_log("Failed to submit to present queue"),
+        }
+    }
+
+    // the device has to be idle
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        for semaphore in &self.image_available_semaphores {
+            device.destroy_semaphore(*semaphore, None);
+        }
+
+        for semaphore in &self.render_finished_semaphores {
+            device.destroy_semaphore(*semaphore, None);
+        }
+
+        self.swapchain_loader
+            .destroy_swapchain(self.swapchain, None);
+        self.surface_loader.destroy_surface(self.surface, None);
+    }
+}
+
+pub unsafe fn surface_information(
+    surface_loader: &Surface,
+    surface: vk::SurfaceKHR,
+    physical_device: vk::PhysicalDevice,
+) -> VkResult<(
+    vk::SurfaceCapabilitiesKHR,
+    Vec<vk::PresentModeKHR>,
+    Vec<vk::SurfaceFormatKHR>,
+)> {
+    let surface_capabilites =
+        surface_loader.get_physical_device_surface_capabilities(physical_device, surface)?;
+
+    let surface_present_modes =
+        surface_loader.get_physical_device_surface_present_modes(physical_device, surface)?;
+
+    let surface_formats =
+        surface_loader.get_physical_device_surface_formats(physical_device, surface)?;
+
+    Ok((surface_capabilites, surface_present_modes, surface_formats))
+}
+
+pub fn choose_swap_surface_format(
+    available_formats: Vec<vk::SurfaceFormatKHR>,
+) -> vk::SurfaceFormatKHR {
+    if available_formats.len() == 1 && available_formats[0].format == vk::Format::UNDEFINED {
+        return vk::SurfaceFormatKHR::builder()
+            .format(vk::Format::B8G8R8A8_UNORM)
+            .color_space(vk::ColorSpaceKHR::SRGB_NONLINEAR)
+            .build();
+    }
+
+    for available_format in &available_formats {
+        if available_format.format == vk::Format::B8G8R8A8_UNORM
+            && available_format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+        {
+            return available_format.clone();
+        }
+    }
+
+    available_formats[0]
+}
+
+fn choose_swap_present_mode(
+    available_present_modes: Vec<vk::PresentModeKHR>,
+    requested: PresentMode,
+) -> vk::PresentModeKHR {
+    let present_mode = requested
+        .preference()
+        .iter()
+        .cloned()
+        .find(|mode| available_present_modes.contains(mode))
+        // fifo support is required by the spec
+        .unwrap_or(vk::PresentModeKHR::FIFO);
+
+    if present_mode != requested.preference()[0] {
+        warn!(
+            "{:?} present mode not supported, using {:?}",
+            requested, present_mode
+        );
+    }
+
+    present_mode
+}
+
+fn choose_swap_extent(
+    window_size: winit::dpi::LogicalSize,
+    capabilites: vk::SurfaceCapabilitiesKHR,
+) -> vk::Extent2D {
+    if capabilites.current_extent.width != u32::max_value() {
+        capabilites.current_extent
+    } else {
+        let (width, height) = window_size.into();
+
+        vk::Extent2D {
+            width: capabilites
+                .min_image_extent
+                .width
+                .max(capabilites.max_image_extent.width.min(width)),
+            height: capabilites
+                .min_image_extent
+                .height
+                .max(capabilites.max_image_extent.height.min(height)),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+// Images the renderer draws into besides the swapchain, created by the render graph
+
+use super::{
+    allocator::{Allocation, Allocator, MemoryUsage},
+    debug::DebugNames,
+    RendererInitError,
+};
+use ash::{
+    version::{DeviceV1_0, InstanceV1_0},
+    vk, Device, Instance,
+};
+
+// in order of preference
+const DEPTH_FORMATS: [vk::Format; 3] = [
+    vk::Format::D32_SFLOAT,
+    vk::Format::D32_SFLOAT_S8_UINT,
+    vk::Format::D24_UNORM_S8_UINT,
+];
+
+pub struct AttachmentImage {
+    pub image: vk::Image,
+    pub view: vk::ImageView,
+    allocation: Allocation,
+}
+
+impl AttachmentImage {
+    pub unsafe fn new(
+        device: &Device,
+        allocator: &mut Allocator,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        samples: vk::SampleCountFlags,
+        usage: vk::ImageUsageFlags,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> Result<Self, RendererInitError> {
+        let image_create_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(samples)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+
+        let (image, allocation) = allocator
+            .create_image(&image_create_info, MemoryUsage::GpuOnly)
+            .map_err(|err| RendererInitError::AllocationError { err })?;
+
+        let view_create_info = vk::ImageViewCreateInfo::builder()
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image(image);
+
+        let view = match device.create_image_view(&view_create_info, None) {
+            Ok(view) => view,
+            Err(err) => {
+                allocator.destroy_image(image, allocation);
+                return Err(RendererInitError::RenderTargetError { err });
+            }
+        };
+
+        Ok(AttachmentImage {
+            image,
+            view,
+            allocation,
+        })
+    }
+
+    pub unsafe fn name(&self, debug_names: &DebugNames, name: &str) {
+        debug_names.name(self.image, name);
+        debug_names.name(self.view, &format!("{} view", name));
+    }
+
+    pub unsafe fn destroy(self, device: &Device, allocator: &mut Allocator) {
+        device.destroy_image_view(self.view, None);
+        allocator.destroy_image(self.image, self.allocation);
+    }
+}
+
+pub unsafe fn choose_depth_format(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> Option<vk::Format> {
+    DEPTH_FORMATS.iter().cloned().find(|&format| {
+        instance
+            .get_physical_device_format_properties(physical_device, format)
+            .optimal_tiling_features
+            .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+    })
+}
+
+// highest supported sample count that isn't above the requested one
+pub fn choose_sample_count(
+    limits: &vk::PhysicalDeviceLimits,
+    requested: u32,
+    depth: bool,
+) -> vk::SampleCountFlags {
+    let mut supported = limits.framebuffer_color_sample_counts;
+    if depth {
+        supported &= limits.framebuffer_depth_sample_counts;
+    }
+
+    [
+        vk::SampleCountFlags::TYPE_64,
+        vk::SampleCountFlags::TYPE_32,
+        vk::SampleCountFlags::TYPE_16,
+        vk::SampleCountFlags::TYPE_8,
+        vk::SampleCountFlags::TYPE_4,
+        vk::SampleCountFlags::TYPE_2,
+    ]
+    .iter()
+    .cloned()
+    .find(|&samples| samples.as_raw() <= requested && supported.contains(samples))
+    .unwrap_or(vk::SampleCountFlags::TYPE_1)
+}
@@ -0,0 +1,617 @@
+// Text pass
+//
+// Text is laid out on the CPU every frame and drawn with the sprite pipeline, one quad per
+// glyph sampling the font's atlas. Glyphs missing from an atlas are rasterized while
+// preparing the frame and copied into its image before the main render pass.
+// World space text is drawn after the sprites, screen space text last over the whole window.
+
+use super::{
+    allocator::{Allocator, LinearAllocator},
+    backend::TextDraw,
+    debug::DebugNames,
+    font::{self, Font, GlyphAtlas, ATLAS_SIZE, WORLD_RASTER_SIZE},
+    sprites::{self, SpriteBatch, SpriteInstance, SpritePass},
+    targets::AttachmentImage,
+    texture,
+    uniforms::{CameraMatrices, FrameUniforms},
+    RendererInitError,
+};
+use crate::{
+    components::{Text, TextSpace, Viewport},
+    debug_draw::DebugLabel,
+    resources::{Resource, ResourceState},
+};
+use ash::{version::DeviceV1_0, vk, Device};
+use fnv::FnvHashMap;
+use log::warn;
+use nalgebra_glm as glm;
+use rusttype::{PositionedGlyph, Rect};
+use std::{mem, sync::Arc};
+use winit::dpi::LogicalSize;
+
+// in logical pixels
+const DEBUG_LABEL_SIZE: f32 = 16.0;
+// per frame in flight, glyphs after that are dropped
+const MAX_GLYPHS: usize = 16384;
+// per frame in flight, enough for a whole atlas
+const STAGING_SIZE: vk::DeviceSize = (ATLAS_SIZE * ATLAS_SIZE * 4) as vk::DeviceSize;
+
+// (font index, (staging offset, atlas region))
+type AtlasUploads = Vec<(usize, Vec<(vk::DeviceSize, Rect<u32>)>)>;
+
+struct FontEntry {
+    // a loaded font resource
+    resource: Arc<ResourceState>,
+    atlas: GlyphAtlas,
+    image: AttachmentImage,
+    descriptor_set: vk::DescriptorSet,
+}
+
+impl FontEntry {
+    fn font(&self) -> &Font {
+        match &*self.resource {
+            ResourceState::Loaded(Resource::Font(font)) => font,
+            _ => unreachable!(),
+        }
+    }
+}
+
+// the glyphs of one frame, ready to be recorded
+#[derive(Debug, Default)]
+pub struct TextBatches {
+    buffer: vk::Buffer,
+    offset: vk::DeviceSize,
+    // batch textures are font indices
+    world: Vec<SpriteBatch>,
+    screen: Vec<SpriteBatch>,
+    uploads: AtlasUploads,
+    staging: vk::Buffer,
+}
+
+impl TextBatches {
+    pub fn has_uploads(&self) -> bool {
+        !self.uploads.is_empty()
+    }
+}
+
+pub struct TextPass {
+    // font name -> index into fonts
+    font_indices: FnvHashMap<String, usize>,
+    fonts: Vec<FontEntry>,
+    descriptor_pool: vk::DescriptorPool,
+    // logical pixels from the top left corner of the window
+    screen_uniforms: FrameUniforms,
+    instances: LinearAllocator,
+    staging: LinearAllocator,
+    // the last frame had too many glyphs, so the warning isn't logged every frame
+    overflowed: bool,
+}
+
+impl TextPass {
+    // uploads the atlases built on the loader threads, waits until that finished
+    pub unsafe fn new(
+        device: &Device,
+        allocator: &mut Allocator,
+        queue: vk::Queue,
+        queue_family_index: u32,
+        // (name, loaded font resource)
+        fonts: Vec<(String, Arc<ResourceState>)>,
+        sprites: &SpritePass,
+        frames_in_flight: usize,
+    ) -> Result<Self, RendererInitError> {
+        let descriptor_error = |err| RendererInitError::DescriptorCreationError { err };
+
+        let mut atlases = fonts
+            .iter()
+            .map(|(_, resource)| match &**resource {
+                ResourceState::Loaded(Resource::Font(font)) => font.take_atlas(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        let atlas_textures = atlases
+            .iter_mut()
+            .map(GlyphAtlas::texture)
+            .collect::<Vec<_>>();
+        let images = texture::upload_textures(
+            device,
+            allocator,
+            queue,
+            queue_family_index,
+            &atlas_textures.iter().collect::<Vec<_>>(),
+        )?;
+
+        // pools can't be empty
+        let set_count = fonts.len().max(1) as u32;
+        let pool_sizes = [
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::SAMPLED_IMAGE,
+                descriptor_count: set_count,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::SAMPLER,
+                descriptor_count: set_count,
+            },
+        ];
+        let descriptor_pool_create_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&pool_sizes)
+            .max_sets(set_count);
+        let descriptor_pool = device
+            .create_descriptor_pool(&descriptor_pool_create_info, None)
+            .map_err(descriptor_error)?;
+
+        let descriptor_sets = if fonts.is_empty() {
+            Vec::new()
+        } else {
+            let set_layouts = vec![sprites.descriptor_set_layout(); fonts.len()];
+            let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&set_layouts);
+
+            device
+                .allocate_descriptor_sets(&descriptor_set_allocate_info)
+                .map_err(descriptor_error)?
+        };
+
+        for (&descriptor_set, image) in descriptor_sets.iter().zip(&images) {
+            sprites::write_texture_set(device, descriptor_set, image.view, sprites.sampler());
+        }
+
+        let mut font_indices = FnvHashMap::default();
+        let mut entries = Vec::with_capacity(fonts.len());
+        for (index, ((((name, resource), atlas), image), descriptor_set)) in fonts
+            .into_iter()
+            .zip(atlases)
+            .zip(images)
+            .zip(descriptor_sets)
+            .enumerate()
+        {
+            font_indices.insert(name, index);
+            entries.push(FontEntry {
+                resource,
+                atlas,
+                image,
+                descriptor_set,
+            });
+        }
+
+        let screen_uniforms = FrameUniforms::new(device, allocator, frames_in_flight)?;
+
+        let instances = LinearAllocator::new(
+            allocator,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            (MAX_GLYPHS * mem::size_of::<SpriteInstance>()) as vk::DeviceSize,
+            frames_in_flight,
+        )
+        .map_err(|err| RendererInitError::AllocationError { err })?;
+        let staging = LinearAllocator::new(
+            allocator,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            STAGING_SIZE,
+            frames_in_flight,
+        )
+        .map_err(|err| RendererInitError::AllocationError { err })?;
+
+        Ok(TextPass {
+            font_indices,
+            fonts: entries,
+            descriptor_pool,
+            screen_uniforms,
+            instances,
+            staging,
+            overflowed: false,
+        })
+    }
+
+    // screen space text at the projected label positions, drawn with the first font,
+    // labels behind the camera and all labels without a font are skipped
+    pub fn label_texts(
+        &self,
+        labels: &[DebugLabel],
+        camera: &CameraMatrices,
+        window_size: LogicalSize,
+    ) -> Vec<(Text, glm::Vec2)> {
+        let font = match self.font_indices.iter().find(|(_, index)| **index == 0) {
+            Some((font, _)) => font,
+            None => return Vec::new(),
+        };
+
+        labels
+            .iter()
+            .filter_map(|label| {
+                let position = project(&label.position, camera, window_size)?;
+                let mut text = Text::screen(&label.text, font, DEBUG_LABEL_SIZE);
+                text.color = label.color;
+
+                Some((text, position))
+            })
+            .collect()
+    }
+
+    // lays out the text, caches missing glyphs and writes the instances of `frame`,
+    // whose fence has to be waited on, text with unknown fonts is skipped
+    pub fn prepare(
+        &mut self,
+        frame: usize,
+        texts: &[TextDraw],
+        window_size: LogicalSize,
+    ) -> TextBatches {
+        self.screen_uniforms.update(
+            frame,
+            &CameraMatrices {
+                view: glm::Mat4::identity(),
+                projection: glm::ortho_rh_zo(
+                    0.0,
+                    window_size.width as f32,
+                    0.0,
+                    window_size.height as f32,
+                    -1.0,
+                    1.0,
+                ),
+                ..CameraMatrices::default()
+            },
+        );
+
+        let mut texts = texts
+            .iter()
+            .filter_map(|draw| Some((*self.font_indices.get(&draw.text.font)?, draw)))
+            .collect::<Vec<_>>();
+        // stable, world space text first, then by font
+        texts.sort_by_key(|(font, draw)| (draw.text.space == TextSpace::Screen, *font));
+
+        // (font index, space, glyphs, scale from pixels to the text's space)
+        let laid_out = texts
+            .iter()
+            .map(|(font, draw)| {
+                let text = draw.text;
+                let (raster_size, scale) = match text.space {
+                    TextSpace::Screen => (text.size, 1.0),
+                    TextSpace::World => (WORLD_RASTER_SIZE, text.size / WORLD_RASTER_SIZE),
+                };
+                let glyphs = font::layout(
+                    self.fonts[*font].font().font(),
+                    &text.text,
+                    raster_size,
+                    text.align,
+                    text.wrap_width.map(|wrap_width| wrap_width / scale),
+                );
+
+                (*font, draw, glyphs, scale)
+            })
+            .collect::<Vec<_>>();
+
+        // all glyphs of a font have to be queued at once, the others may get evicted
+        let mut font_glyphs = vec![Vec::<PositionedGlyph<'static>>::new(); self.fonts.len()];
+        for (font, _, glyphs, _) in &laid_out {
+            font_glyphs[*font].extend(glyphs.iter().cloned());
+        }
+        for (entry, glyphs) in self.fonts.iter_mut().zip(&font_glyphs) {
+            if glyphs.is_empty() {
+                continue;
+            }
+            if let Err(err) = entry.atlas.cache(glyphs) {
+                warn!("Failed to cache glyphs: {}", err);
+            }
+        }
+
+        let uploads = self.stage_uploads(frame);
+
+        let mut instances = Vec::new();
+        let mut world = Vec::<SpriteBatch>::new();
+        let mut screen = Vec::<SpriteBatch>::new();
+        for (font, draw, glyphs, scale) in &laid_out {
+            let batches = match draw.text.space {
+                TextSpace::World => &mut world,
+                TextSpace::Screen => &mut screen,
+            };
+
+            for glyph in glyphs {
+                let (uv, pixels) = match self.fonts[*font].atlas.rect_for(glyph) {
+                    Some(rect) => rect,
+                    None => continue,
+                };
+                if instances.len() == MAX_GLYPHS {
+                    break;
+                }
+
+                let uv = [uv.min.x, uv.min.y, uv.width(), uv.height()];
+                let (width, height) = (pixels.width() as f32, pixels.height() as f32);
+                // the quad grows up from its position, Y points down on screen and up in the world
+                let (position, size) = match draw.text.space {
+                    TextSpace::Screen => (
+                        glm::vec2(pixels.min.x as f32, pixels.max.y as f32),
+                        glm::vec2(width, -height),
+                    ),
+                    TextSpace::World => (
+                        glm::vec2(pixels.min.x as f32, -pixels.max.y as f32) * *scale,
+                        glm::vec2(width, height) * *scale,
+                    ),
+                };
+
+                instances.push(SpriteInstance::new(
+                    draw.position + position,
+                    size,
+                    0.0,
+                    glm::vec2(0.0, 0.0),
+                    uv,
+                    draw.text.color,
+                ));
+                match batches.last_mut() {
+                    Some(batch) if batch.texture == *font => batch.instance_count += 1,
+                    _ => batches.push(SpriteBatch {
+                        texture: *font,
+                        first_instance: instances.len() as u32 - 1,
+                        instance_count: 1,
+                    }),
+                }
+            }
+        }
+
+        let overflowed = instances.len() == MAX_GLYPHS;
+        if overflowed && !self.overflowed {
+            warn!("Too many glyphs in one frame, only drawing {}", MAX_GLYPHS);
+        }
+        self.overflowed = overflowed;
+
+        let mut batches = TextBatches {
+            uploads,
+            staging: self.staging.buffer(),
+            ..TextBatches::default()
+        };
+        if instances.is_empty() {
+            return batches;
+        }
+
+        self.instances.reset(frame);
+        if let Some(allocation) = self.instances.allocate(
+            mem::size_of_val(instances.as_slice()) as vk::DeviceSize,
+            mem::align_of::<SpriteInstance>() as vk::DeviceSize,
+        ) {
+            allocation.write(&instances);
+            batches.buffer = allocation.buffer;
+            batches.offset = allocation.offset;
+            batches.world = world;
+            batches.screen = screen;
+        }
+
+        batches
+    }
+
+    // copies the regions written since the last frame into the staging buffer
+    fn stage_uploads(&mut self, frame: usize) -> AtlasUploads {
+        self.staging.reset(frame);
+
+        let mut uploads = Vec::new();
+        for (index, entry) in self.fonts.iter_mut().enumerate() {
+            let mut regions = Vec::new();
+            for (rect, pixels) in entry.atlas.take_dirty() {
+                match self.staging.allocate(pixels.len() as vk::DeviceSize, 4) {
+                    Some(allocation) => {
+                        allocation.write(&pixels);
+                        regions.push((allocation.offset, rect));
+                    }
+                    None => {
+                        // uploaded with the next frame instead
+                        entry.atlas.invalidate();
+                        break;
+                    }
+                }
+            }
+
+            if !regions.is_empty() {
+                uploads.push((index, regions));
+            }
+        }
+
+        uploads
+    }
+
+    // has to be recorded outside of a render pass, before the text is drawn
+    pub unsafe fn record_uploads(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        batches: &TextBatches,
+    ) {
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+
+        for (font, regions) in &batches.uploads {
+            let image = self.fonts[*font].image.image;
+
+            // also waits for earlier frames still sampling the atlas
+            let to_transfer = vk::ImageMemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::empty())
+                .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .old_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(subresource_range)
+                .build();
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_transfer],
+            );
+
+            let copy_regions = regions
+                .iter()
+                .map(|(offset, rect)| {
+                    vk::BufferImageCopy::builder()
+                        .buffer_offset(*offset)
+                        .image_subresource(vk::ImageSubresourceLayers {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            mip_level: 0,
+                            base_array_layer: 0,
+                            layer_count: 1,
+                        })
+                        .image_offset(vk::Offset3D {
+                            x: rect.min.x as i32,
+                            y: rect.min.y as i32,
+                            z: 0,
+                        })
+                        .image_extent(vk::Extent3D {
+                            width: rect.width(),
+                            height: rect.height(),
+                            depth: 1,
+                        })
+                        .build()
+                })
+                .collect::<Vec<_>>();
+            device.cmd_copy_buffer_to_image(
+                command_buffer,
+                batches.staging,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &copy_regions,
+            );
+
+            let to_shader = vk::ImageMemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(subresource_range)
+                .build();
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_shader],
+            );
+        }
+    }
+
+    // has to be recorded inside of the main render pass, with the camera viewport set
+    pub unsafe fn record_world(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        sprites: &SpritePass,
+        camera_set: vk::DescriptorSet,
+        batches: &TextBatches,
+    ) {
+        self.record_batches(
+            device,
+            command_buffer,
+            sprites,
+            camera_set,
+            batches,
+            &batches.world,
+        );
+    }
+
+    // has to be recorded inside of the main render pass, with a viewport over the whole window
+    pub unsafe fn record_screen(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        sprites: &SpritePass,
+        frame: usize,
+        batches: &TextBatches,
+    ) {
+        self.record_batches(
+            device,
+            command_buffer,
+            sprites,
+            self.screen_uniforms.descriptor_set(frame),
+            batches,
+            &batches.screen,
+        );
+    }
+
+    unsafe fn record_batches(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        sprites: &SpritePass,
+        camera_set: vk::DescriptorSet,
+        batches: &TextBatches,
+        font_batches: &[SpriteBatch],
+    ) {
+        if font_batches.is_empty() {
+            return;
+        }
+
+        sprites.bind(
+            device,
+            command_buffer,
+            camera_set,
+            batches.buffer,
+            batches.offset,
+        );
+        for batch in font_batches {
+            sprites.draw(
+                device,
+                command_buffer,
+                self.fonts[batch.texture].descriptor_set,
+                batch,
+            );
+        }
+    }
+
+    pub unsafe fn name(&self, debug_names: &DebugNames) {
+        for (name, index) in &self.font_indices {
+            let entry = &self.fonts[*index];
+            entry
+                .image
+                .name(debug_names, &format!("{} glyph atlas", name));
+            debug_names.name(entry.descriptor_set, &format!("{} glyph atlas set", name));
+        }
+        debug_names.name(self.descriptor_pool, "glyph atlas pool");
+        debug_names.name(self.instances.buffer(), "glyph instances");
+        debug_names.name(self.staging.buffer(), "glyph staging");
+    }
+
+    pub unsafe fn destroy(&mut self, device: &Device, allocator: &mut Allocator) {
+        self.instances.destroy(allocator);
+        self.staging.destroy(allocator);
+        self.screen_uniforms.destroy(device, allocator);
+
+        device.destroy_descriptor_pool(self.descriptor_pool, None);
+
+        for entry in self.fonts.drain(..) {
+            entry.image.destroy(device, allocator);
+        }
+    }
+}
+
+// world position -> logical pixels from the top left corner of the window
+fn project(
+    position: &glm::Vec3,
+    camera: &CameraMatrices,
+    window_size: LogicalSize,
+) -> Option<glm::Vec2> {
+    let clip = camera.projection * camera.view * glm::vec4(position.x, position.y, position.z, 1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+
+    // Y points down in clip space
+    let Viewport {
+        x,
+        y,
+        width,
+        height,
+    } = camera.viewport;
+    Some(glm::vec2(
+        (x + (clip.x / clip.w + 1.0) / 2.0 * width) * window_size.width as f32,
+        (y + (clip.y / clip.w + 1.0) / 2.0 * height) * window_size.height as f32,
+    ))
+}
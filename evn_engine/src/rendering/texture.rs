@@ -0,0 +1,311 @@
+// Textures
+//
+// Texture resources are decoded on the loader threads and uploaded while the renderer
+// is created, all of them with a single submission.
+
+use super::{
+    allocator::{Allocator, MemoryUsage},
+    readback::Frame,
+    targets::AttachmentImage,
+    RendererInitError,
+};
+use ash::{prelude::VkResult, version::DeviceV1_0, vk, Device};
+use std::{io::Error as IoError, path::Path};
+
+pub const TEXTURE_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+
+// tightly packed RGBA8 rows from top to bottom
+#[derive(Debug, Clone, PartialEq)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Texture {
+    pub fn load_png(path: &Path) -> Result<Self, IoError> {
+        let frame = Frame::load_png(path)?;
+
+        Ok(Texture {
+            width: frame.width,
+            height: frame.height,
+            pixels: frame.pixels,
+        })
+    }
+}
+
+// the returned images are in SHADER_READ_ONLY_OPTIMAL, waits until the upload finished
+pub unsafe fn upload_textures(
+    device: &Device,
+    allocator: &mut Allocator,
+    queue: vk::Queue,
+    queue_family_index: u32,
+    textures: &[&Texture],
+) -> Result<Vec<AttachmentImage>, RendererInitError> {
+    if textures.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut images = Vec::with_capacity(textures.len());
+    for texture in textures {
+        let image = AttachmentImage::new(
+            device,
+            allocator,
+            vk::Extent2D {
+                width: texture.width,
+                height: texture.height,
+            },
+            TEXTURE_FORMAT,
+            vk::SampleCountFlags::TYPE_1,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            vk::ImageAspectFlags::COLOR,
+        );
+
+        match image {
+            Ok(image) => images.push(image),
+            Err(err) => {
+                for image in images {
+                    image.destroy(device, allocator);
+                }
+                return Err(err);
+            }
+        }
+    }
+
+    let staging_size = textures
+        .iter()
+        .map(|texture| texture.pixels.len() as vk::DeviceSize)
+        .sum();
+    let buffer_create_info = vk::BufferCreateInfo::builder()
+        .size(staging_size)
+        .usage(vk::BufferUsageFlags::TRANSFER_SRC)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+    let (staging_buffer, mut staging_allocation) =
+        match allocator.create_buffer(&buffer_create_info, MemoryUsage::CpuToGpu) {
+            Ok(staging) => staging,
+            Err(err) => {
+                for image in images {
+                    image.destroy(device, allocator);
+                }
+                return Err(RendererInitError::AllocationError { err });
+            }
+        };
+
+    let mut offsets = Vec::with_capacity(textures.len());
+    let mut offset = 0;
+    for texture in textures {
+        staging_allocation.write(offset, &texture.pixels);
+        offsets.push(offset);
+        offset += texture.pixels.len() as vk::DeviceSize;
+    }
+
+    let result = submit_once(device, queue, queue_family_index, |command_buffer| {
+        for ((texture, image), offset) in textures.iter().zip(&images).zip(&offsets) {
+            record_upload(
+                device,
+                command_buffer,
+                staging_buffer,
+                *offset,
+                texture,
+                image,
+                None,
+            );
+        }
+    })
+    .map_err(|err| RendererInitError::TextureUploadError { err });
+
+    allocator.destroy_buffer(staging_buffer, staging_allocation);
+
+    match result {
+        Ok(()) => Ok(images),
+        Err(err) => {
+            for image in images {
+                image.destroy(device, allocator);
+            }
+            Err(err)
+        }
+    }
+}
+
+// copies `texture` into a region of an uploaded image, waits until that finished
+pub unsafe fn update_texture(
+    device: &Device,
+    allocator: &mut Allocator,
+    queue: vk::Queue,
+    queue_family_index: u32,
+    image: &AttachmentImage,
+    offset: vk::Offset2D,
+    texture: &Texture,
+) -> Result<(), RendererInitError> {
+    let buffer_create_info = vk::BufferCreateInfo::builder()
+        .size(texture.pixels.len() as vk::DeviceSize)
+        .usage(vk::BufferUsageFlags::TRANSFER_SRC)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+    let (staging_buffer, mut staging_allocation) = allocator
+        .create_buffer(&buffer_create_info, MemoryUsage::CpuToGpu)
+        .map_err(|err| RendererInitError::AllocationError { err })?;
+    staging_allocation.write(0, &texture.pixels);
+
+    let result = submit_once(device, queue, queue_family_index, |command_buffer| {
+        record_upload(
+            device,
+            command_buffer,
+            staging_buffer,
+            0,
+            texture,
+            image,
+            Some(offset),
+        );
+    })
+    .map_err(|err| RendererInitError::TextureUploadError { err });
+
+    allocator.destroy_buffer(staging_buffer, staging_allocation);
+    result
+}
+
+// records the commands into a transient command buffer, submits it and waits for the queue
+unsafe fn submit_once(
+    device: &Device,
+    queue: vk::Queue,
+    queue_family_index: u32,
+    record: impl FnOnce(vk::CommandBuffer),
+) -> VkResult<()> {
+    let command_pool_create_info = vk::CommandPoolCreateInfo::builder()
+        .queue_family_index(queue_family_index)
+        .flags(vk::CommandPoolCreateFlags::TRANSIENT);
+    let command_pool = device.create_command_pool(&command_pool_create_info, None)?;
+
+    let result = record_and_submit(device, queue, command_pool, record);
+
+    // also frees the command buffer
+    device.destroy_command_pool(command_pool, None);
+    result
+}
+
+unsafe fn record_and_submit(
+    device: &Device,
+    queue: vk::Queue,
+    command_pool: vk::CommandPool,
+    record: impl FnOnce(vk::CommandBuffer),
+) -> VkResult<()> {
+    let command_buffer_alloc_info = vk::CommandBufferAllocateInfo::builder()
+        .command_pool(command_pool)
+        .level(vk::CommandBufferLevel::PRIMARY)
+        .command_buffer_count(1);
+    let command_buffer = device.allocate_command_buffers(&command_buffer_alloc_info)?[0];
+
+    let begin_info =
+        vk::CommandBufferBeginInfo::builder().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+    device.begin_command_buffer(command_buffer, &begin_info)?;
+    record(command_buffer);
+    device.end_command_buffer(command_buffer)?;
+
+    let submit_info = vk::SubmitInfo::builder()
+        .command_buffers(&[command_buffer])
+        .build();
+    device.queue_submit(queue, &[submit_info], vk::Fence::null())?;
+    device.queue_wait_idle(queue)
+}
+
+// the whole image if `image_offset` is None, otherwise only a region of an image that
+// was already uploaded and may be read by earlier commands
+unsafe fn record_upload(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    staging_buffer: vk::Buffer,
+    offset: vk::DeviceSize,
+    texture: &Texture,
+    image: &AttachmentImage,
+    image_offset: Option<vk::Offset2D>,
+) {
+    let subresource_range = vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    };
+
+    let (old_layout, src_stage) = match image_offset {
+        Some(_) => (
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+        ),
+        None => (
+            vk::ImageLayout::UNDEFINED,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+        ),
+    };
+
+    let to_transfer = vk::ImageMemoryBarrier::builder()
+        .src_access_mask(vk::AccessFlags::empty())
+        .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .old_layout(old_layout)
+        .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image.image)
+        .subresource_range(subresource_range)
+        .build();
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        src_stage,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[to_transfer],
+    );
+
+    let region = vk::BufferImageCopy::builder()
+        .buffer_offset(offset)
+        .image_subresource(vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        })
+        .image_offset({
+            let offset = image_offset.unwrap_or(vk::Offset2D { x: 0, y: 0 });
+            vk::Offset3D {
+                x: offset.x,
+                y: offset.y,
+                z: 0,
+            }
+        })
+        .image_extent(vk::Extent3D {
+            width: texture.width,
+            height: texture.height,
+            depth: 1,
+        })
+        .build();
+    device.cmd_copy_buffer_to_image(
+        command_buffer,
+        staging_buffer,
+        image.image,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        &[region],
+    );
+
+    let to_shader = vk::ImageMemoryBarrier::builder()
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ)
+        .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image.image)
+        .subresource_range(subresource_range)
+        .build();
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::FRAGMENT_SHADER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[to_shader],
+    );
+}
@@ -0,0 +1,170 @@
+// GPU timestamps
+//
+// Every frame in flight has its own range of queries, two per render pass. They're reset
+// at the start of the frame's command buffer and resolved after its fence was waited on,
+// which is `frames_in_flight` frames later.
+
+use super::{debug::DebugNames, RendererInitError};
+use crate::profiler::PassTiming;
+use ash::{
+    version::{DeviceV1_0, InstanceV1_0},
+    vk, Device, Instance,
+};
+use std::time::Duration;
+
+// passes after that still get CPU timings
+const MAX_PASSES: usize = 16;
+const QUERIES_PER_FRAME: u32 = MAX_PASSES as u32 * 2;
+
+pub struct Timestamps {
+    // None if the graphics queue doesn't support timestamps
+    query_pool: Option<vk::QueryPool>,
+    // nanoseconds per tick
+    period: f64,
+    valid_mask: u64,
+    // per frame in flight, the recorded passes with their CPU time
+    passes: Vec<Vec<(String, Duration)>>,
+}
+
+impl Timestamps {
+    pub unsafe fn new(
+        instance: &Instance,
+        device: &Device,
+        physical_device: vk::PhysicalDevice,
+        graphics_family_index: u32,
+        frames_in_flight: usize,
+    ) -> Result<Self, RendererInitError> {
+        let limits = instance
+            .get_physical_device_properties(physical_device)
+            .limits;
+        let valid_bits = instance.get_physical_device_queue_family_properties(physical_device)
+            [graphics_family_index as usize]
+            .timestamp_valid_bits;
+
+        // only the graphics queue is timed, timestamp_compute_and_graphics is about all of them
+        let query_pool = if valid_bits > 0 {
+            let create_info = vk::QueryPoolCreateInfo::builder()
+                .query_type(vk::QueryType::TIMESTAMP)
+                .query_count(QUERIES_PER_FRAME * frames_in_flight as u32);
+
+            Some(
+                device
+                    .create_query_pool(&create_info, None)
+                    .map_err(|err| RendererInitError::QueryPoolCreationError { err })?,
+            )
+        } else {
+            None
+        };
+
+        Ok(Timestamps {
+            query_pool,
+            period: f64::from(limits.timestamp_period),
+            valid_mask: if valid_bits >= 64 {
+                !0
+            } else {
+                (1 << valid_bits) - 1
+            },
+            passes: vec![Vec::new(); frames_in_flight],
+        })
+    }
+
+    // has to be recorded outside of a render pass
+    pub unsafe fn reset(&self, device: &Device, command_buffer: vk::CommandBuffer, frame: usize) {
+        if let Some(query_pool) = self.query_pool {
+            device.cmd_reset_query_pool(
+                command_buffer,
+                query_pool,
+                frame as u32 * QUERIES_PER_FRAME,
+                QUERIES_PER_FRAME,
+            );
+        }
+    }
+
+    // `pass` is the index of the pass in this frame
+    pub unsafe fn write(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        pass: usize,
+        end: bool,
+    ) {
+        if let (Some(query_pool), true) = (self.query_pool, pass < MAX_PASSES) {
+            let (stage, offset) = if end {
+                (vk::PipelineStageFlags::BOTTOM_OF_PIPE, 1)
+            } else {
+                (vk::PipelineStageFlags::TOP_OF_PIPE, 0)
+            };
+
+            device.cmd_write_timestamp(
+                command_buffer,
+                stage,
+                query_pool,
+                frame as u32 * QUERIES_PER_FRAME + pass as u32 * 2 + offset,
+            );
+        }
+    }
+
+    // the passes recorded into the frame's command buffer
+    pub fn submitted(&mut self, frame: usize, passes: Vec<(String, Duration)>) {
+        self.passes[frame] = passes;
+    }
+
+    // the frame's fence has to be signaled, None if nothing was submitted since the last call
+    pub unsafe fn resolve(&mut self, device: &Device, frame: usize) -> Option<Vec<PassTiming>> {
+        let passes = std::mem::take(&mut self.passes[frame]);
+        if passes.is_empty() {
+            return None;
+        }
+
+        let timed_passes = passes.len().min(MAX_PASSES);
+        let mut results = vec![0u64; timed_passes * 2];
+        let gpu_times = match self.query_pool {
+            Some(query_pool) => device
+                .get_query_pool_results(
+                    query_pool,
+                    frame as u32 * QUERIES_PER_FRAME,
+                    results.len() as u32,
+                    &mut results,
+                    vk::QueryResultFlags::TYPE_64,
+                )
+                .ok()
+                .map(|()| {
+                    results
+                        .chunks(2)
+                        .map(|pair| {
+                            let ticks = pair[1].wrapping_sub(pair[0]) & self.valid_mask;
+                            Duration::from_nanos((ticks as f64 * self.period) as u64)
+                        })
+                        .collect::<Vec<_>>()
+                }),
+            None => None,
+        };
+
+        Some(
+            passes
+                .into_iter()
+                .enumerate()
+                .map(|(index, (name, cpu))| PassTiming {
+                    name,
+                    cpu,
+                    gpu: gpu_times
+                        .as_ref()
+                        .and_then(|gpu_times| gpu_times.get(index).cloned()),
+                })
+                .collect(),
+        )
+    }
+
+    pub unsafe fn name(&self, debug_names: &DebugNames) {
+        if let Some(query_pool) = self.query_pool {
+            debug_names.name(query_pool, "timestamp query pool");
+        }
+    }
+
+    pub unsafe fn destroy(&self, device: &Device) {
+        if let Some(query_pool) = self.query_pool {
+            device.destroy_query_pool(query_pool, None);
+        }
+    }
+}
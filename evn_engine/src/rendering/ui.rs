@@ -0,0 +1,616 @@
+// Developer UI pass
+//
+// Draws the meshes of the egui overlay last, over the whole window, with one scissor rect
+// per mesh. egui's textures (mostly its font atlas) are created and updated before the
+// frame is recorded, full replacements wait for the queue since the old image may be in use.
+// Vertices and indices are rewritten every frame like the other passes' instances.
+
+use super::{
+    allocator::{Allocator, LinearAllocator},
+    debug::DebugNames,
+    pipeline::{self, BlendMode, PipelineTarget},
+    sprites::{self, SpritePass},
+    targets::AttachmentImage,
+    texture::{self, Texture},
+    RendererInitError,
+};
+use crate::dev_ui::UiFrame;
+use ash::{version::DeviceV1_0, vk, Device};
+use egui::{
+    epaint::{ImageData, ImageDelta, Primitive, Vertex},
+    Rect, TextureId,
+};
+use fnv::FnvHashMap;
+use log::warn;
+use std::{ffi::CString, mem};
+use winit::dpi::LogicalSize;
+
+// per frame in flight, meshes after that are dropped
+const MAX_VERTICES: usize = 262_144;
+const MAX_INDICES: usize = MAX_VERTICES * 3;
+// egui only needs one for its fonts, the rest is for images shown in the UI
+const MAX_TEXTURES: u32 = 16;
+
+const VERTEX_SHADER: &[u8] = include_bytes!("../../../resources/closed/shaders/ui.vert.spv");
+const FRAGMENT_SHADER: &[u8] = include_bytes!("../../../resources/closed/shaders/ui.frag.spv");
+
+struct UiTexture {
+    image: AttachmentImage,
+    descriptor_set: vk::DescriptorSet,
+}
+
+struct UiDraw {
+    // in logical pixels
+    clip_rect: Rect,
+    texture: u64,
+    first_index: u32,
+    index_count: u32,
+    vertex_offset: i32,
+}
+
+// the meshes of one frame, ready to be recorded
+#[derive(Default)]
+pub struct UiBatches {
+    vertex_buffer: vk::Buffer,
+    vertex_offset: vk::DeviceSize,
+    index_buffer: vk::Buffer,
+    index_offset: vk::DeviceSize,
+    // logical pixels of the window
+    screen_size: [f32; 2],
+    draws: Vec<UiDraw>,
+}
+
+pub struct UiPass {
+    // set 0 is a sprite texture, the screen size is a push constant
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    descriptor_pool: vk::DescriptorPool,
+    // egui's managed texture ids
+    textures: FnvHashMap<u64, UiTexture>,
+    // freed by egui, destroyed once the frames drawing them finished
+    pending_frees: Vec<u64>,
+    vertices: LinearAllocator,
+    indices: LinearAllocator,
+    // the last frame had too many vertices, so the warning isn't logged every frame
+    overflowed: bool,
+}
+
+impl UiPass {
+    pub unsafe fn new(
+        device: &Device,
+        allocator: &mut Allocator,
+        sprites: &SpritePass,
+        target: &PipelineTarget,
+        pipeline_cache: vk::PipelineCache,
+        frames_in_flight: usize,
+    ) -> Result<Self, RendererInitError> {
+        let set_layouts = [sprites.descriptor_set_layout()];
+        let push_constant_ranges = [vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            offset: 0,
+            size: mem::size_of::<[f32; 2]>() as u32,
+        }];
+        let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(&push_constant_ranges);
+        let pipeline_layout = device
+            .create_pipeline_layout(&pipeline_layout_create_info, None)
+            .map_err(|err| RendererInitError::PipelineCreationError { err })?;
+
+        let pipeline = create_pipeline(device, pipeline_layout, target, pipeline_cache)?;
+
+        let pool_sizes = [
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::SAMPLED_IMAGE,
+                descriptor_count: MAX_TEXTURES,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::SAMPLER,
+                descriptor_count: MAX_TEXTURES,
+            },
+        ];
+        let descriptor_pool_create_info = vk::DescriptorPoolCreateInfo::builder()
+            .flags(vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
+            .pool_sizes(&pool_sizes)
+            .max_sets(MAX_TEXTURES);
+        let descriptor_pool = device
+            .create_descriptor_pool(&descriptor_pool_create_info, None)
+            .map_err(|err| RendererInitError::DescriptorCreationError { err })?;
+
+        let vertices = LinearAllocator::new(
+            allocator,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            (MAX_VERTICES * mem::size_of::<Vertex>()) as vk::DeviceSize,
+            frames_in_flight,
+        )
+        .map_err(|err| RendererInitError::AllocationError { err })?;
+        let indices = LinearAllocator::new(
+            allocator,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+            (MAX_INDICES * mem::size_of::<u32>()) as vk::DeviceSize,
+            frames_in_flight,
+        )
+        .map_err(|err| RendererInitError::AllocationError { err })?;
+
+        Ok(UiPass {
+            pipeline_layout,
+            pipeline,
+            descriptor_pool,
+            textures: FnvHashMap::default(),
+            pending_frees: Vec::new(),
+            vertices,
+            indices,
+            overflowed: false,
+        })
+    }
+
+    // applies the texture changes of the frame, waits until they're uploaded
+    pub unsafe fn update_textures(
+        &mut self,
+        device: &Device,
+        allocator: &mut Allocator,
+        queue: vk::Queue,
+        queue_family_index: u32,
+        sprites: &SpritePass,
+        frame: &UiFrame,
+    ) {
+        if !self.pending_frees.is_empty() {
+            if let Err(err) = device.queue_wait_idle(queue) {
+                warn!("Failed to wait for the queue: {}", err);
+                return;
+            }
+
+            for id in self.pending_frees.drain(..) {
+                if let Some(texture) = self.textures.remove(&id) {
+                    destroy_texture(device, allocator, self.descriptor_pool, texture);
+                }
+            }
+        }
+
+        for (id, delta) in &frame.textures.set {
+            let id = match *id {
+                TextureId::Managed(id) => id,
+                // never created by the developer UI
+                TextureId::User(_) => continue,
+            };
+
+            let result = match delta.pos {
+                Some([x, y]) => match self.textures.get(&id) {
+                    Some(ui_texture) => texture::update_texture(
+                        device,
+                        allocator,
+                        queue,
+                        queue_family_index,
+                        &ui_texture.image,
+                        vk::Offset2D {
+                            x: x as i32,
+                            y: y as i32,
+                        },
+                        &convert_image(delta),
+                    ),
+                    None => continue,
+                },
+                None => self.replace_texture(
+                    device,
+                    allocator,
+                    queue,
+                    queue_family_index,
+                    sprites,
+                    (id, delta),
+                ),
+            };
+
+            if let Err(err) = result {
+                warn!("Failed to update UI texture {}: {}", id, err);
+            }
+        }
+
+        self.pending_frees
+            .extend(frame.textures.free.iter().filter_map(|id| match *id {
+                TextureId::Managed(id) => Some(id),
+                TextureId::User(_) => None,
+            }));
+    }
+
+    unsafe fn replace_texture(
+        &mut self,
+        device: &Device,
+        allocator: &mut Allocator,
+        queue: vk::Queue,
+        queue_family_index: u32,
+        sprites: &SpritePass,
+        (id, delta): (u64, &ImageDelta),
+    ) -> Result<(), RendererInitError> {
+        if let Some(texture) = self.textures.remove(&id) {
+            device
+                .queue_wait_idle(queue)
+                .map_err(|err| RendererInitError::TextureUploadError { err })?;
+            destroy_texture(device, allocator, self.descriptor_pool, texture);
+        }
+
+        let image = texture::upload_textures(
+            device,
+            allocator,
+            queue,
+            queue_family_index,
+            &[&convert_image(delta)],
+        )?
+        .remove(0);
+
+        let set_layouts = [sprites.descriptor_set_layout()];
+        let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.descriptor_pool)
+            .set_layouts(&set_layouts);
+        let descriptor_set = match device.allocate_descriptor_sets(&descriptor_set_allocate_info) {
+            Ok(descriptor_sets) => descriptor_sets[0],
+            Err(err) => {
+                image.destroy(device, allocator);
+                return Err(RendererInitError::DescriptorCreationError { err });
+            }
+        };
+        sprites::write_texture_set(device, descriptor_set, image.view, sprites.sampler());
+
+        self.textures.insert(
+            id,
+            UiTexture {
+                image,
+                descriptor_set,
+            },
+        );
+
+        Ok(())
+    }
+
+    // writes the meshes of `frame`, whose fence has to be waited on,
+    // meshes with unknown textures are skipped
+    pub fn prepare(
+        &mut self,
+        frame: usize,
+        ui: Option<&UiFrame>,
+        window_size: LogicalSize,
+    ) -> UiBatches {
+        let ui = match ui {
+            Some(ui) => ui,
+            None => return UiBatches::default(),
+        };
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut draws = Vec::new();
+        let mut overflowed = false;
+        for clipped in &ui.primitives {
+            let mesh = match &clipped.primitive {
+                Primitive::Mesh(mesh) => mesh,
+                Primitive::Callback(_) => continue,
+            };
+            let texture = match mesh.texture_id {
+                TextureId::Managed(id) if self.textures.contains_key(&id) => id,
+                _ => continue,
+            };
+            if mesh.indices.is_empty() {
+                continue;
+            }
+
+            if vertices.len() + mesh.vertices.len() > MAX_VERTICES
+                || indices.len() + mesh.indices.len() > MAX_INDICES
+            {
+                overflowed = true;
+                break;
+            }
+
+            draws.push(UiDraw {
+                clip_rect: clipped.clip_rect,
+                texture,
+                first_index: indices.len() as u32,
+                index_count: mesh.indices.len() as u32,
+                vertex_offset: vertices.len() as i32,
+            });
+            vertices.extend_from_slice(&mesh.vertices);
+            indices.extend_from_slice(&mesh.indices);
+        }
+
+        if overflowed && !self.overflowed {
+            warn!(
+                "The developer UI has too many vertices, only drawing {} of them",
+                vertices.len()
+            );
+        }
+        self.overflowed = overflowed;
+
+        if draws.is_empty() {
+            return UiBatches::default();
+        }
+
+        self.vertices.reset(frame);
+        self.indices.reset(frame);
+        let vertex_allocation = self.vertices.allocate(
+            mem::size_of_val(vertices.as_slice()) as vk::DeviceSize,
+            mem::align_of::<Vertex>() as vk::DeviceSize,
+        );
+        let index_allocation = self.indices.allocate(
+            mem::size_of_val(indices.as_slice()) as vk::DeviceSize,
+            mem::align_of::<u32>() as vk::DeviceSize,
+        );
+        let (vertex_allocation, index_allocation) = match (vertex_allocation, index_allocation) {
+            (Some(vertex_allocation), Some(index_allocation)) => {
+                (vertex_allocation, index_allocation)
+            }
+            _ => return UiBatches::default(),
+        };
+        vertex_allocation.write(&vertices);
+        index_allocation.write(&indices);
+
+        UiBatches {
+            vertex_buffer: vertex_allocation.buffer,
+            vertex_offset: vertex_allocation.offset,
+            index_buffer: index_allocation.buffer,
+            index_offset: index_allocation.offset,
+            screen_size: [window_size.width as f32, window_size.height as f32],
+            draws,
+        }
+    }
+
+    // has to be recorded inside of the main render pass with the viewport covering
+    // the whole framebuffer, changes the scissor
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        extent: vk::Extent2D,
+        batches: &UiBatches,
+    ) {
+        if batches.draws.is_empty() {
+            return;
+        }
+
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+        device.cmd_push_constants(
+            command_buffer,
+            self.pipeline_layout,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            as_bytes(&batches.screen_size),
+        );
+        device.cmd_bind_vertex_buffers(
+            command_buffer,
+            0,
+            &[batches.vertex_buffer],
+            &[batches.vertex_offset],
+        );
+        device.cmd_bind_index_buffer(
+            command_buffer,
+            batches.index_buffer,
+            batches.index_offset,
+            vk::IndexType::UINT32,
+        );
+
+        // framebuffer pixels per logical pixel
+        let scale_x = extent.width as f32 / batches.screen_size[0];
+        let scale_y = extent.height as f32 / batches.screen_size[1];
+
+        for draw in &batches.draws {
+            let min_x = (draw.clip_rect.min.x * scale_x).round().max(0.0) as u32;
+            let min_y = (draw.clip_rect.min.y * scale_y).round().max(0.0) as u32;
+            let max_x =
+                ((draw.clip_rect.max.x * scale_x).round().max(0.0) as u32).min(extent.width);
+            let max_y =
+                ((draw.clip_rect.max.y * scale_y).round().max(0.0) as u32).min(extent.height);
+            if min_x >= max_x || min_y >= max_y {
+                continue;
+            }
+
+            let scissor = vk::Rect2D {
+                offset: vk::Offset2D {
+                    x: min_x as i32,
+                    y: min_y as i32,
+                },
+                extent: vk::Extent2D {
+                    width: max_x - min_x,
+                    height: max_y - min_y,
+                },
+            };
+            device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.textures[&draw.texture].descriptor_set],
+                &[],
+            );
+            device.cmd_draw_indexed(
+                command_buffer,
+                draw.index_count,
+                1,
+                draw.first_index,
+                draw.vertex_offset,
+                0,
+            );
+        }
+    }
+
+    pub unsafe fn name(&self, debug_names: &DebugNames) {
+        debug_names.name(self.pipeline_layout, "ui pipeline layout");
+        debug_names.name(self.pipeline, "ui pipeline");
+        debug_names.name(self.descriptor_pool, "ui texture pool");
+        debug_names.name(self.vertices.buffer(), "ui vertices");
+        debug_names.name(self.indices.buffer(), "ui indices");
+    }
+
+    pub unsafe fn destroy(&mut self, device: &Device, allocator: &mut Allocator) {
+        for (_, texture) in self.textures.drain() {
+            texture.image.destroy(device, allocator);
+        }
+        self.vertices.destroy(allocator);
+        self.indices.destroy(allocator);
+        device.destroy_descriptor_pool(self.descriptor_pool, None);
+        device.destroy_pipeline(self.pipeline, None);
+        device.destroy_pipeline_layout(self.pipeline_layout, None);
+    }
+}
+
+unsafe fn destroy_texture(
+    device: &Device,
+    allocator: &mut Allocator,
+    descriptor_pool: vk::DescriptorPool,
+    texture: UiTexture,
+) {
+    device.free_descriptor_sets(descriptor_pool, &[texture.descriptor_set]);
+    texture.image.destroy(device, allocator);
+}
+
+// premultiplied RGBA8 in gamma space
+fn convert_image(delta: &ImageDelta) -> Texture {
+    let pixels = match &delta.image {
+        ImageData::Color(image) => image
+            .pixels
+            .iter()
+            .flat_map(|pixel| pixel.to_array())
+            .collect(),
+        ImageData::Font(image) => image
+            .srgba_pixels(None)
+            .flat_map(|pixel| pixel.to_array())
+            .collect(),
+    };
+
+    Texture {
+        width: delta.image.width() as u32,
+        height: delta.image.height() as u32,
+        pixels,
+    }
+}
+
+fn as_bytes(screen_size: &[f32; 2]) -> &[u8] {
+    unsafe {
+        std::slice::from_raw_parts(
+            screen_size.as_ptr() as *const u8,
+            mem::size_of_val(screen_size),
+        )
+    }
+}
+
+unsafe fn create_pipeline(
+    device: &Device,
+    layout: vk::PipelineLayout,
+    target: &PipelineTarget,
+    pipeline_cache: vk::PipelineCache,
+) -> Result<vk::Pipeline, RendererInitError> {
+    let vertex_shader_module = pipeline::create_shader_module(device, "ui vertex", VERTEX_SHADER)?;
+    let fragment_shader_module =
+        match pipeline::create_shader_module(device, "ui fragment", FRAGMENT_SHADER) {
+            Ok(module) => module,
+            Err(err) => {
+                device.destroy_shader_module(vertex_shader_module, None);
+                return Err(err);
+            }
+        };
+
+    let entry_name = CString::new("main").unwrap();
+    let stages = [
+        vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(vertex_shader_module)
+            .name(&entry_name)
+            .build(),
+        vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(fragment_shader_module)
+            .name(&entry_name)
+            .build(),
+    ];
+
+    // egui's vertices as they are, locations 0 to 2
+    let vertex_bindings = [vk::VertexInputBindingDescription {
+        binding: 0,
+        stride: mem::size_of::<Vertex>() as u32,
+        input_rate: vk::VertexInputRate::VERTEX,
+    }];
+    let vertex_attributes = [
+        vk::VertexInputAttributeDescription {
+            location: 0,
+            binding: 0,
+            format: vk::Format::R32G32_SFLOAT,
+            offset: 0,
+        },
+        vk::VertexInputAttributeDescription {
+            location: 1,
+            binding: 0,
+            format: vk::Format::R32G32_SFLOAT,
+            offset: mem::size_of::<[f32; 2]>() as u32,
+        },
+        vk::VertexInputAttributeDescription {
+            location: 2,
+            binding: 0,
+            format: vk::Format::R8G8B8A8_UNORM,
+            offset: mem::size_of::<[f32; 4]>() as u32,
+        },
+    ];
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder()
+        .vertex_binding_descriptions(&vertex_bindings)
+        .vertex_attribute_descriptions(&vertex_attributes);
+
+    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+        .primitive_restart_enable(false);
+
+    let rasterizer = vk::PipelineRasterizationStateCreateInfo::builder()
+        .depth_clamp_enable(false)
+        .rasterizer_discard_enable(false)
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.0)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::CLOCKWISE)
+        .depth_bias_enable(false);
+
+    let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(false)
+        .depth_write_enable(false)
+        .depth_bounds_test_enable(false)
+        .stencil_test_enable(false);
+
+    let blend_attachments = [BlendMode::Premultiplied.attachment_state()];
+    let color_blending = vk::PipelineColorBlendStateCreateInfo::builder()
+        .logic_op_enable(false)
+        .attachments(&blend_attachments);
+
+    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissor_count(1);
+
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+
+    let multisampling = vk::PipelineMultisampleStateCreateInfo::builder()
+        .sample_shading_enable(false)
+        .rasterization_samples(target.samples);
+
+    let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&stages)
+        .vertex_input_state(&vertex_input_info)
+        .input_assembly_state(&input_assembly)
+        .viewport_state(&viewport_state)
+        .rasterization_state(&rasterizer)
+        .multisample_state(&multisampling)
+        .depth_stencil_state(&depth_stencil)
+        .color_blend_state(&color_blending)
+        .dynamic_state(&dynamic_state)
+        .layout(layout)
+        .render_pass(target.render_pass)
+        .subpass(0)
+        .build();
+
+    let pipelines = device.create_graphics_pipelines(pipeline_cache, &[pipeline_create_info], None);
+
+    device.destroy_shader_module(vertex_shader_module, None);
+    device.destroy_shader_module(fragment_shader_module, None);
+
+    pipelines
+        .map(|pipelines| pipelines[0])
+        .map_err(|err| RendererInitError::PipelineCreationError { err: err.1 })
+}
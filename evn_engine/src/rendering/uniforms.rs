@@ -0,0 +1,198 @@
+// Per-frame shader data
+//
+// Set 0 binding 0 holds the camera matrices, one uniform buffer per frame in flight.
+// Per-object data (the model matrix) is passed as a push constant.
+
+use super::{
+    allocator::{Allocation, Allocator, MemoryUsage},
+    debug::DebugNames,
+    RendererInitError,
+};
+use crate::components::Viewport;
+use ash::{version::DeviceV1_0, vk, Device};
+use nalgebra_glm as glm;
+use std::mem;
+
+// what the camera system hands to the renderer every frame
+#[derive(Debug, Clone, Copy)]
+pub struct CameraMatrices {
+    pub view: glm::Mat4,
+    pub projection: glm::Mat4,
+    pub viewport: Viewport,
+}
+
+impl Default for CameraMatrices {
+    fn default() -> Self {
+        CameraMatrices {
+            view: glm::Mat4::identity(),
+            projection: glm::Mat4::identity(),
+            viewport: Viewport::default(),
+        }
+    }
+}
+
+// layout(set = 0, binding = 0) uniform CameraUniform
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct CameraUniform {
+    view: [f32; 16],
+    projection: [f32; 16],
+}
+
+// layout(push_constant) uniform PushConstants
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PushConstants {
+    pub model: [f32; 16],
+}
+
+impl PushConstants {
+    pub fn new(model: &glm::Mat4) -> Self {
+        let mut push_constants = PushConstants { model: [0.0; 16] };
+        push_constants.model.copy_from_slice(model.as_slice());
+        push_constants
+    }
+
+    pub fn range() -> vk::PushConstantRange {
+        vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            offset: 0,
+            size: mem::size_of::<PushConstants>() as u32,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(
+                self as *const PushConstants as *const u8,
+                mem::size_of::<PushConstants>(),
+            )
+        }
+    }
+}
+
+pub struct FrameUniforms {
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    buffers: Vec<(vk::Buffer, Allocation)>,
+}
+
+impl FrameUniforms {
+    pub unsafe fn new(
+        device: &Device,
+        allocator: &mut Allocator,
+        frame_count: usize,
+    ) -> Result<Self, RendererInitError> {
+        let bindings = [vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .build()];
+
+        let descriptor_set_layout_create_info =
+            vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+
+        let descriptor_set_layout = device
+            .create_descriptor_set_layout(&descriptor_set_layout_create_info, None)
+            .map_err(|err| RendererInitError::DescriptorCreationError { err })?;
+
+        let pool_sizes = [vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: frame_count as u32,
+        }];
+
+        let descriptor_pool_create_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&pool_sizes)
+            .max_sets(frame_count as u32);
+
+        let descriptor_pool = device
+            .create_descriptor_pool(&descriptor_pool_create_info, None)
+            .map_err(|err| RendererInitError::DescriptorCreationError { err })?;
+
+        let set_layouts = vec![descriptor_set_layout; frame_count];
+        let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(descriptor_pool)
+            .set_layouts(&set_layouts);
+
+        let descriptor_sets = device
+            .allocate_descriptor_sets(&descriptor_set_allocate_info)
+            .map_err(|err| RendererInitError::DescriptorCreationError { err })?;
+
+        let buffer_create_info = vk::BufferCreateInfo::builder()
+            .size(mem::size_of::<CameraUniform>() as vk::DeviceSize)
+            .usage(vk::BufferUsageFlags::UNIFORM_BUFFER)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+        let buffers = (0..frame_count)
+            .map(|_| allocator.create_buffer(&buffer_create_info, MemoryUsage::CpuToGpu))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| RendererInitError::AllocationError { err })?;
+
+        for (&descriptor_set, (buffer, _)) in descriptor_sets.iter().zip(&buffers) {
+            let buffer_info = [vk::DescriptorBufferInfo {
+                buffer: *buffer,
+                offset: 0,
+                range: mem::size_of::<CameraUniform>() as vk::DeviceSize,
+            }];
+
+            let descriptor_write = vk::WriteDescriptorSet::builder()
+                .dst_set(descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .buffer_info(&buffer_info)
+                .build();
+
+            device.update_descriptor_sets(&[descriptor_write], &[]);
+        }
+
+        Ok(FrameUniforms {
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            buffers,
+        })
+    }
+
+    pub fn descriptor_set_layout(&self) -> vk::DescriptorSetLayout {
+        self.descriptor_set_layout
+    }
+
+    pub fn descriptor_set(&self, frame: usize) -> vk::DescriptorSet {
+        self.descriptor_sets[frame]
+    }
+
+    // the frame's fence has to be waited on before calling this
+    pub fn update(&mut self, frame: usize, camera: &CameraMatrices) {
+        let mut uniform = CameraUniform {
+            view: [0.0; 16],
+            projection: [0.0; 16],
+        };
+        uniform.view.copy_from_slice(camera.view.as_slice());
+        uniform
+            .projection
+            .copy_from_slice(camera.projection.as_slice());
+
+        self.buffers[frame].1.write(0, &[uniform]);
+    }
+
+    pub unsafe fn name(&self, debug_names: &DebugNames) {
+        debug_names.name(self.descriptor_set_layout, "frame uniforms layout");
+        debug_names.name(self.descriptor_pool, "frame uniforms pool");
+        debug_names.name_all(&self.descriptor_sets, "frame uniforms set");
+        for (index, (buffer, _)) in self.buffers.iter().enumerate() {
+            debug_names.name(*buffer, &format!("frame uniforms buffer {}", index));
+        }
+    }
+
+    pub unsafe fn destroy(&mut self, device: &Device, allocator: &mut Allocator) {
+        for (buffer, allocation) in self.buffers.drain(..) {
+            allocator.destroy_buffer(buffer, allocation);
+        }
+
+        device.destroy_descriptor_pool(self.descriptor_pool, None);
+        device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+    }
+}
@@ -0,0 +1,208 @@
+// Validation message routing
+//
+// Messages from VK_EXT_debug_utils are parsed into `ValidationMessage`s, filtered by the
+// ignore list of the graphics settings and counted per frame. Panicking inside the callback
+// would unwind into the driver, so `OnValidationError` only acts once the frame is done.
+
+use super::settings::ValidationSettings;
+use ash::vk;
+use log::{error, info, warn};
+use std::{
+    ffi::CStr,
+    fmt,
+    os::raw::{c_char, c_void},
+    sync::Mutex,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Verbose,
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationObject {
+    pub object_type: vk::ObjectType,
+    pub handle: u64,
+    // set with `DebugNames`
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationMessage {
+    pub severity: Severity,
+    pub message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    // e.g. VUID-vkCmdDraw-None-02859, not every message has one
+    pub id_name: Option<String>,
+    pub id_number: i32,
+    pub message: String,
+    pub objects: Vec<ValidationObject>,
+}
+
+impl ValidationMessage {
+    unsafe fn parse(
+        severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+        message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+        data: &vk::DebugUtilsMessengerCallbackDataEXT,
+    ) -> Self {
+        let severity = if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+            Severity::Error
+        } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+            Severity::Warning
+        } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+            Severity::Info
+        } else {
+            Severity::Verbose
+        };
+
+        let objects = if data.p_objects.is_null() {
+            Vec::new()
+        } else {
+            std::slice::from_raw_parts(data.p_objects, data.object_count as usize)
+                .iter()
+                .map(|object| ValidationObject {
+                    object_type: object.object_type,
+                    handle: object.object_handle,
+                    name: optional_string(object.p_object_name),
+                })
+                .collect()
+        };
+
+        ValidationMessage {
+            severity,
+            message_type,
+            id_name: optional_string(data.p_message_id_name),
+            id_number: data.message_id_number,
+            message: optional_string(data.p_message).unwrap_or_default(),
+            objects,
+        }
+    }
+}
+
+impl fmt::Display for ValidationMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(id_name) = &self.id_name {
+            write!(f, "[{}] ", id_name)?;
+        }
+        write!(f, "{}", self.message.trim())?;
+
+        for object in &self.objects {
+            write!(f, "\n    {:?} {:#x}", object.object_type, object.handle)?;
+            if let Some(name) = &object.name {
+                write!(f, " \"{}\"", name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// counted messages since the end of the last frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValidationCounts {
+    pub verbose: usize,
+    pub info: usize,
+    pub warnings: usize,
+    pub errors: usize,
+    // not logged and not part of the counts above
+    pub ignored: usize,
+}
+
+#[derive(Default)]
+struct FrameMessages {
+    counts: ValidationCounts,
+    first_error: Option<ValidationMessage>,
+}
+
+// passed to the callback as user data, has to outlive the messenger
+pub struct Validation {
+    settings: ValidationSettings,
+    frame: Mutex<FrameMessages>,
+}
+
+impl Validation {
+    pub fn new(settings: ValidationSettings) -> Self {
+        Validation {
+            settings,
+            frame: Mutex::new(FrameMessages::default()),
+        }
+    }
+
+    pub fn settings(&self) -> &ValidationSettings {
+        &self.settings
+    }
+
+    pub fn user_data(&self) -> *mut c_void {
+        self as *const Validation as *mut c_void
+    }
+
+    fn handle(&self, message: ValidationMessage) {
+        let mut frame = self.frame.lock().unwrap();
+
+        let ignored = match &message.id_name {
+            Some(id_name) => self.settings.ignore.contains(id_name),
+            None => false,
+        };
+        if ignored {
+            frame.counts.ignored += 1;
+            return;
+        }
+
+        match message.severity {
+            Severity::Verbose => {
+                frame.counts.verbose += 1;
+                info!("Vulkan: {}", message);
+            }
+            Severity::Info => {
+                frame.counts.info += 1;
+                info!("Vulkan: {}", message);
+            }
+            Severity::Warning => {
+                frame.counts.warnings += 1;
+                warn!("Vulkan: {}", message);
+            }
+            Severity::Error => {
+                frame.counts.errors += 1;
+                error!("Vulkan: {}", message);
+
+                if frame.first_error.is_none() {
+                    frame.first_error = Some(message);
+                }
+            }
+        }
+    }
+
+    // the counts and first error since the last call, resets both
+    pub fn end_frame(&self) -> (ValidationCounts, Option<ValidationMessage>) {
+        let mut frame = self.frame.lock().unwrap();
+        let frame = std::mem::take(&mut *frame);
+
+        (frame.counts, frame.first_error)
+    }
+}
+
+unsafe fn optional_string(pointer: *const c_char) -> Option<String> {
+    if pointer.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(pointer).to_string_lossy().into_owned())
+    }
+}
+
+pub unsafe extern "system" fn vulkan_debug_callback(
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
+    user_data: *mut c_void,
+) -> u32 {
+    let validation = &*(user_data as *const Validation);
+    validation.handle(ValidationMessage::parse(
+        message_severity,
+        message_type,
+        &*callback_data,
+    ));
+
+    vk::FALSE
+}
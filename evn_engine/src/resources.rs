@@ -1,4 +1,7 @@
-use crate::{config::Config, rendering::Shader};
+use crate::{
+    config::Config,
+    rendering::{Font, PipelineDesc, Shader, Texture},
+};
 use fnv::{FnvBuildHasher, FnvHashMap};
 use log::{info, warn};
 use std::{
@@ -9,6 +12,7 @@ use std::{
     path::{Path, PathBuf},
     sync::{Arc, Mutex, RwLock},
     thread,
+    time::Duration,
 };
 
 pub type Resources = Arc<RwLock<ResourcesData>>;
@@ -17,6 +21,8 @@ pub type Resources = Arc<RwLock<ResourcesData>>;
 pub enum Resource {
     Config(Config),
     Shader(Shader),
+    Texture(Texture),
+    Font(Font),
 }
 
 #[derive(Debug)]
@@ -98,6 +104,18 @@ impl ResourcesData {
         });
     }
 
+    // blocks until the resource finished loading (or failed to)
+    pub fn wait_for_resource(&self, name: impl AsRef<str>) -> Arc<ResourceState> {
+        loop {
+            let resource = self.get_resource(name.as_ref());
+            if !resource.is_loading() {
+                return resource;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
     pub fn get_resource(&self, name: impl AsRef<str>) -> Arc<ResourceState> {
         {
             let res = self.resources.lock().unwrap();
@@ -107,12 +125,35 @@ impl ResourcesData {
                 .clone()
         }
     }
+
+    // every resource sorted by name
+    pub fn list(&self) -> Vec<(String, Arc<ResourceState>)> {
+        let mut list = {
+            let res = self.resources.lock().unwrap();
+            (*res)
+                .iter()
+                .map(|(name, state)| (name.clone(), state.clone()))
+                .collect::<Vec<_>>()
+        };
+        list.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        list
+    }
+
+    // holders of the old state keep it, only later `get_resource` calls see the new one
+    pub fn replace_resource(&self, name: impl AsRef<str>, resource: Resource) {
+        let mut res = self.resources.lock().unwrap();
+        if let Some(val) = (*res).get_mut(name.as_ref()) {
+            *(val) = Arc::new(ResourceState::Loaded(resource));
+        }
+    }
 }
 
 pub struct ResourceBuilder {
     pub res: Resources,
     pub is_dev: bool,
     pub names: HashMap<String, Vec<String>, FnvBuildHasher>,
+    pub pipelines: Vec<(String, PipelineDesc)>,
 }
 
 impl ResourceBuilder {
@@ -189,9 +230,83 @@ impl ResourceBuilder {
 
         self
     }
+
+    // a fragment shader for `graphics.post`, drawn over the whole target with a built in
+    // vertex shader, see rendering/post.rs for its inputs
+    pub fn with_post_shader<P: AsRef<Path> + Send + Sync + 'static>(
+        mut self,
+        name: impl AsRef<str>,
+        frag_path: P,
+    ) -> ResourceBuilder {
+        let names = self.names.entry("post_shaders".into()).or_default();
+        (*names).push(name.as_ref().to_owned());
+
+        let is_dev = self.is_dev;
+        {
+            let resources = self.res.read().unwrap();
+            (*resources).add_resource(name, move || -> Result<_, IoError> {
+                let path = resource_path(frag_path.as_ref(), is_dev, false);
+                let mut buf = Vec::new();
+                File::open(path)?.read_to_end(&mut buf)?;
+
+                Ok(Resource::Shader(Shader::post(ash::util::read_spv(
+                    &mut Cursor::new(&buf),
+                )?)))
+            });
+        }
+
+        self
+    }
+
+    // 8 bit RGB or RGBA PNGs, referenced by `Sprite::texture`
+    pub fn with_texture<P: AsRef<Path> + Send + Sync + 'static>(
+        mut self,
+        name: impl AsRef<str>,
+        path: P,
+    ) -> ResourceBuilder {
+        let names = self.names.entry("textures".into()).or_default();
+        (*names).push(name.as_ref().to_owned());
+
+        let is_dev = self.is_dev;
+        {
+            let resources = self.res.read().unwrap();
+            (*resources).add_resource(name, move || {
+                Texture::load_png(&resource_path(path.as_ref(), is_dev, false))
+                    .map(Resource::Texture)
+            });
+        }
+
+        self
+    }
+
+    // TrueType or OpenType fonts, referenced by `Text::font`
+    pub fn with_font<P: AsRef<Path> + Send + Sync + 'static>(
+        mut self,
+        name: impl AsRef<str>,
+        path: P,
+    ) -> ResourceBuilder {
+        let names = self.names.entry("fonts".into()).or_default();
+        (*names).push(name.as_ref().to_owned());
+
+        let is_dev = self.is_dev;
+        {
+            let resources = self.res.read().unwrap();
+            (*resources).add_resource(name, move || {
+                Font::load(&resource_path(path.as_ref(), is_dev, false)).map(Resource::Font)
+            });
+        }
+
+        self
+    }
+
+    // the shader of the description has to be registered with `with_shader`
+    pub fn with_pipeline(mut self, name: impl AsRef<str>, desc: PipelineDesc) -> ResourceBuilder {
+        self.pipelines.push((name.as_ref().to_owned(), desc));
+        self
+    }
 }
 
-fn resource_path(path: impl AsRef<Path>, is_dev: bool, open: bool) -> PathBuf {
+pub fn resource_path(path: impl AsRef<Path>, is_dev: bool, open: bool) -> PathBuf {
     let mut res_path = PathBuf::from(match (is_dev, open) {
         (true, true) => "./resources/open/",
         (true, false) => "./resources/closed/",
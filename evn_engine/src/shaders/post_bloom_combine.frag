@@ -0,0 +1,24 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler postSampler;
+layout(set = 0, binding = 1) uniform texture2D input0;
+layout(set = 0, binding = 2) uniform texture2D input1;
+
+// inputs: scene, bloom
+// params: intensity
+layout(push_constant) uniform PushConstants {
+    vec4 params[2];
+    vec2 texelSize;
+};
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec4 scene = texture(sampler2D(input0, postSampler), fragUv);
+    vec3 bloom = texture(sampler2D(input1, postSampler), fragUv).rgb;
+
+    outColor = vec4(scene.rgb + bloom * params[0].x, scene.a);
+}
@@ -0,0 +1,29 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler postSampler;
+layout(set = 0, binding = 1) uniform texture2D input0;
+
+// params: threshold, the part of the brightness above it is kept
+layout(push_constant) uniform PushConstants {
+    vec4 params[2];
+    vec2 texelSize;
+};
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // the target is usually smaller, average a few bilinear taps
+    vec2 offset = texelSize * 0.25;
+    vec3 color = (texture(sampler2D(input0, postSampler), fragUv + vec2(-offset.x, -offset.y)).rgb
+        + texture(sampler2D(input0, postSampler), fragUv + vec2(offset.x, -offset.y)).rgb
+        + texture(sampler2D(input0, postSampler), fragUv + vec2(-offset.x, offset.y)).rgb
+        + texture(sampler2D(input0, postSampler), fragUv + vec2(offset.x, offset.y)).rgb) * 0.25;
+
+    float brightness = max(color.r, max(color.g, color.b));
+    float contribution = max(brightness - params[0].x, 0.0) / max(brightness, 0.0001);
+
+    outColor = vec4(color * contribution, 1.0);
+}
@@ -0,0 +1,31 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler postSampler;
+layout(set = 0, binding = 1) uniform texture2D input0;
+
+// params: direction x, direction y, the input has to be as large as the target
+layout(push_constant) uniform PushConstants {
+    vec4 params[2];
+    vec2 texelSize;
+};
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+// 9 tap gaussian folded into 5 bilinear taps
+const float offsets[3] = float[](0.0, 1.3846153846, 3.2307692308);
+const float weights[3] = float[](0.2270270270, 0.3162162162, 0.0702702703);
+
+void main() {
+    vec2 direction = params[0].xy * texelSize;
+
+    vec4 color = texture(sampler2D(input0, postSampler), fragUv) * weights[0];
+    for (int i = 1; i < 3; i++) {
+        color += texture(sampler2D(input0, postSampler), fragUv + direction * offsets[i]) * weights[i];
+        color += texture(sampler2D(input0, postSampler), fragUv - direction * offsets[i]) * weights[i];
+    }
+
+    outColor = color;
+}
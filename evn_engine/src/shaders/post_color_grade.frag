@@ -0,0 +1,38 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler postSampler;
+layout(set = 0, binding = 1) uniform texture2D input0;
+layout(set = 0, binding = 2) uniform texture2D input1;
+
+// inputs: color in 0..1, lookup table
+// params: strength, 0 leaves the color unchanged
+// the table is a strip of N slices of N x N, red to the right, green down, blue per slice
+layout(push_constant) uniform PushConstants {
+    vec4 params[2];
+    vec2 texelSize;
+};
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec4 color = texture(sampler2D(input0, postSampler), fragUv);
+    vec3 clamped = clamp(color.rgb, 0.0, 1.0);
+
+    float size = float(textureSize(sampler2D(input1, postSampler), 0).y);
+    float slice = clamped.b * (size - 1.0);
+    float lower = floor(slice);
+    float upper = min(lower + 1.0, size - 1.0);
+
+    // texel centers, the sampler interpolates red and green
+    vec2 uv = (clamped.rg * (size - 1.0) + 0.5) / vec2(size * size, size);
+    vec3 graded = mix(
+        texture(sampler2D(input1, postSampler), uv + vec2(lower / size, 0.0)).rgb,
+        texture(sampler2D(input1, postSampler), uv + vec2(upper / size, 0.0)).rgb,
+        slice - lower
+    );
+
+    outColor = vec4(mix(color.rgb, graded, params[0].x), color.a);
+}
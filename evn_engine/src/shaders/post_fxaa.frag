@@ -0,0 +1,61 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler postSampler;
+layout(set = 0, binding = 1) uniform texture2D input0;
+
+// the input should be in gamma space, after tonemapping
+// params: span (8), reduce multiplier (1/8), reduce minimum (1/128), 0 uses the default
+layout(push_constant) uniform PushConstants {
+    vec4 params[2];
+    vec2 texelSize;
+};
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+vec3 sampleColor(vec2 uv) {
+    return texture(sampler2D(input0, postSampler), uv).rgb;
+}
+
+void main() {
+    float spanMax = params[0].x > 0.0 ? params[0].x : 8.0;
+    float reduceMul = params[0].y > 0.0 ? params[0].y : 1.0 / 8.0;
+    float reduceMin = params[0].z > 0.0 ? params[0].z : 1.0 / 128.0;
+
+    vec4 center = texture(sampler2D(input0, postSampler), fragUv);
+    float lumaNw = luma(sampleColor(fragUv + vec2(-1.0, -1.0) * texelSize));
+    float lumaNe = luma(sampleColor(fragUv + vec2(1.0, -1.0) * texelSize));
+    float lumaSw = luma(sampleColor(fragUv + vec2(-1.0, 1.0) * texelSize));
+    float lumaSe = luma(sampleColor(fragUv + vec2(1.0, 1.0) * texelSize));
+    float lumaM = luma(center.rgb);
+
+    float lumaMin = min(lumaM, min(min(lumaNw, lumaNe), min(lumaSw, lumaSe)));
+    float lumaMax = max(lumaM, max(max(lumaNw, lumaNe), max(lumaSw, lumaSe)));
+
+    // perpendicular to the edge
+    vec2 direction = vec2(
+        -((lumaNw + lumaNe) - (lumaSw + lumaSe)),
+        (lumaNw + lumaSw) - (lumaNe + lumaSe)
+    );
+    float reduce = max((lumaNw + lumaNe + lumaSw + lumaSe) * 0.25 * reduceMul, reduceMin);
+    float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2(-spanMax), vec2(spanMax)) * texelSize;
+
+    vec3 colorA = 0.5 * (sampleColor(fragUv + direction * (1.0 / 3.0 - 0.5))
+        + sampleColor(fragUv + direction * (2.0 / 3.0 - 0.5)));
+    vec3 colorB = colorA * 0.5 + 0.25 * (sampleColor(fragUv - direction * 0.5)
+        + sampleColor(fragUv + direction * 0.5));
+
+    float lumaB = luma(colorB);
+    if (lumaB < lumaMin || lumaB > lumaMax) {
+        outColor = vec4(colorA, center.a);
+    } else {
+        outColor = vec4(colorB, center.a);
+    }
+}
@@ -0,0 +1,34 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler postSampler;
+layout(set = 0, binding = 1) uniform texture2D input0;
+
+// params: exposure, operator (0 ACES, 1 Reinhard)
+layout(push_constant) uniform PushConstants {
+    vec4 params[2];
+    vec2 texelSize;
+};
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+// Krzysztof Narkowicz's fit of the ACES curve
+vec3 aces(vec3 color) {
+    return clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec4 color = texture(sampler2D(input0, postSampler), fragUv);
+    vec3 exposed = color.rgb * params[0].x;
+
+    vec3 mapped;
+    if (params[0].y > 0.5) {
+        mapped = exposed / (exposed + 1.0);
+    } else {
+        mapped = aces(exposed);
+    }
+
+    outColor = vec4(mapped, color.a);
+}
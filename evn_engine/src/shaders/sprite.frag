@@ -0,0 +1,14 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 1, binding = 0) uniform texture2D spriteTexture;
+layout(set = 1, binding = 1) uniform sampler spriteSampler;
+
+layout(location = 0) in vec2 fragUv;
+layout(location = 1) in vec4 fragTint;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = texture(sampler2D(spriteTexture, spriteSampler), fragUv) * fragTint;
+}
@@ -0,0 +1,31 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform CameraUniform {
+    mat4 view;
+    mat4 projection;
+} camera;
+
+// per instance
+layout(location = 0) in vec4 positionSize;
+layout(location = 1) in vec4 rotationPivot;
+layout(location = 2) in vec4 uvRect;
+layout(location = 3) in vec4 tint;
+
+layout(location = 0) out vec2 fragUv;
+layout(location = 1) out vec4 fragTint;
+
+void main() {
+    // triangle strip, (0, 0) is the bottom left corner
+    vec2 corner = vec2(float(gl_VertexIndex & 1), float(gl_VertexIndex >> 1));
+
+    vec2 local = (corner - rotationPivot.zw) * positionSize.zw;
+    vec2 rotated = vec2(
+        local.x * rotationPivot.x - local.y * rotationPivot.y,
+        local.x * rotationPivot.y + local.y * rotationPivot.x
+    );
+
+    gl_Position = camera.projection * camera.view * vec4(rotated + positionSize.xy, 0.0, 1.0);
+    fragUv = uvRect.xy + vec2(corner.x, 1.0 - corner.y) * uvRect.zw;
+    fragTint = tint;
+}
@@ -0,0 +1,15 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform texture2D uiTexture;
+layout(set = 0, binding = 1) uniform sampler uiSampler;
+
+layout(location = 0) in vec2 fragUv;
+layout(location = 1) in vec4 fragColor;
+
+layout(location = 0) out vec4 outColor;
+
+// both are premultiplied and in gamma space, like the output
+void main() {
+    outColor = fragColor * texture(sampler2D(uiTexture, uiSampler), fragUv);
+}
@@ -0,0 +1,20 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// logical pixels of the window
+layout(push_constant) uniform PushConstants {
+    vec2 screenSize;
+} pc;
+
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 uv;
+layout(location = 2) in vec4 color;
+
+layout(location = 0) out vec2 fragUv;
+layout(location = 1) out vec4 fragColor;
+
+void main() {
+    gl_Position = vec4(2.0 * position / pc.screenSize - 1.0, 0.0, 1.0);
+    fragUv = uv;
+    fragColor = color;
+}
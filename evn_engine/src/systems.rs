@@ -1,16 +1,29 @@
 // only for most relevant engine systems
 
-use crate::Running;
+use crate::{
+    components::{Camera, Rotation3, Translation3},
+    rendering::{CameraMatrices, Screenshot},
+    Running, WindowSize,
+};
 use crossbeam::channel::Receiver;
-use specs::{ReadExpect, System, WriteExpect};
-use winit::{/* DeviceEvent,*/ Event, WindowEvent};
+use nalgebra_glm as glm;
+use specs::{Join, ReadExpect, ReadStorage, System, Write, WriteExpect};
+use winit::{/* DeviceEvent,*/ ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent,};
 
 pub struct EventHandler;
 
 impl<'a> System<'a> for EventHandler {
-    type SystemData = (ReadExpect<'a, Receiver<Event>>, WriteExpect<'a, Running>);
+    type SystemData = (
+        ReadExpect<'a, Receiver<Event>>,
+        WriteExpect<'a, Running>,
+        WriteExpect<'a, WindowSize>,
+        Write<'a, Screenshot>,
+    );
 
-    fn run(&mut self, (event_receiver, mut running): Self::SystemData) {
+    fn run(
+        &mut self,
+        (event_receiver, mut running, mut window_size, mut screenshot): Self::SystemData,
+    ) {
         for event in event_receiver.try_iter() {
             //println!("Event: {:?}", event);
 
@@ -20,6 +33,16 @@ impl<'a> System<'a> for EventHandler {
                 },
                 Event::WindowEvent { event, .. } => match event {
                     WindowEvent::CloseRequested => running.0 = false,
+                    WindowEvent::Resized(size) => window_size.0 = size,
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::F12),
+                                ..
+                            },
+                        ..
+                    } => screenshot.request(),
                     _ => (),
                 },
                 _ => (),
@@ -27,3 +50,56 @@ impl<'a> System<'a> for EventHandler {
         }
     }
 }
+
+// computes the matrices of the active camera for the renderer
+pub struct CameraSystem;
+
+impl<'a> System<'a> for CameraSystem {
+    type SystemData = (
+        ReadExpect<'a, WindowSize>,
+        ReadStorage<'a, Camera>,
+        ReadStorage<'a, Translation3<f32>>,
+        ReadStorage<'a, Rotation3<f32>>,
+        Write<'a, CameraMatrices>,
+    );
+
+    fn run(
+        &mut self,
+        (window_size, cameras, translations, rotations, mut matrices): Self::SystemData,
+    ) {
+        let active_camera = (&cameras, translations.maybe(), rotations.maybe())
+            .join()
+            .find(|(camera, _, _)| camera.active);
+
+        let (camera, translation, rotation) = match active_camera {
+            Some(active_camera) => active_camera,
+            None => {
+                *matrices = CameraMatrices::default();
+                return;
+            }
+        };
+
+        let width = window_size.0.width as f32 * camera.viewport.width;
+        let height = window_size.0.height as f32 * camera.viewport.height;
+
+        // minimized, keep the last matrices
+        if width <= 0.0 || height <= 0.0 {
+            return;
+        }
+
+        // inverse of the camera transform
+        let mut view = glm::Mat4::identity();
+        if let Some(rotation) = rotation {
+            view = rotation.0.inverse().to_homogeneous();
+        }
+        if let Some(translation) = translation {
+            view *= translation.0.inverse().to_homogeneous();
+        }
+
+        *matrices = CameraMatrices {
+            view,
+            projection: camera.projection_matrix(width / height),
+            viewport: camera.viewport,
+        };
+    }
+}
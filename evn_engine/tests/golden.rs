@@ -0,0 +1,269 @@
+// Golden image tests
+//
+// Every scene is rendered headless at a fixed resolution and compared against
+// tests/golden/<scene>.png. Run them on a software driver so the results don't
+// depend on the GPU, e.g. with lavapipe:
+//
+//     VK_ICD_FILENAMES=/usr/share/vulkan/icd.d/lvp_icd.x86_64.json cargo test --test golden -- --ignored
+//
+// They need a Vulkan loader and device, so they're ignored by default and fail without one.
+// A missing reference fails the test, EVN_UPDATE_GOLDEN=1 writes the rendered images as the
+// new references (commit them). On a mismatch the rendered image and a diff image are
+// written to target/golden/.
+
+use evn_engine::{
+    components::{self, Camera, Material, Rotation3, Translation3},
+    rendering::{
+        CameraMatrices, Frame, GraphicsSettings, OnValidationError, PostPassSettings,
+        RenderBackend, RenderOutput, RenderSystem, Renderer, Screenshot, ValidationSettings,
+    },
+    resources::{ResourceBuilder, ResourcesData},
+    systems::CameraSystem,
+    WindowSize,
+};
+use fnv::FnvHashMap;
+use nalgebra::geometry;
+use specs::{Builder, RunNow, World};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
+use winit::dpi::LogicalSize;
+
+const WIDTH: u32 = 128;
+const HEIGHT: u32 = 128;
+// maximum difference per channel before a pixel counts as different
+const TOLERANCE: u8 = 2;
+
+// the working directory is process wide and drivers don't like parallel instances
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[test]
+#[ignore = "needs a Vulkan device, run with --ignored"]
+fn empty() {
+    golden("empty", |_| ());
+}
+
+#[test]
+#[ignore = "needs a Vulkan device, run with --ignored"]
+fn triangle() {
+    golden("triangle", |world| {
+        world
+            .create_entity()
+            .with(Translation3(geometry::Translation3::new(0.0f32, 0.0, 0.0)))
+            .with(Material::new("shader_normal"))
+            .build();
+    });
+}
+
+#[test]
+#[ignore = "needs a Vulkan device, run with --ignored"]
+fn triangle_rotated() {
+    golden("triangle_rotated", |world| {
+        world
+            .create_entity()
+            .with(Translation3(geometry::Translation3::new(0.3f32, 0.0, -1.0)))
+            .with(Rotation3(geometry::Rotation3::from_euler_angles(
+                0.0,
+                0.0,
+                std::f32::consts::FRAC_PI_4,
+            )))
+            .with(Material::new("shader_normal"))
+            .build();
+    });
+}
+
+#[test]
+#[ignore = "needs a Vulkan device, run with --ignored"]
+fn post_chain() {
+    let pass = |shader: &str, inputs: &[&str], scale: f32, params: &[f32]| PostPassSettings {
+        name: shader.to_owned(),
+        shader: shader.to_owned(),
+        inputs: inputs.iter().map(|input| (*input).to_owned()).collect(),
+        scale,
+        params: params.to_vec(),
+    };
+
+    // a half resolution pass sampled by a later one, scene sampled twice
+    let post = vec![
+        pass("post_bloom_threshold", &["scene"], 0.5, &[0.5]),
+        pass(
+            "post_bloom_combine",
+            &["scene", "post_bloom_threshold"],
+            1.0,
+            &[0.5],
+        ),
+        pass("post_tonemap", &["post_bloom_combine"], 1.0, &[1.0]),
+        pass("post_gamma", &["post_tonemap"], 1.0, &[2.2]),
+        pass("post_fxaa", &["post_gamma"], 1.0, &[]),
+    ];
+
+    golden_with_post("post_chain", post, |world| {
+        world
+            .create_entity()
+            .with(Translation3(geometry::Translation3::new(0.0f32, 0.0, 0.0)))
+            .with(Material::new("shader_normal"))
+            .build();
+    });
+}
+
+fn golden(name: &str, scene: impl FnOnce(&mut World)) {
+    golden_with_post(name, Vec::new(), scene);
+}
+
+// renders the scene through the post chain and compares it against its reference
+fn golden_with_post(name: &str, post: Vec<PostPassSettings>, scene: impl FnOnce(&mut World)) {
+    let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+
+    // resources are looked up relative to the workspace root
+    env::set_current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("..")).unwrap();
+
+    let frame = render(post, scene);
+
+    let reference_path = PathBuf::from(format!("evn_engine/tests/golden/{}.png", name));
+    if env::var_os("EVN_UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+        frame.save_png(&reference_path).unwrap();
+        eprintln!("Updated reference {}", reference_path.display());
+        return;
+    }
+
+    assert!(
+        reference_path.exists(),
+        "Missing reference {}, render it with EVN_UPDATE_GOLDEN=1",
+        reference_path.display()
+    );
+
+    let reference = Frame::load_png(&reference_path).unwrap();
+    assert_eq!(
+        (reference.width, reference.height),
+        (frame.width, frame.height),
+        "Reference {} has a different size",
+        reference_path.display()
+    );
+
+    let (mismatched, diff) = compare(&reference, &frame);
+    if mismatched > 0 {
+        let output = Path::new("target/golden");
+        fs::create_dir_all(output).unwrap();
+        frame
+            .save_png(&output.join(format!("{}.actual.png", name)))
+            .unwrap();
+        diff.save_png(&output.join(format!("{}.diff.png", name)))
+            .unwrap();
+
+        panic!(
+            "Scene \"{}\" differs from its reference in {} pixels, see {}",
+            name,
+            mismatched,
+            output.display()
+        );
+    }
+}
+
+fn render(post: Vec<PostPassSettings>, scene: impl FnOnce(&mut World)) -> Frame {
+    let resources = ResourceBuilder {
+        res: Arc::new(RwLock::new(ResourcesData::new())),
+        is_dev: true,
+        names: FnvHashMap::default(),
+        pipelines: Vec::new(),
+    }
+    .with_shader(
+        "shader_normal",
+        "shaders/normal.vert.spv",
+        "shaders/normal.frag.spv",
+    );
+    let resources = post.iter().fold(resources, |resources, pass| {
+        let path = format!("shaders/{}.frag.spv", pass.shader);
+        resources.with_post_shader(pass.shader.clone(), path)
+    });
+
+    // defaults are stable across machines, the GPU can be pinned for drivers that can't be selected otherwise
+    // validation runs if the layer is installed, any error fails the test
+    let settings = GraphicsSettings {
+        gpu: env::var("EVN_GOLDEN_GPU").ok(),
+        validation: ValidationSettings {
+            on_error: OnValidationError::Panic,
+            ..ValidationSettings::default()
+        },
+        post,
+        ..GraphicsSettings::default()
+    };
+
+    let renderer = Renderer::new(
+        RenderOutput::Headless {
+            width: WIDTH,
+            height: HEIGHT,
+        },
+        true,
+        settings,
+        resources.res.clone(),
+        resources.names,
+        resources.pipelines,
+        env::temp_dir().join("evn_golden_pipeline_cache.bin"),
+    );
+
+    let mut render_system = match renderer {
+        Ok(renderer) => RenderSystem::new(renderer),
+        Err(err) => panic!("Failed to create renderer: {}", err),
+    };
+
+    let mut world = World::new();
+    components::register(&mut world);
+    world.add_resource(WindowSize(LogicalSize::new(
+        f64::from(WIDTH),
+        f64::from(HEIGHT),
+    )));
+    world.add_resource(CameraMatrices::default());
+    world.add_resource(Screenshot::default());
+
+    world
+        .create_entity()
+        .with(Translation3(geometry::Translation3::new(0.0f32, 0.0, 2.0)))
+        .with(Camera::perspective(std::f32::consts::FRAC_PI_3, 0.1, 100.0))
+        .build();
+    scene(&mut world);
+    world.maintain();
+
+    CameraSystem.run_now(&world.res);
+    render_system.run_now(&world.res);
+
+    render_system
+        .backend
+        .read_frame()
+        .expect("Frame failed validation")
+}
+
+// number of differing pixels and an image showing them in red over the dimmed reference
+fn compare(reference: &Frame, frame: &Frame) -> (usize, Frame) {
+    let mut mismatched = 0;
+    let mut pixels = Vec::with_capacity(reference.pixels.len());
+
+    for (expected, actual) in reference.pixels.chunks(4).zip(frame.pixels.chunks(4)) {
+        let difference = expected
+            .iter()
+            .zip(actual)
+            .map(|(expected, actual)| expected.max(actual) - expected.min(actual))
+            .max()
+            .unwrap();
+
+        if difference > TOLERANCE {
+            mismatched += 1;
+            pixels.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let gray = ((u16::from(expected[0]) + u16::from(expected[1]) + u16::from(expected[2]))
+                / 12) as u8;
+            pixels.extend_from_slice(&[gray, gray, gray, 255]);
+        }
+    }
+
+    (
+        mismatched,
+        Frame {
+            width: reference.width,
+            height: reference.height,
+            pixels,
+        },
+    )
+}
@@ -0,0 +1,239 @@
+// Game logic with the null backend, works without a Vulkan loader or a display
+
+use evn_engine::{
+    components::{
+        Camera, Material, Rotation2, Sprite, Text, TextAlign, TextSpace, Translation2, Translation3,
+    },
+    debug_draw::DebugDraw,
+    dev_ui::{DevUi, DevUiSystem},
+    profiler::Profiler,
+    rendering::NullRenderer,
+    Backend, Game,
+};
+use nalgebra::geometry;
+use specs::{Builder, RunNow, World};
+use std::{env, sync::Once};
+use winit::WindowBuilder;
+
+// the working directory is process wide and tests run in parallel, so it's set once
+// for all of them, the logger writes into it
+static WORKING_DIRECTORY: Once = Once::new();
+
+fn game<'a, 'b>(world_access: impl FnOnce(&mut World)) -> (Game<'a, 'b>, NullRenderer) {
+    game_with_window(world_access, |window_builder| window_builder)
+}
+
+// the null renderer shares its recorded frames with the one inside the game
+fn game_with_window<'a, 'b>(
+    world_access: impl FnOnce(&mut World),
+    window_builder: impl FnOnce(WindowBuilder) -> WindowBuilder,
+) -> (Game<'a, 'b>, NullRenderer) {
+    WORKING_DIRECTORY.call_once(|| env::set_current_dir(env::temp_dir()).unwrap());
+
+    let null_renderer = NullRenderer::new();
+    let game = Game::with_backend(
+        "test",
+        Backend::Null(null_renderer.clone()),
+        world_access,
+        |dispatcher| dispatcher,
+        |res_builder| res_builder,
+        window_builder,
+    )
+    .unwrap();
+
+    (game, null_renderer)
+}
+
+#[test]
+fn records_draw_calls() {
+    let (mut game, null_renderer) = game_with_window(
+        |world| {
+            world
+                .create_entity()
+                .with(Translation3(geometry::Translation3::new(1.0f32, 2.0, 3.0)))
+                .with(Material::new("shader_normal"))
+                .build();
+
+            // not drawn without a material
+            world
+                .create_entity()
+                .with(Translation3(geometry::Translation3::new(0.0f32, 0.0, 2.0)))
+                .with(Camera::perspective(std::f32::consts::FRAC_PI_3, 0.1, 100.0))
+                .build();
+        },
+        |window_builder| window_builder.with_dimensions((640, 480).into()),
+    );
+
+    game.step();
+    game.screenshot();
+    game.step();
+
+    let frames = null_renderer.frames();
+    assert_eq!(frames.len(), 2);
+    assert!(!frames[0].screenshot);
+    assert!(frames[1].screenshot);
+
+    let frame = &frames[1];
+    assert_eq!(frame.window_size, (640, 480).into());
+    assert_eq!(frame.draws.len(), 1);
+    assert_eq!(frame.draws[0].0, "shader_normal");
+    assert_eq!(frame.draws[0].1.column(3).xyz(), [1.0, 2.0, 3.0].into());
+
+    // the null backend has no passes to time
+    let timings = game.world.read_resource::<Profiler>().timings();
+    let systems = timings
+        .systems
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(systems, ["camera", "event_handler", "renderer"]);
+    assert!(timings.passes.is_empty());
+}
+
+#[test]
+fn records_sprites() {
+    let (mut game, null_renderer) = game(|world| {
+        let mut sprite = Sprite::new("player", 2.0, 1.0);
+        sprite.layer = 1;
+        world
+            .create_entity()
+            .with(Translation2(geometry::Translation2::new(4.0f32, 5.0)))
+            .with(Rotation2(geometry::Rotation2::new(0.5f32)))
+            .with(sprite)
+            .build();
+
+        // not drawn without a translation
+        world
+            .create_entity()
+            .with(Sprite::new("tree", 1.0, 1.0))
+            .build();
+    });
+
+    game.step();
+
+    let frame = null_renderer.last_frame().unwrap();
+    assert!(frame.draws.is_empty());
+    assert_eq!(frame.sprites.len(), 1);
+
+    let (sprite, position, rotation) = &frame.sprites[0];
+    assert_eq!(sprite.texture, "player");
+    assert_eq!(sprite.layer, 1);
+    assert_eq!(*position, [4.0, 5.0].into());
+    assert!((rotation - 0.5).abs() < 1e-6);
+}
+
+#[test]
+fn records_text() {
+    let (mut game, null_renderer) = game(|world| {
+        let mut label = Text::world("Hello", "sans", 0.5);
+        label.align = TextAlign::Center;
+        world
+            .create_entity()
+            .with(Translation2(geometry::Translation2::new(1.0f32, 2.0)))
+            .with(label)
+            .build();
+
+        world
+            .create_entity()
+            .with(Translation2(geometry::Translation2::new(8.0f32, 8.0)))
+            .with(Text::screen("Score: 0", "sans", 16.0))
+            .build();
+    });
+
+    game.step();
+
+    let mut texts = null_renderer.last_frame().unwrap().texts;
+    assert_eq!(texts.len(), 2);
+    texts.sort_by(|a, b| a.0.text.cmp(&b.0.text));
+
+    let (label, position) = &texts[0];
+    assert_eq!(label.text, "Hello");
+    assert_eq!(label.space, TextSpace::World);
+    assert_eq!(label.align, TextAlign::Center);
+    assert_eq!(*position, [1.0, 2.0].into());
+
+    let (score, position) = &texts[1];
+    assert_eq!(score.space, TextSpace::Screen);
+    assert_eq!(*position, [8.0, 8.0].into());
+}
+
+#[test]
+fn records_debug_draw() {
+    let (mut game, null_renderer) = game(|_| ());
+
+    let white = [1.0, 1.0, 1.0, 1.0].into();
+
+    // disabled outside of --dev
+    game.world
+        .read_resource::<DebugDraw>()
+        .line([0.0; 3].into(), [1.0; 3].into(), white);
+    game.step();
+    assert!(null_renderer.last_frame().unwrap().debug_lines.is_empty());
+
+    game.world.add_resource(DebugDraw::new(true));
+    {
+        let debug_draw = game.world.read_resource::<DebugDraw>();
+        debug_draw.aabb([0.0; 3].into(), [1.0; 3].into(), white);
+        debug_draw.arrow([0.0; 3].into(), [0.0, 0.0, 2.0].into(), white);
+        debug_draw.label([0.0; 3].into(), "origin", white);
+    }
+    game.step();
+
+    let frame = null_renderer.last_frame().unwrap();
+    assert_eq!(frame.debug_lines.len(), 12 + 5);
+    assert_eq!(frame.debug_labels.len(), 1);
+    assert_eq!(frame.debug_labels[0].text, "origin");
+
+    // cleared after every frame
+    game.step();
+    let frame = null_renderer.last_frame().unwrap();
+    assert!(frame.debug_lines.is_empty());
+    assert!(frame.debug_labels.is_empty());
+}
+
+#[test]
+fn records_dev_ui() {
+    let (mut game, null_renderer) = game(|_| ());
+
+    // only built with --dev
+    game.step();
+    assert!(null_renderer.last_frame().unwrap().ui.is_none());
+
+    // the system isn't in the dispatcher without --dev
+    game.world.add_resource(DevUi::new(true, 1.0));
+    DevUiSystem.run_now(&game.world.res);
+    game.step();
+
+    // the font atlas comes with the first frame
+    let ui = null_renderer.last_frame().unwrap().ui.unwrap();
+    assert!(!ui.textures.set.is_empty());
+
+    // egui windows are sized during their first frame and drawn from the second one on
+    DevUiSystem.run_now(&game.world.res);
+    game.step();
+    let ui = null_renderer.last_frame().unwrap().ui.unwrap();
+    assert!(!ui.primitives.is_empty());
+
+    game.world.write_resource::<DevUi>().set_visible(false);
+    DevUiSystem.run_now(&game.world.res);
+    game.step();
+    assert!(null_renderer.last_frame().unwrap().ui.is_none());
+}
+
+#[test]
+fn keeps_the_latest_frames() {
+    let (mut game, null_renderer) = game(|_| ());
+
+    for _ in 0..100 {
+        game.step();
+    }
+    game.screenshot();
+    game.step();
+
+    let frames = null_renderer.frames();
+    assert_eq!(frames.len(), 64);
+    assert!(frames.last().unwrap().screenshot);
+
+    null_renderer.clear();
+    assert!(null_renderer.last_frame().is_none());
+}